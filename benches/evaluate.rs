@@ -0,0 +1,57 @@
+//! Calculating one formula for many values, by each way there is to do it.
+//! Run with `cargo bench`.
+
+#[macro_use]
+extern crate criterion;
+extern crate infix_calculator;
+
+use std::collections::HashMap;
+
+use criterion::Criterion;
+use infix_calculator::{Expr, RPNQueue};
+
+const FORMULA: &str = "3 * x ^ 2 - sin(x) / (1 + y) + max(x, y) * pi";
+const POINTS: usize = 1000;
+
+fn points() -> Vec<(f64, f64)> {
+    (0..POINTS).map(|i| (i as f64 / 100.0, 1.0 - i as f64 / 500.0)).collect()
+}
+
+fn evaluate(c: &mut Criterion) {
+    let points = points();
+    let queue = RPNQueue::from_infix_string(FORMULA).unwrap();
+    let mut group = c.benchmark_group("evaluate");
+
+    group.bench_function("rpn tokens", |b| {
+        let mut vars = HashMap::new();
+        b.iter(|| {
+            points.iter().map(|&(x, y)| {
+                vars.insert("x".to_string(), x);
+                vars.insert("y".to_string(), y);
+                queue.calculate_with_vars(&vars).unwrap()
+            }).sum::<f64>()
+        })
+    });
+
+    let bytecode = queue.compile(&["x", "y"]).unwrap();
+    group.bench_function("bytecode", |b| {
+        b.iter(|| points.iter().map(|&(x, y)| bytecode.calculate(&[x, y]).unwrap()).sum::<f64>())
+    });
+
+    let closure = Expr::from_rpn(&queue).unwrap().compile(&["x", "y"]).unwrap();
+    group.bench_function("closures", |b| {
+        b.iter(|| points.iter().map(|&(x, y)| closure(&[x, y]).unwrap()).sum::<f64>())
+    });
+
+    #[cfg(feature = "jit")]
+    {
+        let jit = bytecode.jit().unwrap();
+        group.bench_function("jit", |b| {
+            b.iter(|| points.iter().map(|&(x, y)| jit.calculate(&[x, y]).unwrap()).sum::<f64>())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, evaluate);
+criterion_main!(benches);
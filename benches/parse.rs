@@ -0,0 +1,37 @@
+//! Parsing short expressions, the common case. Run with `cargo bench`.
+//!
+//! `inline` is `RPNQueue::from_infix_string`, which scans into borrowed
+//! tokens kept inline for short expressions. `owned tokens` parses the same
+//! input from `tokenize_spanned`, a `Vec` of tokens owning their names, the
+//! way the parser was given its input before. Both write the `RPNQueue` out
+//! the same way, a `String` for each entry.
+
+#[macro_use]
+extern crate criterion;
+extern crate infix_calculator;
+
+use criterion::Criterion;
+use infix_calculator::{tokenize_spanned, RPNQueue};
+
+const SHORT: &[&str] = &["1 + 2", "3 * (4 - 1)", "-x ^ 2 / 2", "sqrt(16) + 2!", "max(1, 2, 3) % 2"];
+const LONG: &str = "((1 + 2) * 3 - 4 / (5 + 6)) ^ 2 + sin(x) * cos(y) - max(a, b, c) / sqrt(d ^ 2 + e ^ 2) + 7 % 3";
+
+fn inline(input: &str) -> usize {
+    RPNQueue::from_infix_string(input).unwrap().len()
+}
+
+fn owned_tokens(input: &str) -> usize {
+    RPNQueue::from_tokens(tokenize_spanned(input).unwrap()).unwrap().len()
+}
+
+fn parse(c: &mut Criterion) {
+    for &(name, parse) in &[("inline", inline as fn(&str) -> usize), ("owned tokens", owned_tokens)] {
+        let mut group = c.benchmark_group(name);
+        group.bench_function("short", |b| b.iter(|| SHORT.iter().map(|input| parse(input)).sum::<usize>()));
+        group.bench_function("long", |b| b.iter(|| parse(LONG)));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,25 @@
+/* The functions of src/ffi.rs, regenerated with cbindgen.toml. Build the library with
+ * `cargo rustc --lib --crate-type cdylib --release --features ffi`. */
+
+#ifndef INFIX_CALCULATOR_H
+#define INFIX_CALCULATOR_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Calculates the NUL-terminated UTF-8 string `input`, storing the result
+ * in `*result`. Returns 0 on success, and -1 with `*result` left as it
+ * was if it fails, see `infix_calc_last_error`. */
+int infix_calc_eval(const char *input, double *result);
+
+/* The message of the error of the last call on this thread that failed,
+ * or NULL if it succeeded. The library owns the string: do not free it,
+ * and copy it to keep it past the next call on the same thread. */
+const char *infix_calc_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* INFIX_CALCULATOR_H */
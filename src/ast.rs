@@ -0,0 +1,563 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use {compute_result, factorial, hash_f64, is_comparison, is_identifier, parse_call, short_circuits, whole, CalcError, Calculator, Entry, Environment, Output, RPNQueue, Scope};
+
+/// Operators taking a single operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum UnaryOp {
+    /// Prefix `-`
+    Neg,
+    /// Postfix `!`
+    Factorial,
+    /// Prefix `!`, 1 for 0 and 0 for anything else
+    Not,
+    /// Prefix `~`, flipping every bit of an integer
+    BitNot,
+}
+
+impl UnaryOp {
+    /// The token used for this operator in an `RPNQueue`.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            UnaryOp::Neg => "neg",
+            UnaryOp::Factorial => "!",
+            UnaryOp::Not => "not",
+            UnaryOp::BitNot => "~",
+        }
+    }
+
+    /// How the operator is shown in `to_tree`, `to_dot` and `--ast`, with `¬`
+    /// for a logical not so it isn't taken for a factorial.
+    pub fn written(self) -> &'static str {
+        match self {
+            UnaryOp::Neg => "-",
+            UnaryOp::Factorial => "!",
+            UnaryOp::Not => "¬",
+            UnaryOp::BitNot => "~",
+        }
+    }
+
+    pub(crate) fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "neg" => Some(UnaryOp::Neg),
+            "!" => Some(UnaryOp::Factorial),
+            "not" => Some(UnaryOp::Not),
+            "~" => Some(UnaryOp::BitNot),
+            _ => None,
+        }
+    }
+}
+
+/// Operators taking a left and a right operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    /// `==`, like the other comparisons 1 if it holds and 0 if it doesn't
+    Eq,
+    /// `!=`
+    Ne,
+    Lt,
+    /// `<=`
+    Le,
+    Gt,
+    /// `>=`
+    Ge,
+    /// `&&`, calculating the right operand only if the left one isn't 0
+    And,
+    /// `||`, calculating the right operand only if the left one is 0
+    Or,
+    /// `&`, like `|` and `xor` only defined for integers
+    BitAnd,
+    /// `|`
+    BitOr,
+    /// `xor`
+    BitXor,
+    /// `±`, a measurement and its uncertainty
+    PlusMinus,
+}
+
+impl BinaryOp {
+    /// The token used for this operator in an `RPNQueue`.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::Pow => "^",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::BitXor => "xor",
+            BinaryOp::PlusMinus => "±",
+        }
+    }
+
+    /// Whether it is one of `==`, `!=`, `<`, `<=`, `>` and `>=`.
+    pub fn is_comparison(self) -> bool {
+        is_comparison(self.symbol())
+    }
+
+    /// Whether `first op second` holds, `None` if it isn't a comparison.
+    pub(crate) fn compare<T: PartialOrd>(self, first: T, second: T) -> Option<bool> {
+        match self {
+            BinaryOp::Eq => Some(first == second),
+            BinaryOp::Ne => Some(first != second),
+            BinaryOp::Lt => Some(first < second),
+            BinaryOp::Le => Some(first <= second),
+            BinaryOp::Gt => Some(first > second),
+            BinaryOp::Ge => Some(first >= second),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "+" => Some(BinaryOp::Add),
+            "-" => Some(BinaryOp::Sub),
+            "*" => Some(BinaryOp::Mul),
+            "/" => Some(BinaryOp::Div),
+            "%" => Some(BinaryOp::Rem),
+            "^" => Some(BinaryOp::Pow),
+            "==" => Some(BinaryOp::Eq),
+            "!=" => Some(BinaryOp::Ne),
+            "<" => Some(BinaryOp::Lt),
+            "<=" => Some(BinaryOp::Le),
+            ">" => Some(BinaryOp::Gt),
+            ">=" => Some(BinaryOp::Ge),
+            "&&" => Some(BinaryOp::And),
+            "||" => Some(BinaryOp::Or),
+            "&" => Some(BinaryOp::BitAnd),
+            "|" => Some(BinaryOp::BitOr),
+            "xor" => Some(BinaryOp::BitXor),
+            "±" => Some(BinaryOp::PlusMinus),
+            _ => None,
+        }
+    }
+}
+
+/// A parsed expression tree.
+///
+/// With the `serde` feature each node is serialized as an object naming its
+/// kind, e.g. `{"binary": ["add", {"number": 1.0}, {"variable": "x"}]}`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Expr {
+    Number(f64),
+    /// A variable or a constant such as `pi`
+    Variable(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    /// A call to a built-in or user defined function
+    Call(String, Vec<Expr>),
+}
+
+impl Expr {
+    /// Parses an infix expression into a tree. Accepts the same syntax as
+    /// `RPNQueue::from_infix_string`.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::{BinaryOp, Expr};
+    ///
+    /// let expr = Expr::parse("1 + 2 * 3").unwrap();
+    /// assert_eq!(expr, Expr::Binary(
+    ///     BinaryOp::Add,
+    ///     Box::new(Expr::Number(1.0)),
+    ///     Box::new(Expr::Binary(BinaryOp::Mul, Box::new(Expr::Number(2.0)), Box::new(Expr::Number(3.0)))),
+    /// ));
+    /// ```
+    pub fn parse(input: &str) -> Result<Self, CalcError> {
+        Calculator::standard().parse_expr(input)
+    }
+
+    /// Builds the tree an `RPNQueue` describes.
+    pub fn from_rpn(queue: &RPNQueue) -> Result<Self, CalcError> {
+        let mut operands: Vec<Expr> = Vec::new();
+        for token in queue.iter() {
+            let expr = if let Some(op) = BinaryOp::from_symbol(token) {
+                let right = operands.pop().ok_or(CalcError::NotEnoughOperands)?;
+                let left = operands.pop().ok_or(CalcError::NotEnoughOperands)?;
+                Expr::Binary(op, Box::new(left), Box::new(right))
+            } else if let Some(op) = UnaryOp::from_symbol(token) {
+                let operand = operands.pop().ok_or(CalcError::NotEnoughOperands)?;
+                Expr::Unary(op, Box::new(operand))
+            } else if let Some((name, arity)) = parse_call(token) {
+                if operands.len() < arity {
+                    return Err(CalcError::NotEnoughOperands);
+                }
+                let arguments = operands.split_off(operands.len() - arity);
+                Expr::Call(name.to_string(), arguments)
+            } else if is_identifier(token) {
+                Expr::Variable(token.to_string())
+            } else {
+                let number = token.parse::<f64>().or(Err(CalcError::InvalidRpnToken(token.to_string())))?;
+                Expr::Number(number)
+            };
+            operands.push(expr);
+        }
+
+        let expr = operands.pop().ok_or(CalcError::NotEnoughOperands)?;
+        if !operands.is_empty() {
+            return Err(CalcError::TooManyOperands);
+        }
+        Ok(expr)
+    }
+
+    /// Flattens the tree back into Reverse Polish Notation.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::Expr;
+    ///
+    /// let expr = Expr::parse("max(1, 2) ^ 2").unwrap();
+    /// assert_eq!(expr.to_rpn().tokens(), vec!["1", "2", "max(2)", "2", "^"]);
+    /// ```
+    pub fn to_rpn(&self) -> RPNQueue {
+        let mut output = Vec::new();
+        self.push_rpn(&mut output);
+        RPNQueue::from_vec(output)
+    }
+
+    fn push_rpn(&self, output: &mut Vec<String>) {
+        match *self {
+            Expr::Number(number) => output.push(number.to_string()),
+            Expr::Variable(ref name) => output.push(name.clone()),
+            Expr::Unary(op, ref operand) => {
+                operand.push_rpn(output);
+                output.push(op.symbol().to_string());
+            }
+            Expr::Binary(op, ref left, ref right) => {
+                left.push_rpn(output);
+                right.push_rpn(output);
+                output.push(op.symbol().to_string());
+            }
+            Expr::Call(ref name, ref arguments) => {
+                for argument in arguments {
+                    argument.push_rpn(output);
+                }
+                output.push(format!("{}({})", name, arguments.len()));
+            }
+        }
+    }
+
+    /// Calculate result for the expression, see `RPNQueue::calculate`.
+    pub fn calculate(&self) -> Result<f64, CalcError> {
+        self.calculate_with(&Environment::new())
+    }
+
+    /// Calculate result for the expression, resolving names through `environment`.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::{Environment, Expr};
+    ///
+    /// let mut environment = Environment::new();
+    /// environment.set("x", 3.0);
+    ///
+    /// let expr = Expr::parse("x ^ 2 - 1").unwrap();
+    /// assert_eq!(expr.calculate_with(&environment).unwrap(), 8.0);
+    /// ```
+    pub fn calculate_with(&self, environment: &Environment) -> Result<f64, CalcError> {
+        self.evaluate(&Scope::new(environment.variables(), environment.functions()))
+    }
+
+    fn evaluate(&self, scope: &Scope) -> Result<f64, CalcError> {
+        match *self {
+            Expr::Number(number) => Ok(number),
+            Expr::Variable(ref name) => scope.lookup(name),
+            Expr::Unary(UnaryOp::Neg, ref operand) => Ok(-operand.evaluate(scope)?),
+            Expr::Unary(UnaryOp::Factorial, ref operand) => factorial(operand.evaluate(scope)?),
+            Expr::Unary(UnaryOp::Not, ref operand) => Ok((operand.evaluate(scope)? == 0.0) as u8 as f64),
+            Expr::Unary(UnaryOp::BitNot, ref operand) => Ok(!whole(operand.evaluate(scope)?)? as f64),
+            Expr::Binary(op @ BinaryOp::And, ref left, ref right) | Expr::Binary(op @ BinaryOp::Or, ref left, ref right) => {
+                let left = left.evaluate(scope)? != 0.0;
+                if short_circuits(op.symbol(), left) {
+                    return Ok(left as u8 as f64);
+                }
+                Ok((right.evaluate(scope)? != 0.0) as u8 as f64)
+            }
+            Expr::Binary(op, ref left, ref right) => {
+                compute_result(left.evaluate(scope)?, right.evaluate(scope)?, op.symbol())
+            }
+            Expr::Call(ref name, ref arguments) => {
+                let arguments = arguments.iter()
+                    .map(|argument| argument.evaluate(scope))
+                    .collect::<Result<Vec<f64>, CalcError>>()?;
+                scope.call(name, arguments)
+            }
+        }
+    }
+
+    /// How tightly the expression binds once written out as maths, which
+    /// is where the serializers over the tree put brackets.
+    pub(crate) fn level(&self) -> Level {
+        match *self {
+            Expr::Number(number) if number < 0.0 => Level::Negation,
+            Expr::Unary(UnaryOp::Neg, _) | Expr::Unary(UnaryOp::Not, _) | Expr::Unary(UnaryOp::BitNot, _) => Level::Negation,
+            Expr::Binary(BinaryOp::Or, ..) => Level::Or,
+            Expr::Binary(BinaryOp::And, ..) => Level::And,
+            Expr::Binary(op, ..) if op.is_comparison() => Level::Comparison,
+            Expr::Binary(BinaryOp::BitOr, ..) => Level::BitOr,
+            Expr::Binary(BinaryOp::BitXor, ..) => Level::BitXor,
+            Expr::Binary(BinaryOp::BitAnd, ..) => Level::BitAnd,
+            Expr::Binary(BinaryOp::Add, ..) | Expr::Binary(BinaryOp::Sub, ..) => Level::Sum,
+            Expr::Binary(BinaryOp::Mul, ..) | Expr::Binary(BinaryOp::Rem, ..) => Level::Product,
+            // fractions and powers read as one unit, but not as a base
+            Expr::Binary(BinaryOp::Div, ..) | Expr::Binary(BinaryOp::Pow, ..) => Level::Power,
+            Expr::Call(ref name, _) if name == "pow" => Level::Power,
+            Expr::Binary(BinaryOp::PlusMinus, ..) => Level::Measurement,
+            _ => Level::Atom,
+        }
+    }
+}
+
+/// See `Expr::level`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Level {
+    Or,
+    And,
+    Comparison,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Sum,
+    Product,
+    Negation,
+    Power,
+    /// `±`, which binds tighter than `^`
+    Measurement,
+    Atom,
+}
+
+impl Level {
+    pub(crate) fn next(self) -> Level {
+        match self {
+            Level::Or => Level::And,
+            Level::And => Level::Comparison,
+            Level::Comparison => Level::BitOr,
+            Level::BitOr => Level::BitXor,
+            Level::BitXor => Level::BitAnd,
+            Level::BitAnd => Level::Sum,
+            Level::Sum => Level::Product,
+            Level::Product => Level::Negation,
+            Level::Negation => Level::Power,
+            Level::Power => Level::Measurement,
+            Level::Measurement | Level::Atom => Level::Atom,
+        }
+    }
+}
+
+/// Builds an `Expr` out of what the parser writes, so `Expr::parse` never
+/// goes through the tokens of an `RPNQueue`. The first problem is kept for
+/// `finish`.
+#[derive(Default)]
+pub(crate) struct Tree {
+    operands: Vec<Expr>,
+    entries: usize,
+    error: Option<CalcError>,
+}
+
+impl Tree {
+    /// The whole expression, which has to be exactly one.
+    pub(crate) fn finish(mut self) -> Result<Expr, CalcError> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        let expr = self.operands.pop().ok_or(CalcError::NotEnoughOperands)?;
+        if !self.operands.is_empty() {
+            return Err(CalcError::TooManyOperands);
+        }
+        Ok(expr)
+    }
+
+    fn operand(&mut self) -> Result<Box<Expr>, CalcError> {
+        self.operands.pop().map(Box::new).ok_or(CalcError::NotEnoughOperands)
+    }
+
+    fn node(&mut self, entry: Entry) -> Result<Expr, CalcError> {
+        Ok(match entry {
+            Entry::Number(number, _) => Expr::Number(number),
+            #[cfg(feature = "complex")]
+            Entry::Imaginary(number) => return Err(CalcError::NotInTree(format!("{}i", number))),
+            Entry::Name(name) => Expr::Variable(name.to_string()),
+            Entry::Operator(symbol) => {
+                if let Some(op) = BinaryOp::from_symbol(symbol) {
+                    let right = self.operand()?;
+                    Expr::Binary(op, self.operand()?, right)
+                } else if let Some(op) = UnaryOp::from_symbol(symbol) {
+                    Expr::Unary(op, self.operand()?)
+                } else {
+                    return Err(CalcError::NotInTree(symbol.to_string()));
+                }
+            }
+            Entry::Call(name, arity) => {
+                if self.operands.len() < arity {
+                    return Err(CalcError::NotEnoughOperands);
+                }
+                Expr::Call(name.to_string(), self.operands.split_off(self.operands.len() - arity))
+            }
+        })
+    }
+}
+
+impl Output for Tree {
+    fn push(&mut self, entry: Entry) {
+        self.entries += 1;
+        if self.error.is_none() {
+            match self.node(entry) {
+                Ok(expr) => self.operands.push(expr),
+                Err(err) => self.error = Some(err),
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries
+    }
+}
+
+impl Hash for Expr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        ::std::mem::discriminant(self).hash(state);
+        match *self {
+            Expr::Number(number) => hash_f64(number, state),
+            Expr::Variable(ref name) => name.hash(state),
+            Expr::Unary(op, ref operand) => {
+                op.hash(state);
+                operand.hash(state);
+            }
+            Expr::Binary(op, ref left, ref right) => {
+                op.hash(state);
+                left.hash(state);
+                right.hash(state);
+            }
+            Expr::Call(ref name, ref arguments) => {
+                name.hash(state);
+                arguments.hash(state);
+            }
+        }
+    }
+}
+
+/// Written as infix, see `RPNQueue::to_infix`.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // a tree always makes one whole expression
+        f.write_str(&self.to_rpn().to_infix().unwrap())
+    }
+}
+
+impl<'a> From<&'a Expr> for RPNQueue {
+    fn from(expr: &'a Expr) -> Self {
+        expr.to_rpn()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Span;
+
+    fn number(n: f64) -> Box<Expr> {
+        Box::new(Expr::Number(n))
+    }
+
+    #[test]
+    fn parse_builds_the_tree() {
+        assert_eq!(Expr::parse("-(2 - 1)!").unwrap(), Expr::Unary(
+            UnaryOp::Neg,
+            Box::new(Expr::Unary(UnaryOp::Factorial, Box::new(Expr::Binary(BinaryOp::Sub, number(2.0), number(1.0))))),
+        ));
+        assert_eq!(Expr::parse("pow(x, 2)").unwrap(), Expr::Call(
+            "pow".to_string(),
+            vec![Expr::Variable("x".to_string()), Expr::Number(2.0)],
+        ));
+        assert!(Expr::parse("1 +").is_err());
+        assert!(Expr::from_rpn(&RPNQueue::from_vec(vec!["1".to_string(), "2".to_string()])).is_err());
+    }
+
+    #[test]
+    fn parse_agrees_with_the_queue() {
+        for input in &["1 - 2 * 3 ^ -x!", "max(1, [a, b]) / -(c % 2)", "!a || b && 3 >= ~c & 1", "f()", "(10 ± 1) ^ 2 - 3 ± x"] {
+            let expr = Expr::parse(input).unwrap();
+            assert_eq!(RPNQueue::from(&expr), RPNQueue::from_infix_string(input).unwrap(), "{}", input);
+        }
+        assert_eq!(Expr::parse("sqrt 4").unwrap_err(), CalcError::MissingBrackets { name: "sqrt".to_string(), span: Span::new(0, 4) });
+        #[cfg(feature = "complex")]
+        assert_eq!(Expr::parse("2i").unwrap_err().to_string(), "Cannot be part of an expression tree: 2i");
+    }
+
+    #[test]
+    fn equal_trees_hash_the_same() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let hash = |expr: &Expr| {
+            let mut hasher = DefaultHasher::new();
+            expr.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&Expr::parse("f(x) * 2").unwrap()), hash(&Expr::parse("(f(x)) * 2").unwrap()));
+        assert_ne!(hash(&Expr::parse("f(x) * 2").unwrap()), hash(&Expr::parse("f(x) * 3").unwrap()));
+        assert_eq!(hash(&Expr::Number(0.0)), hash(&Expr::Number(-0.0)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let expr = Expr::parse("-f(x) + 1").unwrap();
+        let json = serde_json::to_string(&expr).unwrap();
+        assert_eq!(json, r#"{"binary":["add",{"unary":["neg",{"call":["f",[{"variable":"x"}]]}]},{"number":1.0}]}"#);
+        assert_eq!(serde_json::from_str::<Expr>(&json).unwrap(), expr);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Expr::parse("(1+x)*max((2),3)!").unwrap().to_string(), "(1 + x) * max(2, 3)!");
+        assert_eq!(Expr::parse("!(a||b)&&!-c").unwrap().to_string(), "!(a || b) && !-c");
+        assert_eq!(Expr::parse("2*(10±1)^2").unwrap().to_string(), "2 * 10 ± 1 ^ 2");
+        // a measurement needs a `Calculator`, like in an `RPNQueue`
+        assert_eq!(Expr::parse("10 ± 1").unwrap().calculate(), Err(CalcError::UnknownOperator("±".to_string())));
+    }
+
+    #[test]
+    fn rpn_round_trip() {
+        for input in &["1.5 + 3 - (4 / 5)", "2 ^ 3 ^ 2", "-x! % 3", "max(1, min(2, 3), f(x)) * pi"] {
+            let rpn = RPNQueue::from_infix_string(input).unwrap();
+            let expr = Expr::parse(input).unwrap();
+            assert_eq!(RPNQueue::from(&expr).tokens(), rpn.tokens());
+        }
+    }
+
+    #[test]
+    fn calculate_matches_rpn() {
+        let mut environment = Environment::new();
+        environment.eval("x = 4").unwrap();
+        environment.eval("f(a) = a * x").unwrap();
+        for input in &["1.0 + 3 - (4 / 5)", "2 ^ 3 ^ 2", "-x! % 5", "max(1, sqrt(x), f(2))", "x > 5 && (-x)! || !x"] {
+            let expected = RPNQueue::from_infix_string(input).unwrap().calculate_with(&environment).unwrap();
+            assert_eq!(Expr::parse(input).unwrap().calculate_with(&environment).unwrap(), expected);
+        }
+    }
+}
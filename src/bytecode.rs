@@ -0,0 +1,222 @@
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use {compute_result, factorial, is_variable, parse_call, right_operands, short_circuits, whole, BinaryOp, CalcError, Calculator, Function, RPNQueue, UnaryOp};
+
+/// One instruction of a `CompiledExpr`, working on a stack of numbers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Op {
+    /// Pushes an entry of the constant pool
+    Constant(usize),
+    /// Pushes the value of the variable at this position
+    Variable(usize),
+    Unary(UnaryOp),
+    Binary(BinaryOp),
+    /// Calls an entry of the function table with this many arguments
+    Call(usize, usize),
+    /// Before the right operand of `&&` or `||`: if the number on top
+    /// decides the result, it is replaced by that and the code carries on at
+    /// this position, past the operator
+    ShortCircuit(BinaryOp, usize),
+}
+
+/// An `RPNQueue` compiled to instructions for a small stack machine, see
+/// `RPNQueue::compile`.
+#[derive(Debug, Clone)]
+pub struct CompiledExpr {
+    pub(crate) code: Vec<Op>,
+    pub(crate) constants: Vec<f64>,
+    pub(crate) functions: Vec<Function>,
+    variables: Vec<String>,
+    /// The most numbers on the stack at once
+    pub(crate) depth: usize,
+}
+
+impl RPNQueue {
+    /// Compiles the queue into bytecode taking the values of `variables`,
+    /// in the same order. Every token is read once here, so calculating the
+    /// result doesn't look at a string again.
+    ///
+    /// Gives `CalcError::UnknownVariable` for a name that is neither one of
+    /// `variables` nor a constant such as `pi`, and `UnknownFunction` for a
+    /// call to anything but a built-in function.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::RPNQueue;
+    ///
+    /// let queue = RPNQueue::from_infix_string("a * x ^ 2 + 1").unwrap();
+    /// let compiled = queue.compile(&["a", "x"]).unwrap();
+    /// assert_eq!(compiled.calculate(&[2.0, 3.0]).unwrap(), 19.0);
+    /// ```
+    pub fn compile(&self, variables: &[&str]) -> Result<CompiledExpr, CalcError> {
+        let calculator = Calculator::standard();
+        let mut compiled = CompiledExpr {
+            code: Vec::with_capacity(self.len()),
+            constants: Vec::new(),
+            functions: Vec::new(),
+            variables: variables.iter().map(|variable| variable.to_string()).collect(),
+            depth: 0,
+        };
+        let mut depth = 0;
+        let skips = right_operands(self.tokens(), &calculator.operators);
+        // where each `ShortCircuit` is, until its operator is compiled
+        let mut jumps: Vec<(usize, usize)> = Vec::new();
+        for (i, token) in self.iter().enumerate() {
+            if let Some(end) = skips[i] {
+                let op = BinaryOp::from_symbol(&self.tokens()[end]).expect("`&&` or `||`");
+                jumps.push((end, compiled.code.len()));
+                compiled.code.push(Op::ShortCircuit(op, 0));
+            }
+            let (op, popped) = if token == "±" {
+                // only a `Calculator` knows what to do with measurements
+                return Err(CalcError::UnknownOperator(token.to_string()));
+            } else if let Some(op) = BinaryOp::from_symbol(token) {
+                (Op::Binary(op), 2)
+            } else if let Some(op) = UnaryOp::from_symbol(token) {
+                (Op::Unary(op), 1)
+            } else if let Some((name, arity)) = parse_call(token) {
+                let function = calculator.functions.get(name).ok_or_else(|| CalcError::UnknownFunction(name.to_string()))?;
+                if !function.accepts(arity) {
+                    return Err(CalcError::WrongArgumentCount { function: name.to_string(), count: arity });
+                }
+                compiled.functions.push(function.clone());
+                (Op::Call(compiled.functions.len() - 1, arity), arity)
+            } else if is_variable(token) {
+                match variables.iter().position(|variable| variable == &token) {
+                    Some(index) => (Op::Variable(index), 0),
+                    None => {
+                        let value = calculator.constants.get(token).ok_or_else(|| CalcError::UnknownVariable(token.to_string()))?;
+                        (compiled.constant(*value), 0)
+                    }
+                }
+            } else {
+                let number = token.parse().map_err(|_| CalcError::InvalidRpnToken(token.to_string()))?;
+                (compiled.constant(number), 0)
+            };
+            if depth < popped {
+                return Err(CalcError::NotEnoughOperands);
+            }
+            depth = depth - popped + 1;
+            compiled.depth = compiled.depth.max(depth);
+            compiled.code.push(op);
+            if let Some(index) = jumps.iter().position(|&(end, _)| end == i) {
+                let (_, at) = jumps.remove(index);
+                if let Op::ShortCircuit(op, _) = compiled.code[at] {
+                    compiled.code[at] = Op::ShortCircuit(op, compiled.code.len());
+                }
+            }
+        }
+        if depth == 0 {
+            return Err(CalcError::NotEnoughOperands);
+        }
+        Ok(compiled)
+    }
+}
+
+/// `compile` checked there is always enough on the stack.
+fn pop(stack: &mut Vec<f64>) -> f64 {
+    stack.pop().expect("stack checked by compile")
+}
+
+impl CompiledExpr {
+    fn constant(&mut self, value: f64) -> Op {
+        self.constants.push(value);
+        Op::Constant(self.constants.len() - 1)
+    }
+
+    /// The names `calculate` takes values for, in order.
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    /// Calculates the result with `values` for the variables. Giving the
+    /// wrong number of values is a `CalcError::WrongArgumentCount`.
+    pub fn calculate(&self, values: &[f64]) -> Result<f64, CalcError> {
+        if values.len() != self.variables.len() {
+            return Err(CalcError::WrongArgumentCount { function: "compiled expression".to_string(), count: values.len() });
+        }
+        let mut stack: Vec<f64> = Vec::with_capacity(self.depth);
+        let mut next = 0;
+        while let Some(&op) = self.code.get(next) {
+            next += 1;
+            let result = match op {
+                Op::Constant(index) => self.constants[index],
+                Op::Variable(index) => values[index],
+                Op::Unary(UnaryOp::Neg) => -pop(&mut stack),
+                Op::Unary(UnaryOp::Factorial) => factorial(pop(&mut stack))?,
+                Op::Unary(UnaryOp::Not) => (pop(&mut stack) == 0.0) as u8 as f64,
+                Op::Unary(UnaryOp::BitNot) => !whole(pop(&mut stack))? as f64,
+                Op::Binary(op) => {
+                    let (second, first) = (pop(&mut stack), pop(&mut stack));
+                    match op {
+                        BinaryOp::Add => first + second,
+                        BinaryOp::Sub => first - second,
+                        BinaryOp::Mul => first * second,
+                        BinaryOp::Div => first / second,
+                        BinaryOp::Rem => first % second,
+                        BinaryOp::Pow => first.powf(second),
+                        op => compute_result(first, second, op.symbol())?,
+                    }
+                }
+                Op::Call(index, arity) => {
+                    let start = stack.len() - arity;
+                    let result = self.functions[index].call(&stack[start..]);
+                    stack.truncate(start);
+                    result
+                }
+                Op::ShortCircuit(op, end) => {
+                    let left = *stack.last().expect("stack checked by compile") != 0.0;
+                    if !short_circuits(op.symbol(), left) {
+                        continue;
+                    }
+                    pop(&mut stack);
+                    next = end;
+                    left as u8 as f64
+                }
+            };
+            stack.push(result);
+        }
+        Ok(pop(&mut stack))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use {CalcError, RPNQueue};
+
+    #[test]
+    fn same_as_calculating() {
+        let queue = RPNQueue::from_infix_string("x * y - max(x, y, 2) / 3 + sqrt(abs(y)) % 2 ^ x - (x + 4)! + pow(y, 2) * pi").unwrap();
+        let compiled = queue.compile(&["x", "y"]).unwrap();
+        assert_eq!(compiled.variables(), &["x", "y"]);
+        let mut vars = HashMap::new();
+        for &(x, y) in &[(-1.0, 2.0), (-3.0, -7.5), (0.0, 0.25), (2.5, 1.0)] {
+            vars.insert("x".to_string(), x);
+            vars.insert("y".to_string(), y);
+            assert_eq!(compiled.calculate(&[x, y]), queue.calculate_with_vars(&vars));
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let queue = RPNQueue::from_infix_string("x + y").unwrap();
+        assert_eq!(queue.compile(&["x"]).unwrap_err(), CalcError::UnknownVariable("y".to_string()));
+        assert_eq!(
+            queue.compile(&["x", "y"]).unwrap().calculate(&[1.0]).unwrap_err(),
+            CalcError::WrongArgumentCount { function: "compiled expression".to_string(), count: 1 }
+        );
+
+        let queue = RPNQueue::from_infix_string("f(2)").unwrap();
+        assert_eq!(queue.compile(&[]).unwrap_err(), CalcError::UnknownFunction("f".to_string()));
+        let queue = RPNQueue::from_infix_string("10 ± 1").unwrap();
+        assert_eq!(queue.compile(&[]).unwrap_err(), CalcError::UnknownOperator("±".to_string()));
+        let queue = RPNQueue::from_vec(vec!["1".to_string(), "+".to_string()]);
+        assert_eq!(queue.compile(&[]).unwrap_err(), CalcError::NotEnoughOperands);
+        assert_eq!(RPNQueue::default().compile(&[]).unwrap_err(), CalcError::NotEnoughOperands);
+        let queue = RPNQueue::from_infix_string("(-1)!").unwrap();
+        assert_eq!(queue.compile(&[]).unwrap().calculate(&[]), Err(CalcError::InvalidFactorial(-1.0)));
+    }
+}
@@ -0,0 +1,188 @@
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use {is_variable, parse_call, validate, CalcError, Calculator, RPNQueue};
+
+/// Written as the first byte, changed whenever the format is.
+const VERSION: u8 = 1;
+
+// The byte each token starts with
+const NUMBER: u8 = 0;
+/// Followed by the operator's index in `OPERATORS`
+const OPERATOR: u8 = 1;
+const CALL: u8 = 2;
+/// Names, custom operators and numbers kept as they were written
+const TEXT: u8 = 3;
+
+/// New operators go at the end, so older encodings keep their meaning
+const OPERATORS: [&str; 22] = [
+    "+", "-", "*", "/", "%", "^", "±", "neg", "!", "==", "!=", "<", "<=", ">", ">=", "&&", "||", "not",
+    "&", "|", "xor", "~",
+];
+
+impl RPNQueue {
+    /// A compact encoding of the queue, for storing it without having to
+    /// parse the expression again. The first byte is the version of the
+    /// format, which `from_bytes` checks.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::RPNQueue;
+    ///
+    /// let queue = RPNQueue::from_infix_string("max(x, 2.5) * 3").unwrap();
+    /// let bytes = queue.to_bytes();
+    /// assert_eq!(RPNQueue::from_bytes(&bytes).unwrap(), queue);
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![VERSION];
+        write_length(&mut bytes, self.len());
+        for token in self {
+            if let Some(index) = OPERATORS.iter().position(|&op| op == token) {
+                bytes.push(OPERATOR);
+                bytes.push(index as u8);
+            } else if let Some((name, arity)) = parse_call(token) {
+                bytes.push(CALL);
+                write_length(&mut bytes, arity);
+                write_text(&mut bytes, name);
+            } else if let Some(number) = token.parse::<f64>().ok().filter(|n| n.to_string() == token) {
+                // only when the number reads back as the same token
+                bytes.push(NUMBER);
+                bytes.extend_from_slice(&number.to_le_bytes());
+            } else {
+                bytes.push(TEXT);
+                write_text(&mut bytes, token);
+            }
+        }
+        bytes
+    }
+
+    /// Reads a queue written by `to_bytes`, checking it is a whole expression
+    /// like `TryFrom<Vec<String>>` does.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CalcError> {
+        let mut reader = Reader { bytes, position: 0 };
+        let version = reader.byte()?;
+        if version != VERSION {
+            return Err(CalcError::UnsupportedVersion(version));
+        }
+        let count = reader.length()?;
+        let mut tokens = Vec::new();
+        // custom operators are only known to the `Calculator` they were
+        // registered on, so queues with them are checked when calculated
+        let mut custom = false;
+        for _ in 0..count {
+            let token = match reader.byte()? {
+                NUMBER => f64::from_le_bytes(reader.array()?).to_string(),
+                TEXT => {
+                    let text = reader.text()?;
+                    custom |= !is_variable(&text) && text.parse::<f64>().is_err();
+                    text
+                }
+                CALL => {
+                    let arity = reader.length()?;
+                    format!("{}({})", reader.text()?, arity)
+                }
+                OPERATOR => {
+                    let index = reader.byte()? as usize;
+                    OPERATORS.get(index).ok_or_else(|| invalid("unknown operator"))?.to_string()
+                }
+                _ => return Err(invalid("unknown kind of token")),
+            };
+            tokens.push(token);
+        }
+        if reader.position != bytes.len() {
+            return Err(invalid("bytes left after the last token"));
+        }
+        if !custom {
+            validate(&tokens, &Calculator::standard().operators)?;
+        }
+        Ok(RPNQueue::from_vec(tokens))
+    }
+}
+
+fn invalid(reason: &str) -> CalcError {
+    CalcError::InvalidBytes(reason.to_string())
+}
+
+/// Lengths and arities are written as LEB128, seven bits a byte.
+fn write_length(bytes: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        bytes.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+fn write_text(bytes: &mut Vec<u8>, text: &str) {
+    write_length(bytes, text.len());
+    bytes.extend_from_slice(text.as_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], CalcError> {
+        let end = self.position.checked_add(n).filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| invalid("ends in the middle of a token"))?;
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, CalcError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn array(&mut self) -> Result<[u8; 8], CalcError> {
+        let mut array = [0; 8];
+        array.copy_from_slice(self.take(8)?);
+        Ok(array)
+    }
+
+    fn length(&mut self) -> Result<usize, CalcError> {
+        let mut n: usize = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            n |= ((byte & 0x7f) as usize).checked_shl(shift).ok_or_else(|| invalid("length too long"))?;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(invalid("length too long"))
+    }
+
+    fn text(&mut self) -> Result<String, CalcError> {
+        let length = self.length()?;
+        let text = self.take(length)?;
+        String::from_utf8(text.to_vec()).map_err(|_| invalid("text is not UTF-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for input in &["1.5 + 3 - (4 / 5)", "-x! ^ 2 ± 0.1", "max(a, min(b, 1e300), f(2)) * pi", "[1, 2] * 3"] {
+            let queue = RPNQueue::from_infix_string(input).unwrap();
+            assert_eq!(RPNQueue::from_bytes(&queue.to_bytes()).unwrap(), queue);
+        }
+
+        let queue = RPNQueue::from_vec(vec!["2.50".to_string(), "1".to_string(), "+".to_string()]);
+        assert_eq!(queue.to_bytes(), [1, 3, 3, 4, b'2', b'.', b'5', b'0', 0, 0, 0, 0, 0, 0, 0, 0xf0, 0x3f, 1, 0]);
+        assert_eq!(RPNQueue::from_bytes(&queue.to_bytes()).unwrap(), queue);
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        let bytes = RPNQueue::from_infix_string("sqrt(x) + 1").unwrap().to_bytes();
+        assert_eq!(RPNQueue::from_bytes(&[]), Err(invalid("ends in the middle of a token")));
+        assert_eq!(RPNQueue::from_bytes(&[2, 0]), Err(CalcError::UnsupportedVersion(2)));
+        assert!(RPNQueue::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(RPNQueue::from_bytes(&[&bytes[..], &[0]].concat()).is_err());
+        assert_eq!(RPNQueue::from_bytes(&[1, 2, 1, 0, 1, 0]), Err(CalcError::NotEnoughOperands));
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::{BTreeMap, HashMap};
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use {is_variable, parse_call, CalcError, Calculator, Environment, RPNQueue, Value};
+
+#[derive(Debug, Clone)]
+struct Entry {
+    parsed: Result<RPNQueue, CalcError>,
+    /// The result, for expressions that don't depend on any name
+    value: Option<Result<Value, CalcError>>,
+    /// When it was last used, its key in `Cache::recent`
+    used: u64,
+}
+
+/// A `Calculator` remembering what it parsed for the inputs it saw last, so
+/// formulas used over and over are only parsed once. When it is full, the
+/// input used least recently is forgotten.
+///
+/// # Example
+/// ```
+/// use infix_calculator::{CachedCalculator, Calculator, Environment, Value};
+///
+/// let mut calculator = CachedCalculator::new(Calculator::new(), 100);
+/// let mut environment = Environment::new();
+/// for x in 0..10 {
+///     environment.set("x", x as f64);
+///     // only parsed the first time
+///     let value = calculator.calculate_with("x ^ 2 + 1", &environment).unwrap();
+///     assert_eq!(value, Value::Float((x * x + 1) as f64));
+/// }
+/// assert_eq!(calculator.len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CachedCalculator {
+    calculator: Calculator,
+    cache_values: bool,
+    cache: Cache,
+}
+
+/// The entries of a `CachedCalculator`, apart from the calculator so
+/// both can be borrowed at once.
+#[derive(Debug, Clone)]
+struct Cache {
+    capacity: usize,
+    entries: HashMap<String, Entry>,
+    /// The inputs in `entries`, least recently used first
+    recent: BTreeMap<u64, String>,
+    clock: u64,
+}
+
+impl Cache {
+    /// The entry for `input`, parsing it unless it is remembered already.
+    fn entry(&mut self, input: &str, calculator: &Calculator) -> Option<&mut Entry> {
+        if self.capacity == 0 {
+            return None;
+        }
+        self.clock += 1;
+        if let Some(entry) = self.entries.get_mut(input) {
+            let key = self.recent.remove(&entry.used).unwrap();
+            self.recent.insert(self.clock, key);
+            entry.used = self.clock;
+            return self.entries.get_mut(input);
+        }
+        if self.entries.len() == self.capacity {
+            let oldest = *self.recent.keys().next().unwrap();
+            let input = self.recent.remove(&oldest).unwrap();
+            self.entries.remove(&input);
+        }
+        let entry = Entry { parsed: calculator.parse(input), value: None, used: self.clock };
+        self.recent.insert(self.clock, input.to_string());
+        Some(self.entries.entry(input.to_string()).or_insert(entry))
+    }
+}
+
+/// Whether `queue` gives the same result in every environment.
+fn is_constant(queue: &RPNQueue, calculator: &Calculator) -> bool {
+    queue.iter().all(|token| match parse_call(token) {
+        Some((name, _)) => calculator.functions.contains_key(name),
+        // even constants such as `pi` can be shadowed by an environment
+        None => !is_variable(token),
+    })
+}
+
+impl CachedCalculator {
+    /// Remembers up to `capacity` inputs parsed with `calculator`.
+    pub fn new(calculator: Calculator, capacity: usize) -> Self {
+        let cache = Cache { capacity, entries: HashMap::new(), recent: BTreeMap::new(), clock: 0 };
+        CachedCalculator { calculator, cache_values: false, cache }
+    }
+
+    pub fn calculator(&self) -> &Calculator {
+        &self.calculator
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cache.capacity
+    }
+
+    /// The number of inputs remembered.
+    pub fn len(&self) -> usize {
+        self.cache.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.entries.is_empty()
+    }
+
+    /// Forgets every input.
+    pub fn clear(&mut self) {
+        self.cache.entries.clear();
+        self.cache.recent.clear();
+    }
+
+    pub fn cache_values(&self) -> bool {
+        self.cache_values
+    }
+
+    /// Whether to also remember the result of expressions without names,
+    /// such as `2 ^ 10`, which then aren't calculated again either. Off by
+    /// default.
+    pub fn set_cache_values(&mut self, cache_values: bool) {
+        self.cache_values = cache_values;
+    }
+
+    /// Parses `input` like `Calculator::parse`, or gives what it gave last time.
+    pub fn parse(&mut self, input: &str) -> Result<RPNQueue, CalcError> {
+        match self.cache.entry(input, &self.calculator) {
+            Some(entry) => entry.parsed.clone(),
+            None => self.calculator.parse(input),
+        }
+    }
+
+    /// Parses and evaluates `input`, see `Calculator::calculate`.
+    pub fn calculate(&mut self, input: &str) -> Result<Value, CalcError> {
+        self.calculate_with(input, &Environment::new())
+    }
+
+    /// Like `calculate`, resolving names through `environment`.
+    pub fn calculate_with(&mut self, input: &str, environment: &Environment) -> Result<Value, CalcError> {
+        let calculator = &self.calculator;
+        let entry = match self.cache.entry(input, calculator) {
+            Some(entry) => entry,
+            None => return calculator.calculate_with(input, environment),
+        };
+        if let Some(ref value) = entry.value {
+            return value.clone();
+        }
+        let queue = match entry.parsed {
+            Ok(ref queue) => queue,
+            Err(ref err) => return Err(err.clone()),
+        };
+        let value = calculator.evaluate(queue, environment);
+        if self.cache_values && is_constant(queue, calculator) {
+            entry.value = Some(value.clone());
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Mode;
+
+    use super::*;
+
+    #[test]
+    fn least_recently_used_is_forgotten() {
+        let mut calculator = CachedCalculator::new(Calculator::new(), 2);
+        assert_eq!(calculator.calculate("1 + 1").unwrap(), Value::Float(2.0));
+        assert_eq!(calculator.calculate("2 + 2").unwrap(), Value::Float(4.0));
+        assert_eq!(calculator.calculate("1 + 1").unwrap(), Value::Float(2.0));
+        assert_eq!(calculator.calculate("3 + 3").unwrap(), Value::Float(6.0));
+        assert_eq!(calculator.len(), 2);
+        assert!(calculator.cache.entries.contains_key("1 + 1"));
+        assert!(!calculator.cache.entries.contains_key("2 + 2"));
+        assert_eq!(calculator.cache.recent.len(), 2);
+
+        calculator.clear();
+        assert!(calculator.is_empty());
+        let mut uncached = CachedCalculator::new(Calculator::new(), 0);
+        assert_eq!(uncached.parse("(1").unwrap_err(), CalcError::UnbalancedParen { position: 0 });
+        assert!(uncached.is_empty());
+    }
+
+    #[test]
+    fn same_results_as_the_calculator() {
+        let inner = Calculator::builder().mode(Mode::Integer).build();
+        let mut calculator = CachedCalculator::new(inner.clone(), 10);
+        calculator.set_cache_values(true);
+        let mut environment = Environment::new();
+        for &x in &[1.0, 2.0] {
+            environment.set("x", x);
+            for input in &["7 / 2", "x * pi", "1 / 0", "max(1, 2", "2 ^ 62 * 2"] {
+                assert_eq!(calculator.calculate_with(input, &environment), inner.calculate_with(input, &environment));
+            }
+        }
+        // names are looked up every time
+        assert!(calculator.cache.entries["7 / 2"].value.is_some());
+        assert!(calculator.cache.entries["x * pi"].value.is_none());
+    }
+}
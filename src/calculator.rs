@@ -0,0 +1,1084 @@
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(feature = "decimal")]
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
+
+#[cfg(feature = "complex")]
+use num_complex::Complex64;
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
+
+#[cfg(feature = "complex")]
+use value::complex_function;
+use value::{fixed_factorial, fixed_integer, fixed_negate, interval_function, uncertain_function};
+use lexer::{scan_at_most, Lexeme, Lexemes, Operator};
+use ast::Tree;
+use optimize::ConstantFolder;
+use {builtin_functions, is_binary_operator, is_identifier, parse, parse_call, right_operands, short_circuits, to_infix, Associativity, CalcError, CancelToken, Environment, Expr, Fold, Function, OperatorInfo, RPNQueue,
+     Value, CONSTANTS, MAX_CALL_DEPTH, OPERATORS};
+
+/// How number literals are read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Mode {
+    /// Every number is an `f64`
+    #[default]
+    Float,
+    /// Literals with a whole value, such as `2`, `1e3` or `0xFF`, are `i64`s.
+    /// See `Value` for how they mix with floats.
+    Integer,
+    /// Literals are exact decimals, so `0.1 + 0.2` is `0.3`. Ones that don't
+    /// fit in a `Decimal` are floats.
+    #[cfg(feature = "decimal")]
+    Decimal,
+}
+
+/// What dividing a float by zero gives, with `/` or `%`. Integers, decimals
+/// and intervals have no infinity so dividing them by zero is always an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ZeroDivision {
+    /// Like `f64`: `1 / 0` is `inf`, `-1 / 0` is `-inf`, `0 / 0` and `1 % 0` are NaN
+    #[default]
+    Infinity,
+    /// A `CalcError::DivisionByZero`
+    Error,
+    /// Always NaN
+    #[cfg_attr(feature = "serde", serde(rename = "nan"))]
+    NaN,
+}
+
+/// What integer mode does with results that don't fit in an `i64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Overflow {
+    /// Carry on with a bigger type, see `Value`
+    #[default]
+    Promote,
+    /// A `CalcError::Overflow`
+    Error,
+    /// Wrap around like `i64::wrapping_add`
+    Wrap,
+    /// Stop at `i64::MIN` or `i64::MAX`
+    Saturate,
+}
+
+/// What happens once a calculation gives NaN or an infinity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum NonFinite {
+    /// Carry on, so everything depending on it is NaN or infinite too
+    #[default]
+    Propagate,
+    /// Stop with a `CalcError::NotFinite` naming the part that gave it
+    Error,
+}
+
+/// Bounds on the input `Calculator::parse` accepts, so untrusted input
+/// can't use up the memory. Every limit is `None`, so unbounded, by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Limits {
+    /// Bytes of input, checked before anything else is done
+    pub max_length: Option<usize>,
+    pub max_tokens: Option<usize>,
+    /// Brackets, including those of calls, open at once
+    pub max_depth: Option<usize>,
+    /// Entries of the `RPNQueue`
+    pub max_output: Option<usize>,
+}
+
+impl Limits {
+    /// The error for the first limit `depth` or `output` is over.
+    pub(crate) fn exceeded(&self, depth: usize, output: usize) -> Option<CalcError> {
+        match (self.max_depth, self.max_output) {
+            (Some(max), _) if depth > max => Some(CalcError::NestingTooDeep(max)),
+            (_, Some(max)) if output > max => Some(CalcError::OutputTooLong(max)),
+            _ => None,
+        }
+    }
+
+    fn check_length(&self, input: &str) -> Result<(), CalcError> {
+        match self.max_length {
+            Some(max) if input.len() > max => Err(CalcError::InputTooLong(max)),
+            _ => Ok(()),
+        }
+    }
+}
+
+type OperatorFunction = dyn Fn(f64, f64) -> f64 + Send + Sync;
+
+/// An operator a `Calculator` knows. Built-in ones are evaluated on their own,
+/// the ones registered with `CalculatorBuilder::operator` have a `function`.
+#[derive(Clone)]
+pub(crate) struct OperatorDefinition {
+    pub(crate) info: OperatorInfo,
+    function: Option<Arc<OperatorFunction>>,
+}
+
+impl fmt::Debug for OperatorDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OperatorDefinition {{ info: {:?}, custom: {} }}", self.info, self.function.is_some())
+    }
+}
+
+/// Evaluates expressions according to a set of options, with its own tables
+/// of operators, functions and constants. `RPNQueue` uses the standard ones.
+///
+/// # Example
+/// ```
+/// use infix_calculator::{Calculator, Mode, Value};
+///
+/// let mut calculator = Calculator::new();
+/// assert_eq!(calculator.calculate("7 / 2").unwrap(), Value::Float(3.5));
+///
+/// calculator.set_mode(Mode::Integer);
+/// assert_eq!(calculator.calculate("7 / 2").unwrap(), Value::Integer(3));
+/// assert_eq!(calculator.calculate("7 / 2 + 0.5").unwrap(), Value::Float(3.5));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Calculator {
+    mode: Mode,
+    zero_division: ZeroDivision,
+    overflow: Overflow,
+    non_finite: NonFinite,
+    fold_constants: bool,
+    cancel_token: Option<CancelToken>,
+    limits: Limits,
+    pub(crate) operators: HashMap<String, OperatorDefinition>,
+    pub(crate) functions: HashMap<String, Function>,
+    pub(crate) constants: HashMap<String, f64>,
+}
+
+impl Default for Calculator {
+    fn default() -> Self {
+        let operators = OPERATORS.iter()
+            .map(|&(symbol, info)| (symbol.to_string(), OperatorDefinition { info, function: None }))
+            .collect();
+        let functions = builtin_functions().into_iter().map(|(name, function)| (name.to_string(), function)).collect();
+        let constants = CONSTANTS.iter().map(|&(name, value)| (name.to_string(), value)).collect();
+        Calculator {
+            mode: Mode::default(),
+            zero_division: ZeroDivision::default(),
+            overflow: Overflow::default(),
+            non_finite: NonFinite::default(),
+            fold_constants: false,
+            cancel_token: None,
+            limits: Limits::default(),
+            operators,
+            functions,
+            constants,
+        }
+    }
+}
+
+impl Calculator {
+    pub fn new() -> Self {
+        Calculator::default()
+    }
+
+    /// The calculator with the default options and tables, used by `RPNQueue`.
+    pub(crate) fn standard() -> &'static Calculator {
+        static STANDARD: OnceLock<Calculator> = OnceLock::new();
+        STANDARD.get_or_init(Calculator::default)
+    }
+
+    /// Starts a `Calculator` with extra functions or options other than the default ones.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::{CalcError, Calculator, Value};
+    ///
+    /// let calculator = Calculator::builder()
+    ///     .function("tax", 1, |args| args[0] * 0.2)
+    ///     .build();
+    /// assert_eq!(calculator.calculate("100 + tax(100)").unwrap(), Value::Float(120.0));
+    /// assert_eq!(calculator.calculate("tax(1, 2)").unwrap_err(),
+    ///            CalcError::WrongArgumentCount { function: "tax".to_string(), count: 2 });
+    /// ```
+    pub fn builder() -> CalculatorBuilder {
+        CalculatorBuilder::default()
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+
+    pub fn zero_division(&self) -> ZeroDivision {
+        self.zero_division
+    }
+
+    pub fn set_zero_division(&mut self, zero_division: ZeroDivision) {
+        self.zero_division = zero_division;
+    }
+
+    pub fn overflow(&self) -> Overflow {
+        self.overflow
+    }
+
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+    }
+
+    pub fn non_finite(&self) -> NonFinite {
+        self.non_finite
+    }
+
+    pub fn set_non_finite(&mut self, non_finite: NonFinite) {
+        self.non_finite = non_finite;
+    }
+
+    pub fn fold_constants(&self) -> bool {
+        self.fold_constants
+    }
+
+    /// Has `parse` work out the parts of expressions that only involve
+    /// numbers, see `Expr::fold_constants`. Only done in `Mode::Float`, the
+    /// other modes would get a different result.
+    pub fn set_fold_constants(&mut self, fold_constants: bool) {
+        self.fold_constants = fold_constants;
+    }
+
+    /// The names of the functions this calculator knows, built-in or added
+    /// with `CalculatorBuilder::function`, in alphabetical order.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::Calculator;
+    ///
+    /// let calculator = Calculator::builder().function("tax", 1, |args| args[0] * 0.2).build();
+    /// let names = calculator.function_names();
+    /// assert!(names.contains(&"sqrt") && names.contains(&"tax"));
+    /// ```
+    pub fn function_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.functions.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// The symbols of the binary operators this calculator knows, built-in or
+    /// added with `CalculatorBuilder::operator`, the ones binding tightest
+    /// first. The unary `-`, `!` and `~` aren't among them.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::{Associativity, Calculator};
+    ///
+    /// let calculator = Calculator::builder().operator("@", 2, Associativity::Left, |a, b| a * 10.0 + b).build();
+    /// let symbols = calculator.operator_symbols();
+    /// assert_eq!(symbols[..6], ["±", "^", "*", "/", "%", "@"]);
+    /// assert_eq!(symbols.last(), Some(&"||"));
+    /// ```
+    pub fn operator_symbols(&self) -> Vec<&str> {
+        let mut symbols: Vec<(&str, &OperatorDefinition)> = self.operators.iter()
+            .filter(|&(symbol, definition)| is_binary_operator(symbol) || definition.function.is_some())
+            .map(|(symbol, definition)| (symbol.as_str(), definition))
+            .collect();
+        // the built-in ones in the order of `OPERATORS` within a precedence
+        symbols.sort_by_key(|&(symbol, definition)| {
+            let builtin = OPERATORS.iter().position(|&(builtin, _)| builtin == symbol).unwrap_or(usize::MAX);
+            (-definition.info.precedence, builtin, symbol)
+        });
+        symbols.into_iter().map(|(symbol, _)| symbol).collect()
+    }
+
+    /// The names of the constants this calculator knows, such as `pi`, in
+    /// alphabetical order.
+    pub fn constant_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.constants.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn cancel_token(&self) -> Option<&CancelToken> {
+        self.cancel_token.as_ref()
+    }
+
+    /// Has evaluations stop with `CalcError::Cancelled` once `cancel_token`
+    /// is cancelled or its deadline has passed.
+    pub fn set_cancel_token(&mut self, cancel_token: Option<CancelToken>) {
+        self.cancel_token = cancel_token;
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Has `parse` reject input that is over any of `limits` with a
+    /// dedicated error such as `CalcError::TooManyTokens`, before it takes
+    /// up the memory.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::{CalcError, Calculator, Limits};
+    ///
+    /// let mut calculator = Calculator::new();
+    /// calculator.set_limits(Limits { max_depth: Some(2), ..Limits::default() });
+    /// assert!(calculator.parse("((1))").is_ok());
+    /// assert_eq!(calculator.parse("(((1)))").unwrap_err(), CalcError::NestingTooDeep(2));
+    /// ```
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub(crate) fn check_cancelled(&self) -> Result<(), CalcError> {
+        self.cancel_token.as_ref().map_or(Ok(()), CancelToken::check)
+    }
+
+    /// Parses `input` like `RPNQueue::from_infix_string`, with the operators
+    /// and functions of this calculator.
+    pub fn parse(&self, input: &str) -> Result<RPNQueue, CalcError> {
+        self.limits.check_length(input)?;
+        let mut errors = Vec::new();
+        let tokens = self.scan(input, &mut errors);
+        match errors.into_iter().next() {
+            Some(err) => Err(err),
+            None => self.parse_tokens(tokens),
+        }
+    }
+
+    /// Whether `input` could be a whole expression rather than the start of
+    /// one that goes on, e.g. on the next line: it isn't if a bracket is
+    /// still open or it ends with an operator such as `+` or with a comma.
+    /// Complete input can still fail to parse.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::Calculator;
+    ///
+    /// let calculator = Calculator::new();
+    /// assert!(!calculator.is_complete("max(1,"));
+    /// assert!(!calculator.is_complete("2 *"));
+    /// assert!(calculator.is_complete("max(1, 2) * 3!"));
+    /// ```
+    pub fn is_complete(&self, input: &str) -> bool {
+        let tokens = self.scan(input, &mut Vec::new());
+        let open = tokens.iter().fold(0isize, |open, &(token, _)| match token {
+            Lexeme::LParen | Lexeme::LBracket => open + 1,
+            Lexeme::RParen | Lexeme::RBracket => open - 1,
+            _ => open,
+        });
+        let continues = match tokens.last() {
+            Some(&(Lexeme::Op(op), _)) => op != Operator::Bang,
+            Some(&(Lexeme::CustomOp(_), _)) | Some(&(Lexeme::Comma, _)) => true,
+            _ => false,
+        };
+        open <= 0 && !continues
+    }
+
+    pub(crate) fn parse_tokens(&self, tokens: Lexemes) -> Result<RPNQueue, CalcError> {
+        let mut errors = Vec::new();
+        let output = RPNQueue::parse(tokens, self, &mut errors);
+        match errors.into_iter().next() {
+            Some(err) => Err(err),
+            None if self.fold_constants && self.mode == Mode::Float => Ok(self.fold(output)),
+            None => Ok(output),
+        }
+    }
+
+    /// `queue` with its constant parts worked out. Queues with something an
+    /// `Expr` can't hold, such as an imaginary number, are left as they are.
+    fn fold(&self, queue: RPNQueue) -> RPNQueue {
+        match Expr::from_rpn(&queue) {
+            Ok(expr) => ConstantFolder { functions: &self.functions }.fold_expr(expr).to_rpn(),
+            Err(_) => queue,
+        }
+    }
+
+    /// Like `parse`, building the tree of the expression instead, see `Expr::parse`.
+    pub(crate) fn parse_expr(&self, input: &str) -> Result<Expr, CalcError> {
+        self.limits.check_length(input)?;
+        let mut errors = Vec::new();
+        let tokens = self.scan(input, &mut errors);
+        if errors.is_empty() {
+            let mut tree = Tree::default();
+            parse(tokens, self, &mut errors, &mut tree);
+            if errors.is_empty() {
+                return tree.finish();
+            }
+        }
+        Err(errors.remove(0))
+    }
+
+    /// Like `parse`, returning every problem found in `input` in the order they appear.
+    pub(crate) fn parse_all(&self, input: &str) -> Result<RPNQueue, Vec<CalcError>> {
+        self.limits.check_length(input).map_err(|err| vec![err])?;
+        let mut errors = Vec::new();
+        let tokens = self.scan(input, &mut errors);
+        let output = RPNQueue::parse(tokens, self, &mut errors);
+        // stable, so errors without a position stay in the order they were found
+        errors.sort_by_key(|err| err.span().map_or(usize::MAX, |span| span.start));
+        if errors.is_empty() {
+            Ok(output)
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn scan<'a>(&self, input: &'a str, errors: &mut Vec<CalcError>) -> Lexemes<'a> {
+        scan_at_most(input, &self.custom_symbols(), self.limits.max_tokens.unwrap_or(usize::MAX), errors)
+    }
+
+    /// The symbols of the operators registered with `CalculatorBuilder::operator`.
+    fn custom_symbols(&self) -> Vec<&str> {
+        self.operators.iter()
+            .filter(|&(_, operator)| operator.function.is_some())
+            .map(|(symbol, _)| symbol.as_str())
+            .collect()
+    }
+
+    /// The function of an operator registered with `CalculatorBuilder::operator`.
+    fn custom_operator(&self, symbol: &str) -> Option<&OperatorFunction> {
+        self.operators.get(symbol).and_then(|operator| operator.function.as_deref())
+    }
+
+    /// Parses and evaluates `input`, see `RPNQueue::from_infix_string` for the syntax.
+    pub fn calculate(&self, input: &str) -> Result<Value, CalcError> {
+        self.calculate_with(input, &Environment::new())
+    }
+
+    /// Like `calculate`, resolving names through `environment`.
+    pub fn calculate_with(&self, input: &str, environment: &Environment) -> Result<Value, CalcError> {
+        self.evaluate(&self.parse(input)?, environment)
+    }
+
+    /// Evaluates an already parsed expression. The functions `environment`
+    /// defines are calculated like this too, with this mode and policies.
+    pub fn evaluate(&self, queue: &RPNQueue, environment: &Environment) -> Result<Value, CalcError> {
+        self.evaluate_in(queue, &Frame { environment, arguments: HashMap::new(), depth: 0 })
+    }
+
+    fn evaluate_in(&self, queue: &RPNQueue, frame: &Frame) -> Result<Value, CalcError> {
+        let mut values: Vec<Value> = Vec::new();
+        let skips = right_operands(queue.tokens(), &self.operators);
+        let mut i = 0;
+        while let Some(x) = queue.get(i) {
+            self.check_cancelled()?;
+            // the right operand of `&&` and `||` only if it is needed
+            if let Some(end) = skips[i] {
+                let left = values.last().and_then(Value::truth);
+                if let Some(left) = left.filter(|&left| short_circuits(&queue.tokens()[end], left)) {
+                    let result = values.pop().unwrap().boolean(left);
+                    values.push(result);
+                    i = end + 1;
+                    continue;
+                }
+            }
+            self.apply(x, &mut values, frame)?;
+            if self.non_finite == NonFinite::Error {
+                if let Some(value) = values.last().filter(|value| !is_finite(value)) {
+                    let expression = self.last_operand(&queue.tokens()[..=i]);
+                    return Err(CalcError::NotFinite { value: value.to_string(), expression });
+                }
+            }
+            i += 1;
+        }
+
+        let result = values.pop().ok_or(CalcError::NotEnoughOperands)?;
+        Ok(result)
+    }
+
+    /// Applies a single token of an `RPNQueue` to the stack of `values`.
+    fn apply(&self, x: &str, values: &mut Vec<Value>, frame: &Frame) -> Result<(), CalcError> {
+        match x {
+            x if is_binary_operator(x) => {
+                let second = values.pop().ok_or(CalcError::NotEnoughOperands)?;
+                let first = values.pop().ok_or(CalcError::NotEnoughOperands)?;
+                if (x == "/" || x == "%") && is_float_division(&first, &second) && second.to_f64() == 0.0 {
+                    match self.zero_division {
+                        ZeroDivision::Infinity => {}
+                        ZeroDivision::Error => return Err(CalcError::DivisionByZero),
+                        ZeroDivision::NaN => {
+                            values.push(Value::Float(f64::NAN));
+                            return Ok(());
+                        }
+                    }
+                }
+                match (first, second) {
+                    (Value::Integer(first), Value::Integer(second)) => {
+                        values.push(fixed_integer(first, second, x, self.overflow)?);
+                    }
+                    (first, second) => values.push(first.binary(x, second)?),
+                }
+            }
+            "neg" => {
+                let operand = values.pop().ok_or(CalcError::NotEnoughOperands)?;
+                match operand {
+                    Value::Integer(n) => values.push(fixed_negate(n, self.overflow)?),
+                    operand => values.push(operand.negate()),
+                }
+            }
+            "not" => {
+                let operand = values.pop().ok_or(CalcError::NotEnoughOperands)?;
+                values.push(operand.not()?);
+            }
+            "~" => {
+                let operand = values.pop().ok_or(CalcError::NotEnoughOperands)?;
+                values.push(operand.bit_not()?);
+            }
+            "!" => {
+                let operand = values.pop().ok_or(CalcError::NotEnoughOperands)?;
+                match operand {
+                    Value::Integer(n) => values.push(fixed_factorial(n, self.overflow)?),
+                    operand => values.push(operand.factorial()?),
+                }
+            }
+            symbol if self.custom_operator(symbol).is_some() => {
+                let second = values.pop().ok_or(CalcError::NotEnoughOperands)?;
+                let first = values.pop().ok_or(CalcError::NotEnoughOperands)?;
+                let function = self.custom_operator(symbol).unwrap();
+                values.push(Value::Float(function(first.to_f64(), second.to_f64())));
+            }
+            call if parse_call(call).is_some() => {
+                let (name, arity) = parse_call(call).unwrap();
+                if values.len() < arity {
+                    return Err(CalcError::NotEnoughOperands);
+                }
+                let arguments = values.split_off(values.len() - arity);
+                if let Some(result) = interval_function(name, &arguments) {
+                    values.push(result?);
+                    return Ok(());
+                }
+                if let Some(result) = uncertain_function(name, &arguments) {
+                    values.push(result);
+                    return Ok(());
+                }
+                #[cfg(feature = "complex")]
+                {
+                    if let Some(result) = complex_function(name, &arguments) {
+                        values.push(result);
+                        return Ok(());
+                    }
+                }
+                match self.functions.get(name) {
+                    Some(function) if !function.accepts(arity) => {
+                        return Err(CalcError::WrongArgumentCount { function: name.to_string(), count: arity });
+                    }
+                    Some(function) => {
+                        let arguments: Vec<f64> = arguments.iter().map(Value::to_f64).collect();
+                        values.push(Value::Float(function.call(&arguments)));
+                    }
+                    None => values.push(self.call_user(name, arguments, frame)?),
+                }
+            }
+            name if is_identifier(name) => {
+                let value = frame.lookup(name, &self.constants);
+                // `i` is the imaginary unit unless it is defined as something else
+                #[cfg(feature = "complex")]
+                let value = value.or_else(|| Some(Value::Complex(Complex64::new(0.0, 1.0))).filter(|_| name == "i"));
+                values.push(value.ok_or_else(|| CalcError::UnknownVariable(name.to_string()))?);
+            }
+            number => values.push(self.literal(number)?),
+        }
+        Ok(())
+    }
+
+    /// Calls the function `name` that `frame` has defined, its body calculated
+    /// by this calculator in a frame of its own.
+    fn call_user(&self, name: &str, arguments: Vec<Value>, frame: &Frame) -> Result<Value, CalcError> {
+        let function = frame.environment.functions().get(name).ok_or_else(|| CalcError::UnknownFunction(name.to_string()))?;
+        if function.parameters.len() != arguments.len() {
+            return Err(CalcError::WrongArgumentCount { function: name.to_string(), count: arguments.len() });
+        }
+        if frame.depth >= MAX_CALL_DEPTH {
+            return Err(CalcError::RecursionLimit(name.to_string()));
+        }
+        let arguments = function.parameters.iter().cloned().zip(arguments).collect();
+        self.evaluate_in(&function.body, &Frame { environment: frame.environment, arguments, depth: frame.depth + 1 })
+    }
+
+    /// The operand that ends with the last of `tokens`, written as infix.
+    fn last_operand(&self, tokens: &[String]) -> String {
+        let mut start = tokens.len();
+        let mut needed = 1;
+        while needed > 0 && start > 0 {
+            start -= 1;
+            let operands = match tokens[start].as_ref() {
+                symbol if is_binary_operator(symbol) => 2,
+                "neg" | "not" | "~" | "!" => 1,
+                symbol if self.custom_operator(symbol).is_some() => 2,
+                token => parse_call(token).map_or(0, |(_, arity)| arity),
+            };
+            needed = needed - 1 + operands;
+        }
+        to_infix(&tokens[start..], &self.operators).unwrap_or_else(|_| tokens[start..].join(" "))
+    }
+
+    fn literal(&self, number: &str) -> Result<Value, CalcError> {
+        match self.mode {
+            Mode::Integer => {
+                if let Ok(n) = number.parse::<i64>() {
+                    return Ok(Value::Integer(n));
+                }
+                // too big for an `i64`, straight from the digits rather than a rounded f64
+                #[cfg(feature = "bigint")]
+                if let (Overflow::Promote, Ok(n)) = (self.overflow, number.parse::<BigInt>()) {
+                    return Ok(Value::BigInt(n));
+                }
+            }
+            #[cfg(feature = "decimal")]
+            Mode::Decimal => {
+                // the literal as written, e.g. `1.5e-30` is kept with its exponent
+                if let Ok(n) = Decimal::from_str(number).or_else(|_| Decimal::from_scientific(number)) {
+                    return Ok(Value::Decimal(n));
+                }
+            }
+            Mode::Float => {}
+        }
+        #[cfg(feature = "complex")]
+        {
+            if let Some(imaginary) = number.strip_suffix('i') {
+                return imaginary.parse::<f64>().map(|im| Value::Complex(Complex64::new(0.0, im)))
+                    .or(Err(CalcError::InvalidRpnToken(number.to_string())));
+            }
+        }
+        number.parse::<f64>().map(Value::Float).or(Err(CalcError::InvalidRpnToken(number.to_string())))
+    }
+}
+
+/// What names stand for while `Calculator::evaluate` works through a queue.
+struct Frame<'a> {
+    environment: &'a Environment,
+    /// the parameters of the user defined function being calculated
+    arguments: HashMap<String, Value>,
+    /// how many user defined functions deep the queue is
+    depth: usize,
+}
+
+impl<'a> Frame<'a> {
+    /// A parameter, else a variable, else one of `constants`.
+    fn lookup(&self, name: &str, constants: &HashMap<String, f64>) -> Option<Value> {
+        self.arguments.get(name).cloned()
+            .or_else(|| self.environment.value(name))
+            .or_else(|| constants.get(name).map(|&n| Value::Float(n)))
+    }
+}
+
+fn is_finite(value: &Value) -> bool {
+    match *value {
+        Value::Float(n) => n.is_finite(),
+        Value::Interval(interval) => interval.lo().is_finite() && interval.hi().is_finite(),
+        Value::Uncertain(n) => n.value().is_finite() && n.error().is_finite(),
+        #[cfg(feature = "complex")]
+        Value::Complex(z) => z.is_finite(),
+        _ => true,
+    }
+}
+
+
+/// Sets up a `Calculator`, see `Calculator::builder`.
+#[derive(Debug, Default)]
+pub struct CalculatorBuilder {
+    calculator: Calculator,
+}
+
+impl CalculatorBuilder {
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.calculator.mode = mode;
+        self
+    }
+
+    pub fn zero_division(mut self, zero_division: ZeroDivision) -> Self {
+        self.calculator.zero_division = zero_division;
+        self
+    }
+
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.calculator.overflow = overflow;
+        self
+    }
+
+    pub fn non_finite(mut self, non_finite: NonFinite) -> Self {
+        self.calculator.non_finite = non_finite;
+        self
+    }
+
+    /// See `Calculator::set_fold_constants`.
+    pub fn fold_constants(mut self, fold_constants: bool) -> Self {
+        self.calculator.fold_constants = fold_constants;
+        self
+    }
+
+    /// See `Calculator::set_limits`.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.calculator.limits = limits;
+        self
+    }
+
+    /// See `Calculator::set_cancel_token`.
+    pub fn cancel_token(mut self, cancel_token: CancelToken) -> Self {
+        self.calculator.cancel_token = Some(cancel_token);
+        self
+    }
+
+    /// Adds the function `name`, taking `arity` arguments. Calls with another
+    /// number of arguments are a `CalcError::WrongArgumentCount`. Replaces a
+    /// built-in function with the same name.
+    pub fn function<F>(mut self, name: &str, arity: usize, function: F) -> Self
+        where F: Fn(&[f64]) -> f64 + Send + Sync + 'static
+    {
+        self.calculator.functions.insert(name.to_string(), Function::Native(arity, Arc::new(function)));
+        self
+    }
+
+    /// Adds a binary operator written `symbol`, such as `@` or `//`. The built-in
+    /// ones have a precedence of 0 for `&`, 1 for `+ -`, 2 for `* / %`, 3 for
+    /// a unary minus, `!` or `~`, 4 for `^` and 5 for `±`, while `xor`, `|`,
+    /// the comparisons `== != < <= > >=`, `&&` and `||` bind looser than any
+    /// of them, in that order. Operands are passed as floats.
+    ///
+    /// # Panics
+    /// If `symbol` is empty or starts with something that isn't an operator
+    /// character, such as a digit, a letter or a bracket.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::{Associativity, Calculator, Value};
+    ///
+    /// let calculator = Calculator::builder()
+    ///     .operator("//", 2, Associativity::Left, |a, b| (a / b).floor())
+    ///     .build();
+    /// assert_eq!(calculator.calculate("7 // 2 + 1").unwrap(), Value::Float(4.0));
+    /// ```
+    pub fn operator<F>(mut self, symbol: &str, precedence: u8, associativity: Associativity, function: F) -> Self
+        where F: Fn(f64, f64) -> f64 + Send + Sync + 'static
+    {
+        let first = symbol.chars().next();
+        assert!(first.is_some_and(|c| !c.is_alphanumeric() && !c.is_whitespace() && !"()[],._".contains(c)),
+                "invalid operator symbol: {:?}", symbol);
+        let info = OperatorInfo::new(precedence.into(), associativity);
+        let operator = OperatorDefinition { info, function: Some(Arc::new(function)) };
+        self.calculator.operators.insert(symbol.to_string(), operator);
+        self
+    }
+
+    /// Adds a constant, or changes the value of a built-in one such as `e`.
+    pub fn constant(mut self, name: &str, value: f64) -> Self {
+        self.calculator.constants.insert(name.to_string(), value);
+        self
+    }
+
+    pub fn build(self) -> Calculator {
+        self.calculator
+    }
+}
+
+/// Whether `first` and `second` are divided as floats rather than exactly.
+fn is_float_division(first: &Value, second: &Value) -> bool {
+    let real = |value: &Value| match *value {
+        Value::Integer(_) | Value::Float(_) => true,
+        #[cfg(feature = "bigint")]
+        Value::BigInt(_) => true,
+        #[cfg(feature = "decimal")]
+        Value::Decimal(_) => true,
+        _ => false,
+    };
+    let float = |value: &Value| matches!(*value, Value::Float(_));
+    real(first) && real(second) && (float(first) || float(second))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Interval;
+
+    #[test]
+    fn integer_mode() {
+        let mut calculator = Calculator::new();
+        calculator.set_mode(Mode::Integer);
+        assert_eq!(calculator.calculate("2 ^ 62 + (2 ^ 62 - 1)").unwrap(), Value::Integer(i64::MAX));
+        assert_eq!(calculator.calculate("-17 / 5 + 17 % 5").unwrap(), Value::Integer(-1));
+        assert_eq!(calculator.calculate("1e3 * 2").unwrap(), Value::Integer(2000));
+        assert_eq!(calculator.calculate("5! / (2 - 2)").unwrap_err(), CalcError::DivisionByZero);
+        // the right operand of `&&` and `||` is only calculated when needed
+        assert_eq!(calculator.calculate("0 && 1 / 0").unwrap(), Value::Integer(0));
+        assert_eq!(calculator.calculate("2 > 1 || 1 / 0").unwrap(), Value::Integer(1));
+        assert_eq!(calculator.calculate("!(7 / 2 == 3) || 1 / 0").unwrap_err(), CalcError::DivisionByZero);
+        assert_eq!(calculator.calculate("0xFF & 0x0F | 0x30").unwrap(), Value::Integer(0x3F));
+        assert_eq!(calculator.calculate("5 xor 3 == 6 && ~0 == -1").unwrap(), Value::Integer(1));
+        assert_eq!(calculator.calculate("1.5 & 1").unwrap_err(), CalcError::NotAnInteger(1.5));
+        // literals past 2^53 keep every digit instead of going through an f64
+        assert_eq!(calculator.calculate("9007199254740993 - 9007199254740992").unwrap(), Value::Integer(1));
+        assert_eq!(calculator.calculate("0x7FFFFFFFFFFFFFFF").unwrap(), Value::Integer(i64::MAX));
+        assert_eq!(calculator.calculate("0x7FFF_FFFF_FFFF_FFFF - 9_223_372_036_854_775_806").unwrap(), Value::Integer(1));
+        #[cfg(feature = "bigint")]
+        assert_eq!(calculator.calculate("~-(2 ^ 64) == 2 ^ 64 - 1").unwrap(), Value::Integer(1));
+
+        // anything involving floats, functions or names goes on as a float
+        assert_eq!(calculator.calculate("1 + 0.5").unwrap(), Value::Float(1.5));
+        assert_eq!(calculator.calculate("abs(-3) * 2").unwrap(), Value::Float(6.0));
+        #[cfg(not(feature = "bigint"))]
+        assert_eq!(calculator.calculate("2 ^ 63").unwrap(), Value::Float(9223372036854775808.0));
+        #[cfg(feature = "bigint")]
+        assert_eq!(calculator.calculate("2 ^ 200 / 2 ^ 199").unwrap(), Value::Integer(2));
+        #[cfg(feature = "bigint")]
+        assert_eq!(calculator.calculate("123456789012345678901234567890 + 1").unwrap().to_string(), "123456789012345678901234567891");
+        #[cfg(feature = "bigint")]
+        assert_eq!(calculator.calculate("0x1_0000_0000_0000_0000 - 2 ^ 64").unwrap(), Value::Integer(0));
+
+        let mut environment = Environment::new();
+        environment.set("x", 4.0);
+        assert_eq!(calculator.calculate_with("x / 8", &environment).unwrap(), Value::Float(0.5));
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn decimal_mode() {
+        let mut calculator = Calculator::new();
+        assert_ne!(calculator.calculate("0.1 + 0.2").unwrap().to_string(), "0.3");
+
+        calculator.set_mode(Mode::Decimal);
+        assert_eq!(calculator.calculate("0.1 + 0.2").unwrap().to_string(), "0.3");
+        assert_eq!(calculator.calculate("1 / 8 + 0.375").unwrap().to_string(), "0.5");
+        assert_eq!(calculator.calculate("1.5 ^ 2 - 0.25").unwrap().to_string(), "2");
+        assert_eq!(calculator.calculate("0.5 / 0").unwrap_err(), CalcError::DivisionByZero);
+        assert_eq!(calculator.calculate("sqrt(4) + 0.1").unwrap(), Value::Float(2.1));
+        // more significant digits than an f64 has
+        assert_eq!(calculator.calculate("1.0000000000000000000000001 - 1").unwrap().to_string(), "0.0000000000000000000000001");
+        assert_eq!(calculator.calculate("0.12345678901234567890123").unwrap().to_string(), "0.12345678901234567890123");
+        assert_eq!(calculator.calculate("1.2345678901234567890123e3").unwrap().to_string(), "1234.5678901234567890123");
+    }
+
+    #[cfg(feature = "complex")]
+    #[test]
+    fn complex_numbers() {
+        let calculator = Calculator::new();
+        assert_eq!(calculator.calculate("(1 + 2i) * (3 - i)").unwrap().to_string(), "5 + 5i");
+        assert_eq!(calculator.calculate("i ^ 2").unwrap().to_string(), "-1");
+        assert_eq!(calculator.calculate("abs(3 + 4i)").unwrap(), Value::Float(5.0));
+        assert_eq!(calculator.calculate("conj(2i) + re(1 + i) + arg(-1)").unwrap().to_string(),
+                   format!("{} - 2i", 1.0 + std::f64::consts::PI));
+        assert_eq!(calculator.calculate("sqrt(-4 + 0i)").unwrap().to_string(), "2i");
+
+        let mut environment = Environment::new();
+        environment.set("i", 3.0);
+        assert_eq!(calculator.calculate_with("2i + i", &environment).unwrap().to_string(), "3 + 2i");
+    }
+
+    #[test]
+    fn intervals() {
+        let calculator = Calculator::new();
+        let interval = |input| match calculator.calculate(input).unwrap() {
+            Value::Interval(interval) => interval,
+            other => panic!("{} is not an interval", other),
+        };
+        assert_eq!(interval("[1, 2] * 3 - [0, 1]"), Interval::new(2.0, 6.0).unwrap());
+        assert_eq!(interval("-[1, 2] / 4"), Interval::new(-0.5, -0.25).unwrap());
+        assert_eq!(calculator.calculate("abs([-3, 2])").unwrap().to_string(), "[0, 3]");
+        let root = interval("sqrt([4, 9])");
+        assert!(root.contains(2.0) && root.contains(3.0) && root.hi() - root.lo() < 1.0 + 1e-9);
+        let spread = interval("[1.0, 1.1] * 3");
+        assert!(spread.contains(3.0) && spread.contains(3.3));
+
+        assert_eq!(calculator.calculate("[2, 1]").unwrap_err(), CalcError::InvalidInterval { lo: 2.0, hi: 1.0 });
+        assert_eq!(calculator.calculate("1 / [-1, 1]").unwrap_err(), CalcError::DivisionByZero);
+    }
+
+    #[test]
+    fn uncertainties() {
+        let calculator = Calculator::new();
+        assert_eq!(calculator.calculate("10.0 ± 0.3 + 5 +- 0.4").unwrap().to_string(), "15 ± 0.5");
+        assert_eq!(calculator.calculate("2 * 10 ± 0.1").unwrap().to_string(), "20 ± 0.2");
+        assert_eq!(calculator.calculate("(6 ± 1.5) / 3").unwrap().to_string(), "2 ± 0.5");
+        assert_eq!(calculator.calculate("sqrt(4 ± 0.4)").unwrap().to_string(), "2 ± 0.1");
+        assert_eq!(calculator.calculate("-(1 ± 0.5) ± 0").unwrap().to_string(), "-1 ± 0.5");
+        assert_eq!(RPNQueue::from_infix_string("1 ± 0.5").unwrap().calculate().unwrap_err(),
+                   CalcError::UnknownOperator("±".to_string()));
+    }
+
+    #[test]
+    fn zero_division() {
+        let mut calculator = Calculator::new();
+        assert_eq!(calculator.calculate("-1 / 0").unwrap(), Value::Float(f64::NEG_INFINITY));
+        assert!(calculator.calculate("1 % 0").unwrap().to_f64().is_nan());
+
+        calculator.set_zero_division(ZeroDivision::Error);
+        assert_eq!(calculator.calculate("1 / (2 - 2)").unwrap_err(), CalcError::DivisionByZero);
+        assert_eq!(calculator.calculate("1 % 0").unwrap_err(), CalcError::DivisionByZero);
+        assert_eq!(calculator.calculate("1 / 4").unwrap(), Value::Float(0.25));
+
+        calculator.set_zero_division(ZeroDivision::NaN);
+        assert!(calculator.calculate("1 / 0").unwrap().to_f64().is_nan());
+        calculator.set_mode(Mode::Integer);
+        assert_eq!(calculator.calculate("1 / 0").unwrap_err(), CalcError::DivisionByZero);
+        assert!(calculator.calculate("1 / (0 * 0.5) + 1").unwrap().to_f64().is_nan());
+    }
+
+    #[test]
+    fn user_functions_follow_the_calculator() {
+        let mut environment = Environment::new();
+        environment.eval("f(x) = 1 / x").unwrap();
+        environment.eval("half(n) = n / 2").unwrap();
+        environment.eval("loop(x) = loop(x + 1)").unwrap();
+        let mut calculator = Calculator::new();
+        calculator.set_zero_division(ZeroDivision::Error);
+        assert_eq!(calculator.calculate_with("f(0)", &environment), calculator.calculate("1 / 0"));
+        assert_eq!(calculator.calculate_with("f([1, 2])", &environment).unwrap().to_string(), "[0.5, 1]");
+        assert_eq!(calculator.calculate_with("loop(0)", &environment).unwrap_err(), CalcError::RecursionLimit("loop".to_string()));
+        assert_eq!(calculator.calculate_with("half(1, 2)", &environment).unwrap_err(),
+                   CalcError::WrongArgumentCount { function: "half".to_string(), count: 2 });
+
+        calculator.set_mode(Mode::Integer);
+        assert_eq!(calculator.calculate_with("half(7) + half(9007199254740993)", &environment).unwrap(), Value::Integer(4503599627370499));
+    }
+
+    #[test]
+    fn overflow() {
+        let mut calculator = Calculator::new();
+        calculator.set_mode(Mode::Integer);
+        calculator.set_overflow(Overflow::Error);
+        assert_eq!(calculator.calculate("2 ^ 62 + (2 ^ 62 - 1)").unwrap(), Value::Integer(i64::MAX));
+        assert_eq!(calculator.calculate("2 ^ 63").unwrap_err(), CalcError::Overflow);
+        assert_eq!(calculator.calculate("-(-2 ^ 63)").unwrap_err(), CalcError::Overflow);
+        assert_eq!(calculator.calculate("25!").unwrap_err(), CalcError::Overflow);
+        assert_eq!(calculator.calculate("2 ^ 63 * 0.5").unwrap_err(), CalcError::Overflow);
+        assert_eq!(calculator.calculate("10 ± 1").unwrap().to_string(), "10 ± 1");
+
+        calculator.set_overflow(Overflow::Wrap);
+        assert_eq!(calculator.calculate("2 ^ 63").unwrap(), Value::Integer(i64::MIN));
+        calculator.set_overflow(Overflow::Saturate);
+        assert_eq!(calculator.calculate("-(2 ^ 64) - 1").unwrap(), Value::Integer(i64::MIN));
+
+        // floats never overflow
+        calculator.set_mode(Mode::Float);
+        calculator.set_overflow(Overflow::Error);
+        assert_eq!(calculator.calculate("2 ^ 64").unwrap(), Value::Float(18446744073709551616.0));
+    }
+
+    #[test]
+    fn non_finite() {
+        let mut calculator = Calculator::new();
+        assert!(calculator.calculate("sqrt(-1) + 1").unwrap().to_f64().is_nan());
+
+        calculator.set_non_finite(NonFinite::Error);
+        assert_eq!(calculator.calculate("2 * (1 + sqrt(-1)) - 3").unwrap_err(),
+                   CalcError::NotFinite { value: "NaN".to_string(), expression: "sqrt(-1)".to_string() });
+        assert_eq!(calculator.calculate("max(1, 2 / (1 - 1)) * 0").unwrap_err(),
+                   CalcError::NotFinite { value: "inf".to_string(), expression: "2 / (1 - 1)".to_string() });
+        assert_eq!(calculator.calculate("1 / [0.5, 1]").unwrap().to_string(), "[1, 2]");
+
+        let mut environment = Environment::new();
+        environment.set("x", f64::INFINITY);
+        assert_eq!(calculator.calculate_with("1 + x", &environment).unwrap_err(),
+                   CalcError::NotFinite { value: "inf".to_string(), expression: "x".to_string() });
+    }
+
+    #[test]
+    fn custom_functions() {
+        let calculator = Calculator::builder()
+            .mode(Mode::Integer)
+            .function("hypot", 2, |args| args[0].hypot(args[1]))
+            .function("answer", 0, |_| 42.0)
+            .function("sqrt", 1, |_| 0.0)
+            .build();
+        assert_eq!(calculator.calculate("hypot(3, 4) + answer()").unwrap(), Value::Float(47.0));
+        assert_eq!(calculator.calculate("sqrt(4)").unwrap(), Value::Float(0.0));
+        assert_eq!(calculator.calculate("answer(1)").unwrap_err(),
+                   CalcError::WrongArgumentCount { function: "answer".to_string(), count: 1 });
+        assert_eq!(calculator.calculate("7 / 2").unwrap(), Value::Integer(3));
+
+        // user defined functions can call them too
+        let mut environment = Environment::new();
+        environment.eval("f(x) = hypot(x, x)").unwrap();
+        assert_eq!(calculator.calculate_with("f(1)", &environment).unwrap(), Value::Float(2f64.sqrt()));
+        assert!(Calculator::new().calculate("answer()").is_err());
+    }
+
+    #[test]
+    fn constants() {
+        let calculator = Calculator::builder().constant("g", 9.81).constant("e", 1.0).build();
+        assert_eq!(calculator.calculate("2 * g + e").unwrap(), Value::Float(20.62));
+        assert_eq!(Calculator::new().calculate("e").unwrap(), Value::Float(std::f64::consts::E));
+
+        // variables still come first
+        let mut environment = Environment::new();
+        environment.set("g", 10.0);
+        assert_eq!(calculator.calculate_with("g", &environment).unwrap(), Value::Float(10.0));
+    }
+
+    #[test]
+    fn folded_constants() {
+        let calculator = Calculator::builder().fold_constants(true).function("half", 1, |args| args[0] / 2.0).build();
+        assert_eq!(calculator.parse("x * (2 + 3) - half(2 ^ 3)").unwrap().to_string(), "x 5 * 4 -");
+        assert_eq!(calculator.parse("(1 ± 0.1) * 2").unwrap().to_string(), "1 0.1 ± 2 *");
+
+        let mut environment = Environment::new();
+        environment.set("x", 2.0);
+        assert_eq!(calculator.calculate_with("x * (2 + 3)", &environment).unwrap(), Value::Float(10.0));
+
+        let integers = Calculator::builder().fold_constants(true).mode(Mode::Integer).build();
+        assert_eq!(integers.parse("7 / 2").unwrap().to_string(), "7 2 /");
+        assert_eq!(Calculator::new().parse("2 + 3").unwrap().to_string(), "2 3 +");
+    }
+
+    #[test]
+    fn custom_operators() {
+        let calculator = Calculator::builder()
+            .operator("@", 2, Associativity::Left, |a, b| a * 10.0 + b)
+            .operator("**", 4, Associativity::Right, f64::powf)
+            .operator("<>", 0, Associativity::Left, |a, b| (a - b).abs())
+            .build();
+        assert_eq!(calculator.calculate("1 @ 2 @ 3 + 1").unwrap(), Value::Float(124.0));
+        assert_eq!(calculator.calculate("2 ** 3 ** 2").unwrap(), Value::Float(512.0));
+        assert_eq!(calculator.calculate("1 + 2 <> 10").unwrap(), Value::Float(7.0));
+        assert_eq!(calculator.parse("2 * 3 ** 2").unwrap().tokens(), vec!["2", "3", "2", "**", "*"]);
+        assert!(Calculator::new().calculate("1 @ 2").is_err());
+    }
+
+    #[test]
+    fn limits() {
+        let limits = Limits { max_length: Some(40), max_tokens: Some(12), max_depth: Some(3), max_output: Some(5) };
+        let calculator = Calculator::builder().limits(limits).build();
+        assert_eq!(calculator.calculate("max(1, 2 + 3)").unwrap(), Value::Float(5.0));
+        assert_eq!(calculator.parse(&"1".repeat(41)).unwrap_err(), CalcError::InputTooLong(40));
+        assert_eq!(calculator.parse("1 + 2 + 3 + 4 + 5 + 6 + 7").unwrap_err(), CalcError::TooManyTokens(12));
+        assert_eq!(calculator.parse("((((1))))").unwrap_err(), CalcError::NestingTooDeep(3));
+        assert_eq!(calculator.parse("(([max(1)]))").unwrap_err(), CalcError::NestingTooDeep(3));
+        assert_eq!(calculator.parse("- - - - - 1").unwrap_err(), CalcError::OutputTooLong(5));
+        assert_eq!(calculator.parse_all("1 + 2 + 3 + 4 + 5 + 6 + 7").unwrap_err(), vec![CalcError::TooManyTokens(12), CalcError::OutputTooLong(5)]);
+
+        let deep = "(".repeat(100_000) + "1" + &")".repeat(100_000);
+        let calculator = Calculator::builder().limits(Limits { max_depth: Some(100), ..Limits::default() }).build();
+        assert_eq!(calculator.parse(&deep).unwrap_err(), CalcError::NestingTooDeep(100));
+        assert_eq!(Calculator::new().limits(), Limits::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid operator symbol")]
+    fn operator_symbols_are_checked() {
+        Calculator::builder().operator("mod", 2, Associativity::Left, |a, b| a % b);
+    }
+
+    #[test]
+    fn complete_input() {
+        let calculator = Calculator::builder().operator("<>", 1, Associativity::Left, |a, b| a.max(b)).build();
+        assert!(calculator.is_complete(""));
+        assert!(calculator.is_complete("(1 + 2))"));
+        assert!(calculator.is_complete("1 $"));
+        assert!(!calculator.is_complete("[1, (2"));
+        assert!(!calculator.is_complete("1 <>"));
+        assert!(!calculator.is_complete("2 ^"));
+    }
+
+    #[test]
+    fn float_mode_matches_rpn() {
+        let calculator = Calculator::new();
+        for input in &["7 / 2", "-3! + 2 ^ 0.5", "max(1, 2, 3) % 2"] {
+            let expected = RPNQueue::from_infix_string(input).unwrap().calculate().unwrap();
+            assert_eq!(calculator.calculate(input).unwrap(), Value::Float(expected));
+        }
+    }
+}
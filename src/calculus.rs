@@ -0,0 +1,512 @@
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use {BinaryOp, CalcError, Environment, Expr, UnaryOp};
+
+fn number(n: f64) -> Expr {
+    Expr::Number(n)
+}
+
+fn binary(op: BinaryOp, left: Expr, right: Expr) -> Expr {
+    Expr::Binary(op, Box::new(left), Box::new(right))
+}
+
+fn call(name: &str, argument: Expr) -> Expr {
+    Expr::Call(name.to_string(), vec![argument])
+}
+
+fn negate(operand: Expr) -> Expr {
+    Expr::Unary(UnaryOp::Neg, Box::new(operand))
+}
+
+fn add(left: Expr, right: Expr) -> Expr {
+    binary(BinaryOp::Add, left, right)
+}
+
+fn sub(left: Expr, right: Expr) -> Expr {
+    binary(BinaryOp::Sub, left, right)
+}
+
+fn mul(left: Expr, right: Expr) -> Expr {
+    binary(BinaryOp::Mul, left, right)
+}
+
+fn div(left: Expr, right: Expr) -> Expr {
+    binary(BinaryOp::Div, left, right)
+}
+
+fn pow(left: Expr, right: Expr) -> Expr {
+    binary(BinaryOp::Pow, left, right)
+}
+
+impl Expr {
+    /// Whether `variable` appears anywhere in the expression.
+    fn depends_on(&self, variable: &str) -> bool {
+        self.variables().iter().any(|name| name == variable)
+    }
+
+    /// The derivative of the expression with respect to `variable`, simplified.
+    /// Any other name, constants such as `pi` included, is treated as a
+    /// constant.
+    ///
+    /// Every operator and built-in function can be differentiated except for
+    /// `%`, `!`, `min` and `max`, and user defined functions can't either. Those
+    /// give `CalcError::NotDifferentiable`, unless the part of the expression
+    /// using them doesn't depend on `variable`.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::Expr;
+    ///
+    /// let expr = Expr::parse("x ^ 3 + sin(2 * x)").unwrap();
+    /// assert_eq!(expr.differentiate("x").unwrap().to_string(), "3 * x ^ 2 + cos(2 * x) * 2");
+    /// ```
+    pub fn differentiate(&self, variable: &str) -> Result<Expr, CalcError> {
+        Ok(self.derivative(variable)?.simplify())
+    }
+
+    fn derivative(&self, variable: &str) -> Result<Expr, CalcError> {
+        if !self.depends_on(variable) {
+            return Ok(number(0.0));
+        }
+        let not_differentiable = || CalcError::NotDifferentiable(self.to_string());
+        let derivative = match *self {
+            Expr::Number(_) => number(0.0),
+            Expr::Variable(_) => number(1.0),
+            Expr::Unary(UnaryOp::Neg, ref operand) => negate(operand.derivative(variable)?),
+            Expr::Unary(UnaryOp::Factorial, _) | Expr::Unary(UnaryOp::Not, _) | Expr::Unary(UnaryOp::BitNot, _) => {
+                return Err(not_differentiable())
+            }
+            Expr::Binary(op, ref left, ref right) => {
+                let (u, v) = ((**left).clone(), (**right).clone());
+                let (du, dv) = (left.derivative(variable)?, right.derivative(variable)?);
+                match op {
+                    BinaryOp::Add => add(du, dv),
+                    BinaryOp::Sub => sub(du, dv),
+                    BinaryOp::Mul => add(mul(du, v), mul(u, dv)),
+                    BinaryOp::Div => div(sub(mul(du, v.clone()), mul(u, dv)), pow(v, number(2.0))),
+                    // comparisons are steps, without a slope where they change
+                    BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge |
+                    BinaryOp::And | BinaryOp::Or | BinaryOp::Rem |
+                    BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor | BinaryOp::PlusMinus => return Err(not_differentiable()),
+                    BinaryOp::Pow => power(u, v, du, dv, variable),
+                }
+            }
+            Expr::Call(ref name, ref arguments) => match (name.as_str(), &arguments[..]) {
+                ("pow", [base, exponent]) => {
+                    let (du, dv) = (base.derivative(variable)?, exponent.derivative(variable)?);
+                    power(base.clone(), exponent.clone(), du, dv, variable)
+                }
+                (name, [argument]) => {
+                    let u = argument.clone();
+                    let outer = match name {
+                        "sqrt" => div(number(1.0), mul(number(2.0), call("sqrt", u))),
+                        "sin" => call("cos", u),
+                        "cos" => negate(call("sin", u)),
+                        "tan" => div(number(1.0), pow(call("cos", u), number(2.0))),
+                        "ln" => div(number(1.0), u),
+                        "log" => div(number(1.0), mul(u, call("ln", number(10.0)))),
+                        "exp" => call("exp", u),
+                        "abs" => div(u.clone(), call("abs", u)),
+                        _ => return Err(not_differentiable()),
+                    };
+                    // the chain rule
+                    mul(outer, argument.derivative(variable)?)
+                }
+                _ => return Err(not_differentiable()),
+            },
+        };
+        Ok(derivative)
+    }
+}
+
+/// Parses `expression` into a function of `variable`.
+fn function_of<'a>(expression: &str, variable: &'a str) -> Result<impl FnMut(f64) -> Result<f64, CalcError> + 'a, CalcError> {
+    let expr = Expr::parse(expression)?;
+    let mut environment = Environment::new();
+    Ok(move |x| {
+        environment.set(variable, x);
+        expr.calculate_with(&environment)
+    })
+}
+
+/// Like `function_of`, where a NaN or infinite value is a
+/// `CalcError::NotFinite` saying where it was, e.g. `1 / x at x = 0`.
+fn finite_function_of<'a>(expression: &'a str, variable: &'a str) -> Result<impl FnMut(f64) -> Result<f64, CalcError> + 'a, CalcError> {
+    let mut f = function_of(expression, variable)?;
+    Ok(move |x| match f(x)? {
+        y if y.is_finite() => Ok(y),
+        y => Err(CalcError::NotFinite { value: y.to_string(), expression: format!("{} at {} = {}", expression, variable, x) }),
+    })
+}
+
+/// The slope of `expression` at `variable = x`, worked out numerically.
+///
+/// Central differences are taken with smaller and smaller steps and
+/// extrapolated to a step of zero (Ridders' method), stopping once rounding
+/// errors start to grow. Unlike `Expr::differentiate` this works for any
+/// expression that can be calculated near `x`. It is a
+/// `CalcError::NotFinite` if the expression is NaN or infinite at one of
+/// the steps, such as `sqrt(x)` left of 0, and a `CalcError::NoDerivative`
+/// if the estimates don't settle, such as at the step of `x / abs(x)`.
+///
+/// # Example
+/// ```
+/// use infix_calculator::derivative_at;
+///
+/// let slope = derivative_at("x ^ 2 + max(x, 1)", "x", 3.0).unwrap();
+/// assert!((slope - 7.0).abs() < 1e-9);
+/// ```
+pub fn derivative_at(expression: &str, variable: &str, x: f64) -> Result<f64, CalcError> {
+    const STEPS: usize = 10;
+    // how much smaller each step is than the one before
+    const SHRINK: f64 = 1.4;
+
+    // how close the best estimates have to be, relative to their size once it is above one
+    const TOLERANCE: f64 = 1e-6;
+
+    let mut f = finite_function_of(expression, variable)?;
+    let mut central = |h: f64| -> Result<f64, CalcError> { Ok((f(x + h)? - f(x - h)?) / (2.0 * h)) };
+
+    let mut h = 0.1 * x.abs().max(1.0);
+    // table[i][j] is extrapolated j times from the steps up to the i-th
+    let mut table = vec![vec![central(h)?]];
+    let (mut best, mut error) = (table[0][0], f64::INFINITY);
+    for i in 1..STEPS {
+        h /= SHRINK;
+        let mut row = vec![central(h)?];
+        let mut factor = SHRINK * SHRINK;
+        for j in 1..=i {
+            let estimate = (row[j - 1] * factor - table[i - 1][j - 1]) / (factor - 1.0);
+            let change = (estimate - row[j - 1]).abs().max((estimate - table[i - 1][j - 1]).abs());
+            if change <= error {
+                best = estimate;
+                error = change;
+            }
+            row.push(estimate);
+            factor *= SHRINK * SHRINK;
+        }
+        // rounding has taken over when extrapolating more makes it worse
+        let worse = (row[i] - table[i - 1][i - 1]).abs() >= 2.0 * error;
+        table.push(row);
+        if worse {
+            break;
+        }
+    }
+    if !best.is_finite() || error > TOLERANCE * best.abs().max(1.0) {
+        return Err(CalcError::NoDerivative(format!("{} at {} = {}", expression, variable, x)));
+    }
+    Ok(best)
+}
+
+/// How hard `integrate_with` tries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Accuracy {
+    /// The absolute error the integral should be within
+    pub tolerance: f64,
+    /// How many times the range may be split in half, which bounds the work
+    /// done on integrals that never reach `tolerance`
+    pub max_depth: u32,
+}
+
+impl Default for Accuracy {
+    fn default() -> Self {
+        Accuracy { tolerance: 1e-10, max_depth: 50 }
+    }
+}
+
+/// The result of `integrate`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Integral {
+    pub value: f64,
+    /// An estimate of how far `value` is from the exact integral
+    pub error: f64,
+}
+
+/// The integral of `expression` over `variable` from `a` to `b`, with the
+/// default `Accuracy`.
+///
+/// # Example
+/// ```
+/// use infix_calculator::integrate;
+///
+/// let integral = integrate("sin(x)", "x", 0.0, std::f64::consts::PI).unwrap();
+/// assert!((integral.value - 2.0).abs() < 1e-10);
+/// assert!(integral.error < 1e-10);
+/// ```
+pub fn integrate(expression: &str, variable: &str, a: f64, b: f64) -> Result<Integral, CalcError> {
+    integrate_with(expression, variable, a, b, Accuracy::default())
+}
+
+/// The integral of `expression` over `variable` from `a` to `b`, worked out
+/// by adaptive Simpson's rule: parts of the range where the estimate doesn't
+/// settle are split in half until it does. It is a `CalcError::NotFinite`
+/// if the expression is NaN or infinite where it is sampled, such as `1 / x`
+/// at 0, or if the integral overflows.
+pub fn integrate_with(expression: &str, variable: &str, a: f64, b: f64, accuracy: Accuracy) -> Result<Integral, CalcError> {
+    let mut f = finite_function_of(expression, variable)?;
+    let (fa, fb) = (f(a)?, f(b)?);
+    let m = (a + b) / 2.0;
+    let fm = f(m)?;
+    let whole = Simpson { a, b, fa, fm, fb };
+    let mut integral = Integral { value: 0.0, error: 0.0 };
+    whole.refine(&mut f, whole.estimate(), 0, &accuracy, &mut integral)?;
+    if !integral.value.is_finite() || !integral.error.is_finite() {
+        let expression = format!("the integral of {} from {} = {} to {}", expression, variable, a, b);
+        return Err(CalcError::NotFinite { value: integral.value.to_string(), expression });
+    }
+    Ok(integral)
+}
+
+/// When `solve_with` stops.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Iterations {
+    /// How close successive guesses have to be, relative to their size
+    /// once it is above one
+    pub tolerance: f64,
+    pub max_iterations: u32,
+}
+
+impl Default for Iterations {
+    fn default() -> Self {
+        Iterations { tolerance: 1e-12, max_iterations: 100 }
+    }
+}
+
+/// A value of `variable` where `expression` is zero, found by Newton's
+/// method starting from `guess`. Which root is found depends on the guess.
+///
+/// # Example
+/// ```
+/// use infix_calculator::solve;
+///
+/// let x = solve("x^3 - 2 * x - 5", "x", 2.0).unwrap();
+/// assert!((x - 2.0945514815423265).abs() < 1e-12);
+/// ```
+pub fn solve(expression: &str, variable: &str, guess: f64) -> Result<f64, CalcError> {
+    solve_with(expression, variable, guess, Iterations::default())
+}
+
+/// Like `solve`, but stopping as `iterations` says. Gives
+/// `CalcError::NoConvergence` if the guesses haven't settled within
+/// `max_iterations`, or run into a point where the slope is zero.
+pub fn solve_with(expression: &str, variable: &str, guess: f64, iterations: Iterations) -> Result<f64, CalcError> {
+    let mut f = function_of(expression, variable)?;
+    // the symbolic derivative if there is one, otherwise a numeric estimate
+    let derivative = Expr::parse(expression)?.differentiate(variable).ok();
+    let mut environment = Environment::new();
+
+    let mut x = guess;
+    for iteration in 0..iterations.max_iterations {
+        let y = f(x)?;
+        if y == 0.0 {
+            return Ok(x);
+        }
+        let slope = match derivative {
+            Some(ref derivative) => {
+                environment.set(variable, x);
+                derivative.calculate_with(&environment)?
+            }
+            None => {
+                let h = 1e-6 * x.abs().max(1.0);
+                (f(x + h)? - f(x - h)?) / (2.0 * h)
+            }
+        };
+        let step = y / slope;
+        if !step.is_finite() {
+            return Err(CalcError::NoConvergence(iteration));
+        }
+        x -= step;
+        if step.abs() <= iterations.tolerance * x.abs().max(1.0) {
+            return Ok(x);
+        }
+    }
+    Err(CalcError::NoConvergence(iterations.max_iterations))
+}
+
+/// A part of the range being integrated and the values at its ends and middle.
+struct Simpson {
+    a: f64,
+    b: f64,
+    fa: f64,
+    fm: f64,
+    fb: f64,
+}
+
+impl Simpson {
+    fn estimate(&self) -> f64 {
+        (self.b - self.a) / 6.0 * (self.fa + 4.0 * self.fm + self.fb)
+    }
+
+    /// Adds the integral over this part to `integral`, `splits` being how
+    /// many times the range has been split to get here.
+    fn refine<F>(&self, f: &mut F, whole: f64, splits: u32, accuracy: &Accuracy, integral: &mut Integral) -> Result<(), CalcError>
+        where F: FnMut(f64) -> Result<f64, CalcError>
+    {
+        // too few splits can miss a curve entirely, like sin(x) from 0 to 2 pi
+        const MIN_DEPTH: u32 = 4;
+
+        let m = (self.a + self.b) / 2.0;
+        let left = Simpson { a: self.a, b: m, fa: self.fa, fm: f((self.a + m) / 2.0)?, fb: self.fm };
+        let right = Simpson { a: m, b: self.b, fa: self.fm, fm: f((m + self.b) / 2.0)?, fb: self.fb };
+        let (left_estimate, right_estimate) = (left.estimate(), right.estimate());
+        let delta = left_estimate + right_estimate - whole;
+        // each half has to be within half the tolerance
+        let tolerance = accuracy.tolerance / 2f64.powi(splits as i32);
+        if splits >= accuracy.max_depth || (splits >= MIN_DEPTH && delta.abs() <= 15.0 * tolerance) {
+            // the difference is about 15 times the error of the finer estimate
+            integral.value += left_estimate + right_estimate + delta / 15.0;
+            integral.error += delta.abs() / 15.0;
+            return Ok(());
+        }
+        left.refine(f, left_estimate, splits + 1, accuracy, integral)?;
+        right.refine(f, right_estimate, splits + 1, accuracy, integral)
+    }
+}
+
+/// The derivative of `u ^ v`, given the derivatives of `u` and `v`.
+fn power(u: Expr, v: Expr, du: Expr, dv: Expr, variable: &str) -> Expr {
+    if !v.depends_on(variable) {
+        mul(mul(v.clone(), pow(u, sub(v, number(1.0)))), du)
+    } else if !u.depends_on(variable) {
+        mul(mul(pow(u.clone(), v), call("ln", u)), dv)
+    } else {
+        let log = call("ln", u.clone());
+        mul(pow(u.clone(), v.clone()), add(mul(dv, log), div(mul(v, du), u)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{derivative_at, integrate, integrate_with, solve, solve_with, Accuracy, Iterations};
+    use {CalcError, Environment, Expr};
+
+    fn derivative(input: &str) -> String {
+        Expr::parse(input).unwrap().differentiate("x").unwrap().to_string()
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(derivative("3 * x + 2"), "3");
+        assert_eq!(derivative("y * x ^ 2 - x / 2"), "y * (2 * x) - 0.5");
+        assert_eq!(derivative("1 / x"), "-1 / x ^ 2");
+        assert_eq!(derivative("-x"), "-1");
+        assert_eq!(derivative("2 ^ x"), "2 ^ x * 0.6931471805599453");
+        assert_eq!(derivative("y + pi"), "0");
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(derivative("ln(x)"), "1 / x");
+        assert_eq!(derivative("cos(x ^ 2)"), "-sin(x ^ 2) * (2 * x)");
+        assert_eq!(derivative("exp(3 * x)"), "exp(3 * x) * 3");
+        assert_eq!(derivative("pow(x, 3)"), "3 * x ^ 2");
+    }
+
+    #[test]
+    fn matches_the_slope() {
+        let expr = Expr::parse("x ^ x + sqrt(abs(x)) * tan(x) - log(x)").unwrap();
+        let derivative = expr.differentiate("x").unwrap();
+        let at = |expr: &Expr, x: f64| {
+            let mut environment = Environment::new();
+            environment.set("x", x);
+            expr.calculate_with(&environment).unwrap()
+        };
+        let h = 1e-6;
+        let slope = (at(&expr, 1.5 + h) - at(&expr, 1.5 - h)) / (2.0 * h);
+        assert!((at(&derivative, 1.5) - slope).abs() < 1e-4);
+    }
+
+    #[test]
+    fn numeric_derivative() {
+        assert!((derivative_at("sin(x)", "x", 0.0).unwrap() - 1.0).abs() < 1e-12);
+        assert!((derivative_at("exp(t)", "t", 10.0).unwrap() - 10f64.exp()).abs() < 1e-6);
+        assert!((derivative_at("x % 2", "x", 3.5).unwrap() - 1.0).abs() < 1e-9);
+        assert!((derivative_at("1e6 * x ^ 3", "x", 1e-3).unwrap() - 3.0).abs() < 1e-9);
+        assert_eq!(derivative_at("x * y", "x", 1.0).unwrap_err(), CalcError::UnknownVariable("y".to_string()));
+    }
+
+    #[test]
+    fn numeric_derivative_outside_the_domain() {
+        assert_eq!(derivative_at("sqrt(x)", "x", 0.0).unwrap_err(),
+                   CalcError::NotFinite { value: "NaN".to_string(), expression: "sqrt(x) at x = -0.1".to_string() });
+        assert_eq!(derivative_at("ln(x)", "x", -2.0).unwrap_err(),
+                   CalcError::NotFinite { value: "NaN".to_string(), expression: "ln(x) at x = -1.8".to_string() });
+        assert_eq!(derivative_at("x / abs(x)", "x", 0.0).unwrap_err(), CalcError::NoDerivative("x / abs(x) at x = 0".to_string()));
+        // the first steps reach past the pole at 0
+        assert_eq!(derivative_at("1 / x", "x", 1e-3).unwrap_err(), CalcError::NoDerivative("1 / x at x = 0.001".to_string()));
+    }
+
+    #[test]
+    fn integrals() {
+        let close = |expression: &str, a: f64, b: f64, exact: f64| {
+            let integral = integrate(expression, "x", a, b).unwrap();
+            assert!((integral.value - exact).abs() < 1e-9, "{} gave {}", expression, integral.value);
+        };
+        close("x ^ 2", 0.0, 3.0, 9.0);
+        close("sin(x)", 0.0, 2.0 * std::f64::consts::PI, 0.0);
+        close("sqrt(x)", 0.0, 1.0, 2.0 / 3.0);
+        close("exp(x)", 1.0, 0.0, 1.0 - std::f64::consts::E);
+        close("abs(x)", -1.0, 2.0, 2.5);
+    }
+
+    #[test]
+    fn integration_accuracy() {
+        let accuracy = Accuracy { tolerance: 1e-3, max_depth: 50 };
+        let loose = integrate_with("1 / x", "x", 1.0, 100.0, accuracy).unwrap();
+        let exact = 100f64.ln();
+        assert!((loose.value - exact).abs() < 1e-3);
+        assert!(loose.error > 0.0 && loose.error < 1e-3);
+
+        let tight = integrate("1 / x", "x", 1.0, 100.0).unwrap();
+        assert!((tight.value - exact).abs() < 1e-10);
+        assert!(tight.error < loose.error);
+
+        // a spike it can never settle on is given up on at the maximum depth
+        let accuracy = Accuracy { tolerance: 1e-12, max_depth: 8 };
+        let rough = integrate_with("1 / sqrt(abs(x - 0.3))", "x", 0.0, 1.0, accuracy).unwrap();
+        assert!(rough.error > 1e-12);
+        assert_eq!(integrate("y", "x", 0.0, 1.0).unwrap_err(), CalcError::UnknownVariable("y".to_string()));
+    }
+
+    #[test]
+    fn integrands_that_blow_up() {
+        assert_eq!(integrate("1 / sqrt(x)", "x", 0.0, 1.0).unwrap_err(),
+                   CalcError::NotFinite { value: "inf".to_string(), expression: "1 / sqrt(x) at x = 0".to_string() });
+        assert_eq!(integrate("1 / x", "x", -1.0, 1.0).unwrap_err(),
+                   CalcError::NotFinite { value: "inf".to_string(), expression: "1 / x at x = 0".to_string() });
+        assert_eq!(integrate("sqrt(x)", "x", -1.0, 1.0).unwrap_err(),
+                   CalcError::NotFinite { value: "NaN".to_string(), expression: "sqrt(x) at x = -1".to_string() });
+        // every sample is finite, but not the area
+        let err = integrate("1e300", "x", 0.0, 1e10).unwrap_err();
+        assert_eq!(err, CalcError::NotFinite { value: "inf".to_string(), expression: "the integral of 1e300 from x = 0 to 10000000000".to_string() });
+    }
+
+    #[test]
+    fn roots() {
+        let root = solve("x^3 - 2 * x - 5", "x", 2.0).unwrap();
+        assert!((root.powi(3) - 2.0 * root - 5.0).abs() < 1e-12);
+        assert!((solve("cos(t) - t", "t", 0.0).unwrap() - 0.7390851332151607).abs() < 1e-12);
+        // max has no symbolic derivative
+        assert!((solve("max(x, 2 * x) - 3", "x", 5.0).unwrap() - 1.5).abs() < 1e-12);
+        assert_eq!(solve("x - 4", "x", 4.0).unwrap(), 4.0);
+    }
+
+    #[test]
+    fn roots_that_cannot_be_found() {
+        assert_eq!(solve("x ^ 2 + 1", "x", 0.0).unwrap_err(), CalcError::NoConvergence(0));
+        let iterations = Iterations { tolerance: 1e-12, max_iterations: 3 };
+        assert_eq!(solve_with("x ^ 2 - 2", "x", 100.0, iterations).unwrap_err(), CalcError::NoConvergence(3));
+        let iterations = Iterations { tolerance: 1e-3, max_iterations: 20 };
+        assert!((solve_with("x ^ 2 - 2", "x", 100.0, iterations).unwrap() - 2f64.sqrt()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn not_differentiable() {
+        let err = Expr::parse("max(x, 1) + 2").unwrap().differentiate("x").unwrap_err();
+        assert_eq!(err, CalcError::NotDifferentiable("max(x, 1)".to_string()));
+        let err = Expr::parse("x % 2").unwrap().differentiate("x").unwrap_err();
+        assert_eq!(err, CalcError::NotDifferentiable("x % 2".to_string()));
+        assert_eq!(derivative("x + 3! + f(2)"), "1");
+    }
+}
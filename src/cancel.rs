@@ -0,0 +1,135 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+use CalcError;
+
+/// Stops a `Calculator` partway through an evaluation, when `cancel` is
+/// called on any clone of the token or once its deadline has passed. It is
+/// checked before every step, including the steps of user defined
+/// functions, which then give `CalcError::Cancelled`. Deadlines need the
+/// `std` feature.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use infix_calculator::{CalcError, CancelToken, Calculator};
+///
+/// let token = CancelToken::with_timeout(Duration::from_millis(100));
+/// let calculator = Calculator::builder().cancel_token(token.clone()).build();
+/// assert!(calculator.calculate("1 + 2").is_ok());
+///
+/// // e.g. from another thread
+/// token.cancel();
+/// assert_eq!(calculator.calculate("1 + 2").unwrap_err(), CalcError::Cancelled);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    #[cfg(feature = "std")]
+    deadline: Option<Instant>,
+}
+
+impl CancelToken {
+    /// A token that only stops evaluations once it is cancelled.
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    /// A token that also stops evaluations once `deadline` has passed.
+    #[cfg(feature = "std")]
+    pub fn with_deadline(deadline: Instant) -> Self {
+        CancelToken { cancelled: Arc::default(), deadline: Some(deadline) }
+    }
+
+    /// A token that also stops evaluations `timeout` from now.
+    #[cfg(feature = "std")]
+    pub fn with_timeout(timeout: Duration) -> Self {
+        CancelToken::with_deadline(Instant::now() + timeout)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Stops the evaluations using this token or any of its clones, now and
+    /// from then on.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed) || self.past_deadline()
+    }
+
+    #[cfg(feature = "std")]
+    fn past_deadline(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn past_deadline(&self) -> bool {
+        false
+    }
+
+    pub(crate) fn check(&self) -> Result<(), CalcError> {
+        if self.is_cancelled() {
+            Err(CalcError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use {CalcError, Calculator, Environment, Value};
+
+    use super::*;
+
+    #[test]
+    fn deadlines() {
+        let token = CancelToken::with_deadline(Instant::now());
+        assert!(token.is_cancelled());
+        let calculator = Calculator::builder().cancel_token(token).build();
+        assert_eq!(calculator.calculate("1").unwrap_err(), CalcError::Cancelled);
+
+        let token = CancelToken::with_timeout(Duration::from_secs(3600));
+        assert!(!token.is_cancelled());
+        let calculator = Calculator::builder().cancel_token(token).build();
+        assert_eq!(calculator.calculate("1 + 2").unwrap(), Value::Float(3.0));
+    }
+
+    /// A calculator with a function `stop()` cancelling `token`.
+    fn stopping(token: &CancelToken) -> Calculator {
+        let stop = token.clone();
+        Calculator::builder()
+            .function("stop", 0, move |_| {
+                stop.cancel();
+                0.0
+            })
+            .cancel_token(token.clone())
+            .build()
+    }
+
+    #[test]
+    fn cancelled_while_evaluating() {
+        let token = CancelToken::new();
+        let calculator = stopping(&token);
+        assert_eq!(calculator.calculate("stop() + 1").unwrap_err(), CalcError::Cancelled);
+        assert!(token.is_cancelled());
+
+        // stopped inside the body of a user defined function
+        let token = CancelToken::new();
+        let calculator = stopping(&token);
+        let mut environment = Environment::new();
+        environment.define("f", vec!["x".to_string()], calculator.parse("stop() + x").unwrap()).unwrap();
+        let queue = calculator.parse("f(2)").unwrap();
+        assert_eq!(calculator.evaluate(&queue, &environment).unwrap_err(), CalcError::Cancelled);
+        assert_eq!(token.deadline(), None);
+    }
+}
@@ -0,0 +1,528 @@
+//! The REPL commands, lines starting with a `:` such as `:help`. Adding one
+//! is adding an entry to `COMMANDS`, which `:help` and Tab completion read.
+
+#[cfg(feature = "session")]
+use std::fs;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+
+use infix_calculator::{Environment, Expr, Mode, NonFinite, Value, ZeroDivision};
+#[cfg(feature = "session")]
+use infix_calculator::Overflow;
+
+use {describe_error, Notation, Session};
+
+/// Whether the REPL goes on after a command.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flow {
+    Continue,
+    Quit,
+}
+
+pub struct Command {
+    pub name: &'static str,
+    /// What goes after the name, for `:help`
+    pub arguments: &'static str,
+    pub help: &'static str,
+    /// Runs the command with the rest of the line, trimmed.
+    pub run: fn(&mut Session, &str) -> Flow,
+}
+
+pub const COMMANDS: &[Command] = &[
+    Command { name: "help", arguments: "", help: "list the commands, operators and functions", run: help },
+    Command { name: "quit", arguments: "", help: "leave the calculator, like Ctrl-D", run: quit },
+    Command { name: "clear", arguments: "", help: "clear the screen", run: clear },
+    Command {
+        name: "load",
+        arguments: "<path>",
+        help: "run every line of a file, as if it was typed in",
+        run: load,
+    },
+    Command {
+        name: "save",
+        arguments: "<path>",
+        help: "write the variables, functions and settings to a JSON file",
+        run: save,
+    },
+    Command { name: "restore", arguments: "<path>", help: "go back to a session from :save", run: restore },
+    Command { name: "reset", arguments: "", help: "forget every variable and function defined", run: reset },
+    Command { name: "m+", arguments: "", help: "add the last result to the memory, mem", run: memory_add },
+    Command { name: "m-", arguments: "", help: "take the last result away from the memory", run: memory_subtract },
+    Command { name: "mr", arguments: "", help: "recall the memory as a result", run: memory_recall },
+    Command { name: "mc", arguments: "", help: "clear the memory, back to 0", run: memory_clear },
+    Command { name: "rpn", arguments: "on|off", help: "show the RPN of each expression or not", run: rpn },
+    Command {
+        name: "timing",
+        arguments: "on|off",
+        help: "show how long parsing and calculating took or not",
+        run: timing,
+    },
+    Command {
+        name: "precision",
+        arguments: "<decimal places>|off",
+        help: "round results to a number of decimal places",
+        run: precision,
+    },
+    Command {
+        name: "format",
+        arguments: "plain|sci|eng",
+        help: "write results plainly, in scientific or in engineering notation",
+        run: format,
+    },
+    Command {
+        name: "mode",
+        arguments: "float|int|decimal",
+        help: "read numbers as floats, integers or exact decimals, there is no rational mode",
+        run: mode,
+    },
+    Command { name: "division", arguments: "inf|error|nan", help: "what dividing by zero gives", run: division },
+    Command {
+        name: "nonfinite",
+        arguments: "propagate|error",
+        help: "whether NaN and infinities are an error",
+        run: nonfinite,
+    },
+    Command { name: "simplify", arguments: "<expression>", help: "print a simpler version of an expression", run: simplify },
+];
+
+/// Runs `line`, the part after the `:`.
+pub fn run(session: &mut Session, line: &str) -> Flow {
+    let line = line.trim();
+    let (name, arguments) = line.split_at(line.find(char::is_whitespace).unwrap_or(line.len()));
+    match COMMANDS.iter().find(|command| command.name == name) {
+        Some(command) => (command.run)(session, arguments.trim()),
+        None => {
+            println!("Unknown command: :{}, see :help", name);
+            Flow::Continue
+        }
+    }
+}
+
+fn help(session: &mut Session, _: &str) -> Flow {
+    println!("Commands:");
+    for command in COMMANDS {
+        let usage = format!(":{} {}", command.name, command.arguments);
+        println!("  {:<34}{}", usage, command.help);
+    }
+    println!("Operators: {}, prefix - ! ~, postfix !, and = to assign or solve", session.calculator.operator_symbols().join(" "));
+    println!("Functions: {}", session.calculator.function_names().join(", "));
+    println!("Constants: {}", session.calculator.constant_names().join(", "));
+    Flow::Continue
+}
+
+fn quit(_: &mut Session, _: &str) -> Flow {
+    Flow::Quit
+}
+
+fn clear(_: &mut Session, _: &str) -> Flow {
+    if io::stdout().is_terminal() {
+        print!("\x1b[2J\x1b[H");
+        io::stdout().flush().unwrap();
+    }
+    Flow::Continue
+}
+
+fn load(session: &mut Session, path: &str) -> Flow {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            session.fail(&format!("Cannot open {}: {}", path, err));
+            return Flow::Continue;
+        }
+    };
+    let mut entry = String::new();
+    for line in BufReader::new(file).lines() {
+        match line {
+            Ok(line) => {
+                if !entry.is_empty() {
+                    entry.push(' ');
+                }
+                entry.push_str(&line);
+            }
+            Err(err) => {
+                session.fail(&format!("Cannot read {}: {}", path, err));
+                return Flow::Continue;
+            }
+        }
+        // expressions go on over the lines after them like in the REPL
+        if entry.trim_start().starts_with(':') || session.calculator.is_complete(&entry) {
+            if session.run(&entry) == Flow::Quit {
+                return Flow::Quit;
+            }
+            entry.clear();
+        }
+    }
+    // the part there is gives the error
+    session.run(&entry)
+}
+
+/// What `:save` keeps of a session.
+#[cfg(feature = "session")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Saved {
+    environment: Environment,
+    mode: Mode,
+    zero_division: ZeroDivision,
+    // sessions saved before it was kept don't have it
+    #[serde(default)]
+    overflow: Overflow,
+    non_finite: NonFinite,
+    precision: Option<usize>,
+    notation: Notation,
+    show_rpn: bool,
+}
+
+#[cfg(feature = "session")]
+fn save(session: &mut Session, path: &str) -> Flow {
+    let saved = Saved {
+        environment: session.environment.clone(),
+        mode: session.calculator.mode(),
+        zero_division: session.calculator.zero_division(),
+        overflow: session.calculator.overflow(),
+        non_finite: session.calculator.non_finite(),
+        precision: session.options.precision,
+        notation: session.options.notation,
+        show_rpn: session.options.show_rpn,
+    };
+    let json = serde_json::to_string_pretty(&saved).unwrap();
+    if let Err(err) = fs::write(path, json + "\n") {
+        session.fail(&format!("Cannot write {}: {}", path, err));
+    }
+    Flow::Continue
+}
+
+#[cfg(feature = "session")]
+fn restore(session: &mut Session, path: &str) -> Flow {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(err) => {
+            session.fail(&format!("Cannot read {}: {}", path, err));
+            return Flow::Continue;
+        }
+    };
+    match serde_json::from_str::<Saved>(&json) {
+        Ok(saved) => {
+            session.environment = saved.environment;
+            session.calculator.set_mode(saved.mode);
+            session.calculator.set_zero_division(saved.zero_division);
+            session.calculator.set_overflow(saved.overflow);
+            session.calculator.set_non_finite(saved.non_finite);
+            session.options.precision = saved.precision;
+            session.options.notation = saved.notation;
+            session.options.show_rpn = saved.show_rpn;
+        }
+        Err(err) => session.fail(&format!("{} is not a saved session: {}", path, err)),
+    }
+    Flow::Continue
+}
+
+#[cfg(not(feature = "session"))]
+fn save(_: &mut Session, _: &str) -> Flow {
+    println!("Saving a session needs the session feature");
+    Flow::Continue
+}
+
+#[cfg(not(feature = "session"))]
+fn restore(_: &mut Session, _: &str) -> Flow {
+    println!("Restoring a session needs the session feature");
+    Flow::Continue
+}
+
+fn reset(session: &mut Session, _: &str) -> Flow {
+    session.environment = Environment::new();
+    Flow::Continue
+}
+
+/// The variable the memory keys keep their number in, so expressions can
+/// use it too.
+const MEMORY: &str = "mem";
+
+fn memory(session: &Session) -> f64 {
+    session.environment.get(MEMORY).unwrap_or(0.0)
+}
+
+/// Adds `sign` times `ans` to the memory.
+fn add_to_memory(session: &mut Session, sign: f64) -> Flow {
+    match session.environment.value("ans") {
+        Some(ans) => {
+            let sum = memory(session) + sign * ans.to_f64();
+            session.environment.set(MEMORY, sum);
+        }
+        None => println!("There is no result yet"),
+    }
+    Flow::Continue
+}
+
+fn memory_add(session: &mut Session, _: &str) -> Flow {
+    add_to_memory(session, 1.0)
+}
+
+fn memory_subtract(session: &mut Session, _: &str) -> Flow {
+    add_to_memory(session, -1.0)
+}
+
+fn memory_recall(session: &mut Session, _: &str) -> Flow {
+    let value = memory(session);
+    session.print_result(&Value::Float(value));
+    Flow::Continue
+}
+
+fn memory_clear(session: &mut Session, _: &str) -> Flow {
+    session.environment.set(MEMORY, 0.0);
+    Flow::Continue
+}
+
+fn rpn(session: &mut Session, arguments: &str) -> Flow {
+    match arguments {
+        "on" => session.options.show_rpn = true,
+        "off" => session.options.show_rpn = false,
+        _ => println!("The RPN can be shown with on or hidden with off"),
+    }
+    Flow::Continue
+}
+
+fn timing(session: &mut Session, arguments: &str) -> Flow {
+    match arguments {
+        "on" => session.options.timing = true,
+        "off" => session.options.timing = false,
+        _ => println!("The timing can be shown with on or hidden with off"),
+    }
+    Flow::Continue
+}
+
+fn precision(session: &mut Session, arguments: &str) -> Flow {
+    match arguments {
+        "off" => session.options.precision = None,
+        places => match places.parse() {
+            Ok(places) => session.options.precision = Some(places),
+            Err(_) => println!("The precision is a number of decimal places or off"),
+        },
+    }
+    Flow::Continue
+}
+
+fn format(session: &mut Session, arguments: &str) -> Flow {
+    match arguments {
+        "plain" => session.options.notation = Notation::Plain,
+        "sci" => session.options.notation = Notation::Scientific,
+        "eng" => session.options.notation = Notation::Engineering,
+        _ => println!("Results can be written plain, sci or eng"),
+    }
+    Flow::Continue
+}
+
+fn mode(session: &mut Session, arguments: &str) -> Flow {
+    match arguments {
+        "float" => session.calculator.set_mode(Mode::Float),
+        "int" => session.calculator.set_mode(Mode::Integer),
+        #[cfg(feature = "decimal")]
+        "decimal" => session.calculator.set_mode(Mode::Decimal),
+        #[cfg(not(feature = "decimal"))]
+        "decimal" => println!("Exact decimals need the decimal feature"),
+        // the library has no rational numbers, so there's nothing to switch to
+        "rational" => println!("There is no rational mode, decimal keeps decimal fractions such as 0.1 exact but not 1/3"),
+        _ => println!("Numbers can be read as float, int or decimal"),
+    }
+    Flow::Continue
+}
+
+fn division(session: &mut Session, arguments: &str) -> Flow {
+    match arguments {
+        "inf" => session.calculator.set_zero_division(ZeroDivision::Infinity),
+        "error" => session.calculator.set_zero_division(ZeroDivision::Error),
+        "nan" => session.calculator.set_zero_division(ZeroDivision::NaN),
+        _ => println!("Division by zero can give inf, error or nan"),
+    }
+    Flow::Continue
+}
+
+fn nonfinite(session: &mut Session, arguments: &str) -> Flow {
+    match arguments {
+        "propagate" => session.calculator.set_non_finite(NonFinite::Propagate),
+        "error" => session.calculator.set_non_finite(NonFinite::Error),
+        _ => println!("NaN and infinities can propagate or be an error"),
+    }
+    Flow::Continue
+}
+
+fn simplify(session: &mut Session, expression: &str) -> Flow {
+    match Expr::parse(expression) {
+        Ok(expr) => println!("Simplified: {}", expr.simplify()),
+        Err(err) => println!("{}", describe_error(expression, expression, &err, session.options.colour && io::stdout().is_terminal())),
+    }
+    Flow::Continue
+}
+
+#[cfg(test)]
+mod tests {
+    use infix_calculator::{Associativity, CalcError, Calculator, Limits, Value};
+
+    use {Options, Output};
+
+    use super::*;
+
+    fn session() -> Session {
+        Session {
+            calculator: Calculator::new(),
+            environment: Environment::new(),
+            options: Options { output: Output::Text, precision: None, notation: Notation::Plain, show_rpn: false, colour: false, timing: false, quiet: true },
+            interactive: true,
+            status: 0,
+        }
+    }
+
+    #[test]
+    fn commands_change_the_session() {
+        let mut session = session();
+        session.environment.set("x", 1.0);
+        assert_eq!(run(&mut session, "rpn on"), Flow::Continue);
+        assert_eq!(run(&mut session, " precision  3 "), Flow::Continue);
+        assert_eq!(run(&mut session, "reset"), Flow::Continue);
+        assert_eq!(run(&mut session, "timing on"), Flow::Continue);
+        assert!(session.options.show_rpn);
+        assert!(session.options.timing);
+        assert_eq!(session.options.precision, Some(3));
+        assert_eq!(session.environment.get("x"), None);
+        assert_eq!(run(&mut session, "division error"), Flow::Continue);
+        assert_eq!(session.calculator.zero_division(), ZeroDivision::Error);
+        assert_eq!(run(&mut session, "mode int"), Flow::Continue);
+        assert_eq!(session.calculator.mode(), Mode::Integer);
+        assert_eq!(run(&mut session, "quit"), Flow::Quit);
+    }
+
+    #[test]
+    fn ast_json_unary_operators() {
+        for &(input, symbol) in &[("-x", "-"), ("x!", "!"), ("!x", "¬"), ("~x", "~")] {
+            assert_eq!(::ast_json(&Expr::parse(input).unwrap()), format!("{{\"unary\": \"{}\", \"operand\": {{\"variable\": \"x\"}}}}", symbol));
+        }
+    }
+
+    #[test]
+    fn exit_statuses() {
+        for &(input, status) in &[("1 +", 2), ("* 2", 2), ("()", 2), ("1 * * 2", 2), ("g(1)", 3), ("x", 3)] {
+            let mut session = Session { interactive: false, ..session() };
+            session.run(input);
+            assert_eq!(session.status, status, "{}", input);
+            let calculated = ::calculate(&session.calculator, input, &session.options);
+            assert_eq!(calculated.err().map(|(status, _)| status), Some(status), "{}", input);
+        }
+    }
+
+    #[test]
+    fn expressions_are_parsed_by_the_calculator() {
+        let calculator = Calculator::builder()
+            .operator("@", 2, Associativity::Left, |a, b| a * 10.0 + b)
+            .fold_constants(true)
+            .build();
+        let mut session = Session { calculator, ..session() };
+        session.run("1 @ 2");
+        assert_eq!(session.environment.get("ans"), Some(12.0));
+        session.run("f(x) = x * (2 + 3)");
+        assert_eq!(session.environment.functions()["f"].body.to_string(), "x 5 *");
+    }
+
+    #[test]
+    fn limits_apply_to_the_repl() {
+        let limits = Limits { max_tokens: Some(3), max_depth: Some(1), ..Limits::default() };
+        let mut session = Session { calculator: Calculator::builder().limits(limits).build(), interactive: false, ..session() };
+        assert_eq!(session.calculator.parse("1 + 2 + 3").unwrap_err(), CalcError::TooManyTokens(3));
+        session.run("1 + 2 + 3");
+        assert_eq!(session.status, 2);
+        session.run("f(x) = ((x))");
+        assert!(session.environment.functions().is_empty());
+        session.run("x = 1 + 2");
+        assert_eq!(session.environment.get("x"), Some(3.0));
+        assert_eq!(session.environment.get("ans"), Some(3.0));
+    }
+
+    #[test]
+    fn unknown_options() {
+        let arguments = |line: &str| line.split(' ').map(String::from).collect::<Vec<_>>();
+        assert_eq!(::unknown_option(&arguments("-e 1 + 2"), true), None);
+        assert_eq!(::unknown_option(&arguments("--file --x"), true), Some("--x"));
+        assert_eq!(::unknown_option(&arguments("--dot 1"), false), Some("--dot"));
+        assert_eq!(::unknown_option(&arguments("-e --x"), true), Some("--x"));
+        assert_eq!(::unknown_option(&arguments("-e --1"), true), None);
+        assert_eq!(::unknown_option(&arguments("sqrt(x) --quite"), false), Some("--quite"));
+    }
+
+    #[test]
+    fn assignments_keep_values() {
+        let mut session = session();
+        session.run("x = [1, 2]");
+        assert_eq!(session.environment.value("x"), Some(session.calculator.calculate("[1, 2]").unwrap()));
+        session.run("y = x * 2");
+        assert_eq!(session.environment.value("y").unwrap().to_string(), "[2, 4]");
+        run(&mut session, "mode int");
+        session.run("n = 9007199254740993");
+        assert_eq!(session.environment.value("n"), Some(Value::Integer(9007199254740993)));
+        session.run("x = 1.5");
+        assert_eq!(session.environment.value("x"), Some(Value::Float(1.5)));
+        assert_eq!(session.environment.get("x"), Some(1.5));
+    }
+
+    #[test]
+    fn memory_keys() {
+        let mut session = session();
+        run(&mut session, "m+");
+        assert_eq!(session.environment.get("mem"), None);
+        session.run("2 * 3");
+        run(&mut session, "m+");
+        run(&mut session, "m+");
+        session.run("mem - 2");
+        run(&mut session, "m-");
+        assert_eq!(session.environment.get("mem"), Some(2.0));
+        run(&mut session, "mr");
+        assert_eq!(session.environment.ans(), Some(2.0));
+        run(&mut session, "mc");
+        assert_eq!(session.environment.get("mem"), Some(0.0));
+    }
+
+    #[test]
+    fn loads_a_file() {
+        let path = ::std::env::temp_dir().join("infix_calculator_load_test.calc");
+        ::std::fs::write(&path, "f(x) = x * 2\nr = f(3)\n\n(r +\n 1) * 2\n:quit\nr = 0\n").unwrap();
+        let mut session = session();
+        assert_eq!(run(&mut session, &format!("load {}", path.display())), Flow::Quit);
+        assert_eq!(session.environment.get("r"), Some(6.0));
+        assert_eq!(session.environment.ans(), Some(14.0));
+        ::std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "session")]
+    #[test]
+    fn saves_and_restores() {
+        let path = ::std::env::temp_dir().join("infix_calculator_session_test.json");
+        let mut session = session();
+        session.environment.eval("f(x) = x + 1").unwrap();
+        session.environment.set("y", 2.5);
+        run(&mut session, "mode int");
+        run(&mut session, "format eng");
+        session.calculator.set_overflow(Overflow::Wrap);
+        assert_eq!(run(&mut session, &format!("save {}", path.display())), Flow::Continue);
+
+        let mut restored = self::session();
+        assert_eq!(run(&mut restored, &format!("restore {}", path.display())), Flow::Continue);
+        assert_eq!(restored.environment, session.environment);
+        assert_eq!(restored.calculator.mode(), Mode::Integer);
+        assert_eq!(restored.calculator.overflow(), Overflow::Wrap);
+        assert_eq!(restored.options.notation, Notation::Engineering);
+        ::std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn formats() {
+        let mut options = session().options;
+        options.notation = Notation::Scientific;
+        assert_eq!(options.show(&Value::Float(12345.6)), "1.23456e4");
+        assert_eq!(options.show(&Value::Integer(-2000)), "-2e3");
+        options.notation = Notation::Engineering;
+        assert_eq!(options.show(&Value::Float(12345.6)), "12.3456e3");
+        assert_eq!(options.show(&Value::Float(0.00025)), "250e-6");
+        options.precision = Some(1);
+        assert_eq!(options.show(&Value::Float(999.96)), "1.0e3");
+        assert_eq!(options.show(&Value::Float(0.0)), "0.0e0");
+        options.notation = Notation::Scientific;
+        assert_eq!(options.show(&Value::Float(12345.6)), "1.2e4");
+    }
+}
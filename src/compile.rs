@@ -0,0 +1,151 @@
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use {compute_result, factorial, short_circuits, whole, BinaryOp, CalcError, Calculator, Expr, Function, UnaryOp};
+
+/// A part of a compiled expression, given the values of its variables.
+type Compiled = Box<dyn Fn(&[f64]) -> Result<f64, CalcError> + Send + Sync>;
+
+fn compile_binary(op: BinaryOp, left: Compiled, right: Compiled) -> Compiled {
+    match op {
+        BinaryOp::Add => Box::new(move |values| Ok(left(values)? + right(values)?)),
+        BinaryOp::Sub => Box::new(move |values| Ok(left(values)? - right(values)?)),
+        BinaryOp::Mul => Box::new(move |values| Ok(left(values)? * right(values)?)),
+        BinaryOp::Div => Box::new(move |values| Ok(left(values)? / right(values)?)),
+        BinaryOp::Rem => Box::new(move |values| Ok(left(values)? % right(values)?)),
+        BinaryOp::Pow => Box::new(move |values| Ok(left(values)?.powf(right(values)?))),
+        BinaryOp::And | BinaryOp::Or => Box::new(move |values| {
+            let first = left(values)? != 0.0;
+            if short_circuits(op.symbol(), first) {
+                return Ok(first as u8 as f64);
+            }
+            Ok((right(values)? != 0.0) as u8 as f64)
+        }),
+        op => Box::new(move |values| compute_result(left(values)?, right(values)?, op.symbol())),
+    }
+}
+
+fn compile_call(name: &str, function: Function, mut arguments: Vec<Compiled>) -> Result<Compiled, CalcError> {
+    if !function.accepts(arguments.len()) {
+        return Err(CalcError::WrongArgumentCount { function: name.to_string(), count: arguments.len() });
+    }
+    let compiled: Compiled = match function {
+        // most calls have one argument, which needs no list of them
+        Function::Unary(f) => {
+            let argument = arguments.remove(0);
+            Box::new(move |values| Ok(f(argument(values)?)))
+        }
+        function => Box::new(move |values| {
+            let arguments = arguments.iter().map(|argument| argument(values)).collect::<Result<Vec<f64>, CalcError>>()?;
+            Ok(function.call(&arguments))
+        }),
+    };
+    Ok(compiled)
+}
+
+impl Expr {
+    /// Turns the expression into a function of `variables`, taking their
+    /// values in the same order. Names and function calls are looked up once
+    /// here instead of every time it is calculated, so it is the quickest way
+    /// to calculate one expression for many values.
+    ///
+    /// Gives `CalcError::UnknownVariable` for a name that is neither one of
+    /// `variables` nor a constant such as `pi`, and `UnknownFunction` for a
+    /// call to anything but a built-in function. Calling the result with the
+    /// wrong number of values is a `CalcError::WrongArgumentCount`.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::Expr;
+    ///
+    /// let area = Expr::parse("pi * r ^ 2 * h").unwrap().compile(&["r", "h"]).unwrap();
+    /// assert_eq!(area(&[1.0, 2.0]).unwrap(), 2.0 * std::f64::consts::PI);
+    /// assert_eq!(area(&[2.0, 1.0]).unwrap(), 4.0 * std::f64::consts::PI);
+    /// ```
+    pub fn compile(&self, variables: &[&str]) -> Result<impl Fn(&[f64]) -> Result<f64, CalcError> + Send + Sync, CalcError> {
+        let count = variables.len();
+        let compiled = self.compile_in(variables, Calculator::standard())?;
+        Ok(move |values: &[f64]| {
+            if values.len() != count {
+                return Err(CalcError::WrongArgumentCount { function: "compiled expression".to_string(), count: values.len() });
+            }
+            compiled(values)
+        })
+    }
+
+    fn compile_in(&self, variables: &[&str], calculator: &Calculator) -> Result<Compiled, CalcError> {
+        let compiled: Compiled = match *self {
+            Expr::Number(number) => Box::new(move |_| Ok(number)),
+            Expr::Variable(ref name) => match variables.iter().position(|variable| variable == name) {
+                Some(index) => Box::new(move |values| Ok(values[index])),
+                None => {
+                    let value = *calculator.constants.get(name).ok_or_else(|| CalcError::UnknownVariable(name.clone()))?;
+                    Box::new(move |_| Ok(value))
+                }
+            },
+            Expr::Unary(UnaryOp::Neg, ref operand) => {
+                let operand = operand.compile_in(variables, calculator)?;
+                Box::new(move |values| Ok(-operand(values)?))
+            }
+            Expr::Unary(UnaryOp::Factorial, ref operand) => {
+                let operand = operand.compile_in(variables, calculator)?;
+                Box::new(move |values| factorial(operand(values)?))
+            }
+            Expr::Unary(UnaryOp::Not, ref operand) => {
+                let operand = operand.compile_in(variables, calculator)?;
+                Box::new(move |values| Ok((operand(values)? == 0.0) as u8 as f64))
+            }
+            Expr::Unary(UnaryOp::BitNot, ref operand) => {
+                let operand = operand.compile_in(variables, calculator)?;
+                Box::new(move |values| Ok(!whole(operand(values)?)? as f64))
+            }
+            Expr::Binary(op, ref left, ref right) => {
+                compile_binary(op, left.compile_in(variables, calculator)?, right.compile_in(variables, calculator)?)
+            }
+            Expr::Call(ref name, ref arguments) => {
+                let function = calculator.functions.get(name).cloned().ok_or_else(|| CalcError::UnknownFunction(name.clone()))?;
+                let arguments = arguments.iter()
+                    .map(|argument| argument.compile_in(variables, calculator))
+                    .collect::<Result<Vec<Compiled>, CalcError>>()?;
+                compile_call(name, function, arguments)?
+            }
+        };
+        Ok(compiled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {CalcError, Environment, Expr};
+
+    #[test]
+    fn same_as_calculating() {
+        let expr = Expr::parse("x * y - max(x, y, 2) / 3 + sqrt(abs(y)) % 2 ^ x - (-x)! + pow(y, 2)").unwrap();
+        let compiled = expr.compile(&["x", "y"]).unwrap();
+        let mut environment = Environment::new();
+        for &(x, y) in &[(-1.0, 2.0), (-3.0, -7.5), (0.0, 0.25), (2.0, 1.0)] {
+            environment.set("x", x);
+            environment.set("y", y);
+            assert_eq!(compiled(&[x, y]), expr.calculate_with(&environment));
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let expr = Expr::parse("x + y").unwrap();
+        assert_eq!(expr.compile(&["x"]).err(), Some(CalcError::UnknownVariable("y".to_string())));
+        let compiled = expr.compile(&["y", "x", "z"]).unwrap();
+        assert_eq!(compiled(&[1.0, 2.0, 3.0]).unwrap(), 3.0);
+        assert_eq!(
+            compiled(&[1.0]).unwrap_err(),
+            CalcError::WrongArgumentCount { function: "compiled expression".to_string(), count: 1 }
+        );
+
+        let expr = Expr::parse("f(2) + 1").unwrap();
+        assert_eq!(expr.compile(&[]).err(), Some(CalcError::UnknownFunction("f".to_string())));
+        let expr = Expr::Call("sqrt".to_string(), vec![Expr::Number(1.0), Expr::Number(2.0)]);
+        assert_eq!(expr.compile(&[]).err(), Some(CalcError::WrongArgumentCount { function: "sqrt".to_string(), count: 2 }));
+        let compiled = Expr::parse("x!").unwrap().compile(&["x"]).unwrap();
+        assert_eq!(compiled(&[-1.0]).unwrap_err(), CalcError::InvalidFactorial(-1.0));
+    }
+}
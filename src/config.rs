@@ -0,0 +1,235 @@
+//! The settings in `config.toml`, read at startup for the defaults of the
+//! command line options. A flag on the command line wins over the file, and
+//! the file over the built-in defaults. Only a small part of TOML is read:
+//! `key = value` lines with strings, whole numbers and booleans, and a
+//! `[constants]` table of names and numbers. `colour` can be spelled
+//! `color` too.
+//!
+//! ```toml
+//! precision = 4
+//! angle = "degrees"
+//! colour = false
+//! show_rpn = true
+//! history_size = 500
+//!
+//! [constants]
+//! g = 9.81
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use infix_calculator::Calculator;
+
+/// What the arguments of `sin`, `cos` and `tan` are in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Angle {
+    Radians,
+    Degrees,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub precision: Option<usize>,
+    pub angle: Angle,
+    /// whether errors and the line being typed are coloured on a terminal,
+    /// `colour` or `color` in the file
+    pub colour: bool,
+    pub show_rpn: bool,
+    /// the most lines the REPL history keeps
+    pub history_size: Option<usize>,
+    pub constants: Vec<(String, f64)>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            precision: None,
+            angle: Angle::Radians,
+            colour: true,
+            show_rpn: false,
+            history_size: None,
+            constants: Vec::new(),
+        }
+    }
+}
+
+/// Where the configuration is without `--config`,
+/// `$XDG_CONFIG_HOME/infix-calculator/config.toml` or else
+/// `~/.config/infix-calculator/config.toml`.
+pub fn default_path() -> Option<PathBuf> {
+    let directory = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(directory) => PathBuf::from(directory),
+        None => {
+            let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+            PathBuf::from(home).join(".config")
+        }
+    };
+    Some(directory.join("infix-calculator").join("config.toml"))
+}
+
+impl Config {
+    /// The settings in the file at `path`, or the defaults if there is no
+    /// such file. Lines that aren't a setting are printed to stderr and left
+    /// out.
+    pub fn read(path: &Path) -> Config {
+        match fs::read_to_string(path) {
+            Ok(text) => {
+                let (config, errors) = Config::parse(&text);
+                for err in errors {
+                    eprintln!("{}: {}", path.display(), err);
+                }
+                config
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Config::default(),
+            Err(err) => {
+                eprintln!("Cannot read {}: {}", path.display(), err);
+                Config::default()
+            }
+        }
+    }
+
+    /// The settings in `text`, and what is wrong with every line that isn't one.
+    fn parse(text: &str) -> (Config, Vec<String>) {
+        let mut config = Config::default();
+        let mut errors = Vec::new();
+        let mut in_constants = false;
+        for (number, line) in text.lines().enumerate() {
+            let line = without_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let result = match line.split_once('=') {
+                _ if line == "[constants]" => {
+                    in_constants = true;
+                    Ok(())
+                }
+                _ if line.starts_with('[') => Err(format!("unknown table {}", line)),
+                Some((name, value)) if in_constants => config.add_constant(name.trim(), value.trim()),
+                Some((key, value)) => config.set(key.trim(), value.trim()),
+                None => Err(format!("expected `key = value`, found `{}`", line)),
+            };
+            if let Err(err) = result {
+                errors.push(format!("line {}: {}", number + 1, err));
+            }
+        }
+        (config, errors)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "precision" => self.precision = Some(whole_number(value)?),
+            "angle" => {
+                self.angle = match string(value)? {
+                    "radians" => Angle::Radians,
+                    "degrees" => Angle::Degrees,
+                    angle => return Err(format!("angle is \"radians\" or \"degrees\", not \"{}\"", angle)),
+                }
+            }
+            "colour" | "color" => self.colour = boolean(value)?,
+            "show_rpn" => self.show_rpn = boolean(value)?,
+            "history_size" => self.history_size = Some(whole_number(value)?),
+            key => return Err(format!("unknown setting {}", key)),
+        }
+        Ok(())
+    }
+
+    fn add_constant(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let is_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_name {
+            return Err(format!("{} is not a name", name));
+        }
+        let value = value.parse().map_err(|_| format!("{} is not a number", value))?;
+        self.constants.push((name.to_string(), value));
+        Ok(())
+    }
+
+    /// A calculator with the constants, taking angles in `angle`.
+    pub fn calculator(&self) -> Calculator {
+        let mut builder = Calculator::builder();
+        for (name, value) in &self.constants {
+            builder = builder.constant(name, *value);
+        }
+        if self.angle == Angle::Degrees {
+            builder = builder
+                .function("sin", 1, |x| x[0].to_radians().sin())
+                .function("cos", 1, |x| x[0].to_radians().cos())
+                .function("tan", 1, |x| x[0].to_radians().tan());
+        }
+        builder.build()
+    }
+}
+
+/// `line` up to a `#` that isn't in a string.
+fn without_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn whole_number(value: &str) -> Result<usize, String> {
+    value.parse().map_err(|_| format!("{} is not a whole number", value))
+}
+
+fn boolean(value: &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("{} is not true or false", value)),
+    }
+}
+
+fn string(value: &str) -> Result<&str, String> {
+    value.strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .filter(|value| !value.contains('"'))
+        .ok_or_else(|| format!("{} is not a string", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use infix_calculator::Value;
+
+    use super::*;
+
+    #[test]
+    fn settings_and_errors() {
+        let text = "# defaults\nprecision = 4\nangle = \"degrees\" # for sin\ncolor = false\nshow_rpn = yes\n\
+                    volume = 11\n\n[constants]\ng = 9.81\n2x = 1\n[aliases]\n";
+        let (config, errors) = Config::parse(text);
+        assert_eq!(config.precision, Some(4));
+        assert_eq!(config.angle, Angle::Degrees);
+        assert!(!config.colour);
+        assert!(!config.show_rpn);
+        assert_eq!(config.constants, vec![("g".to_string(), 9.81)]);
+        assert_eq!(errors, vec![
+            "line 5: yes is not true or false",
+            "line 6: unknown setting volume",
+            "line 10: 2x is not a name",
+            "line 11: unknown table [aliases]",
+        ]);
+    }
+
+    #[test]
+    fn colour_has_both_spellings() {
+        assert!(!Config::parse("colour = false\n").0.colour);
+        assert!(!Config::parse("color = false\n").0.colour);
+        assert!(Config::parse("colour = true\n").0.colour);
+    }
+
+    #[test]
+    fn calculator_has_the_constants_and_angles() {
+        let (config, _) = Config::parse("angle = \"degrees\"\n[constants]\nright = 90\n");
+        let calculator = config.calculator();
+        assert_eq!(calculator.calculate("sin(right)").unwrap(), Value::Float(1.0));
+        assert_eq!(Config::default().calculator().calculate("sin(0)").unwrap(), Value::Float(0.0));
+    }
+}
@@ -0,0 +1,85 @@
+use std::fmt::Write;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use Expr;
+
+impl Expr {
+    /// Writes the tree as a Graphviz DOT graph, with an edge from every
+    /// operator or call to each of its operands, in order. Numbers and
+    /// names are drawn as boxes.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::Expr;
+    ///
+    /// let dot = Expr::parse("1 + 2 * 3").unwrap().to_dot();
+    /// assert!(dot.starts_with("digraph expr {\n    n0 [label=\"+\"];\n"));
+    /// assert!(dot.contains("    n2 -> n4;\n"));
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph expr {\n");
+        self.write_dot(&mut dot, &mut 0);
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Writes the node for the expression and everything under it, numbering
+    /// nodes from `next`. Returns the number of the expression's node.
+    fn write_dot(&self, dot: &mut String, next: &mut usize) -> usize {
+        let id = *next;
+        *next += 1;
+        let operands: Vec<&Expr> = match *self {
+            Expr::Number(number) => {
+                writeln!(dot, "    n{} [label=\"{}\", shape=box];", id, number).unwrap();
+                Vec::new()
+            }
+            Expr::Variable(ref name) => {
+                writeln!(dot, "    n{} [label=\"{}\", shape=box];", id, name).unwrap();
+                Vec::new()
+            }
+            Expr::Unary(op, ref operand) => {
+                writeln!(dot, "    n{} [label=\"{}\"];", id, op.written()).unwrap();
+                vec![&**operand]
+            }
+            Expr::Binary(op, ref left, ref right) => {
+                writeln!(dot, "    n{} [label=\"{}\"];", id, op.symbol()).unwrap();
+                vec![&**left, &**right]
+            }
+            Expr::Call(ref name, ref arguments) => {
+                writeln!(dot, "    n{} [label=\"{}()\"];", id, name).unwrap();
+                arguments.iter().collect()
+            }
+        };
+        for operand in operands {
+            let child = operand.write_dot(dot, next);
+            writeln!(dot, "    n{} -> n{};", id, child).unwrap();
+        }
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_dot() {
+        assert_eq!(Expr::parse("-max(x, 2)").unwrap().to_dot(), concat!(
+            "digraph expr {\n",
+            "    n0 [label=\"-\"];\n",
+            "    n1 [label=\"max()\"];\n",
+            "    n2 [label=\"x\", shape=box];\n",
+            "    n1 -> n2;\n",
+            "    n3 [label=\"2\", shape=box];\n",
+            "    n1 -> n3;\n",
+            "    n0 -> n1;\n",
+            "}\n",
+        ));
+        let labels: Vec<String> = Expr::parse("!n! & ~m").unwrap().to_dot().lines()
+            .filter_map(|line| line.split("label=\"").nth(1))
+            .map(|label| label[..label.find('"').unwrap()].to_string())
+            .collect();
+        assert_eq!(labels, ["&", "¬", "!", "n", "~", "m"]);
+    }
+}
@@ -0,0 +1,194 @@
+//! The parts of the REPL's line editor that know about expressions.
+
+use std::borrow::Cow;
+
+use infix_calculator::{tokenize_lossy, CalcError, Token};
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+use commands::COMMANDS;
+
+const NUMBER: &str = "36";
+const OPERATOR: &str = "33";
+const INVALID: &str = "1;31";
+/// The colours of brackets, by how deeply they are nested.
+const BRACKETS: &[&str] = &["35", "34", "32"];
+
+/// `line` with ANSI colours: numbers, operators and brackets by their depth,
+/// and in red whatever isn't a token and brackets closing nothing. Commands
+/// are left as they are.
+fn highlight(line: &str) -> String {
+    if line.trim_start().starts_with(':') {
+        return line.to_string();
+    }
+    let (tokens, errors) = tokenize_lossy(line);
+    // the colour of every char, spans being in chars
+    let mut colours: Vec<Option<&str>> = vec![None; line.chars().count()];
+    let mut depth = 0;
+    for (token, span) in tokens {
+        let colour = match token {
+            Token::Number(_) => NUMBER,
+            Token::Op(_) | Token::CustomOp(_) | Token::Comma => OPERATOR,
+            Token::LParen | Token::LBracket => {
+                depth += 1;
+                BRACKETS[(depth - 1) % BRACKETS.len()]
+            }
+            Token::RParen | Token::RBracket if depth == 0 => INVALID,
+            Token::RParen | Token::RBracket => {
+                depth -= 1;
+                BRACKETS[depth % BRACKETS.len()]
+            }
+            _ => continue,
+        };
+        colours[span.start..span.end].iter_mut().for_each(|c| *c = Some(colour));
+    }
+    for err in errors {
+        match err {
+            // assignments and equations are split off before tokenizing
+            CalcError::InvalidToken { ref token, span } if token == "=" => {
+                colours[span.start..span.end].iter_mut().for_each(|c| *c = Some(OPERATOR))
+            }
+            err => {
+                if let Some(span) = err.span() {
+                    colours[span.start..span.end].iter_mut().for_each(|c| *c = Some(INVALID))
+                }
+            }
+        }
+    }
+    let mut highlighted = String::with_capacity(line.len() * 2);
+    let mut current = None;
+    for (c, colour) in line.chars().zip(colours) {
+        if colour != current {
+            if current.is_some() {
+                highlighted.push_str("\x1b[0m");
+            }
+            if let Some(colour) = colour {
+                highlighted.push_str(&format!("\x1b[{}m", colour));
+            }
+            current = colour;
+        }
+        highlighted.push(c);
+    }
+    if current.is_some() {
+        highlighted.push_str("\x1b[0m");
+    }
+    highlighted
+}
+
+/// Colours the line as it is typed and completes function, variable and
+/// constant names and commands with Tab. The names change as the REPL
+/// defines new ones, see `set_names`.
+#[derive(Default)]
+pub struct ReplHelper {
+    functions: Vec<String>,
+    variables: Vec<String>,
+}
+
+impl ReplHelper {
+    /// Completes `functions` with an opening bracket and `variables`,
+    /// including constants, as they are.
+    pub fn set_names(&mut self, functions: Vec<String>, variables: Vec<String>) {
+        self.functions = functions;
+        self.variables = variables;
+    }
+
+    /// The start of the word before `pos` in `line` and everything it could
+    /// be completed to, commands after a `:` at the start of the line.
+    fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<Pair>) {
+        let before = &line[..pos];
+        let start = before
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| c.is_alphanumeric() || c == '_')
+            .last()
+            .map_or(pos, |(i, _)| i);
+        let word = &before[start..];
+        if before.trim_start() == format!(":{}", word) {
+            let start = before.len() - word.len() - 1;
+            let mut commands: Vec<Pair> = COMMANDS.iter()
+                .filter(|command| command.name.starts_with(word))
+                .map(|command| pair(&format!(":{}", command.name), format!(":{} ", command.name)))
+                .collect();
+            commands.sort_by(|a, b| a.display.cmp(&b.display));
+            return (start, commands);
+        }
+        if word.is_empty() {
+            return (pos, Vec::new());
+        }
+        let functions = self.functions.iter()
+            .filter(|name| name.starts_with(word))
+            .map(|name| pair(name, format!("{}(", name)));
+        let variables = self.variables.iter()
+            .filter(|name| name.starts_with(word))
+            .map(|name| pair(name, name.clone()));
+        let mut candidates: Vec<Pair> = functions.chain(variables).collect();
+        candidates.sort_by(|a, b| a.display.cmp(&b.display));
+        candidates.dedup_by(|a, b| a.display == b.display);
+        (start, candidates)
+    }
+}
+
+fn pair(display: &str, replacement: String) -> Pair {
+    Pair { display: display.to_string(), replacement }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _: usize) -> Cow<'l, str> {
+        Cow::Owned(highlight(line))
+    }
+
+    fn highlight_char(&self, _: &str, _: usize, kind: CmdKind) -> bool {
+        // any char can change the colours, moving the cursor doesn't
+        kind != CmdKind::MoveCursor
+    }
+}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completions(helper: &ReplHelper, line: &str) -> (usize, Vec<String>) {
+        let (start, candidates) = helper.candidates(line, line.len());
+        (start, candidates.into_iter().map(|pair| pair.replacement).collect())
+    }
+
+    #[test]
+    fn completes_names_and_commands() {
+        let mut helper = ReplHelper::default();
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        helper.set_names(names(&["sin", "sqrt"]), names(&["pi", "speed"]));
+        assert_eq!(completions(&helper, "2 * sq"), (4, vec!["sqrt(".to_string()]));
+        assert_eq!(completions(&helper, "s").1, vec!["sin(", "speed", "sqrt("]);
+        assert_eq!(completions(&helper, "1 + ").1, Vec::<String>::new());
+        assert_eq!(completions(&helper, ":pr"), (0, vec![":precision ".to_string()]));
+        assert_eq!(completions(&helper, " :si"), (1, vec![":simplify ".to_string()]));
+    }
+
+    #[test]
+    fn highlights_tokens() {
+        assert_eq!(highlight("2 * x"), "\x1b[36m2\x1b[0m \x1b[33m*\x1b[0m x");
+        assert_eq!(highlight("((1)"), "\x1b[35m(\x1b[0m\x1b[34m(\x1b[0m\x1b[36m1\x1b[0m\x1b[34m)\x1b[0m");
+        assert_eq!(highlight("1 $)"), "\x1b[36m1\x1b[0m \x1b[1;31m$)\x1b[0m");
+        assert_eq!(highlight("x = 1"), "x \x1b[33m=\x1b[0m \x1b[36m1\x1b[0m");
+        assert_eq!(highlight(":precision 2"), ":precision 2");
+    }
+}
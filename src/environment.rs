@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use {is_identifier, CalcError, Calculator, RPNQueue, Value};
+
+/// Name of the variable holding the previous result
+const ANS: &str = "ans";
+
+/// A function defined with `name(parameters) = body`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UserFunction {
+    pub parameters: Vec<String>,
+    pub body: RPNQueue,
+}
+
+/// Variables and user defined functions that persist between evaluated expressions.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Environment {
+    variables: HashMap<String, f64>,
+    /// the variables set with `set_value` to something other than a float,
+    /// kept as they are for `Calculator`
+    #[cfg_attr(feature = "serde", serde(default))]
+    values: HashMap<String, Value>,
+    functions: HashMap<String, UserFunction>,
+    /// how many results `push_result` numbered
+    #[cfg_attr(feature = "serde", serde(default))]
+    results: usize,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Environment::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.variables.get(name).cloned()
+    }
+
+    pub fn set(&mut self, name: &str, value: f64) {
+        self.variables.insert(name.to_string(), value);
+        self.values.remove(name);
+    }
+
+    /// The variable as `Calculator` sees it, e.g. an interval or an integer
+    /// past 2^53 exactly, where `get` only has a float.
+    pub fn value(&self, name: &str) -> Option<Value> {
+        self.values.get(name).cloned().or_else(|| self.get(name).map(Value::Float))
+    }
+
+    /// Sets a variable to any `Value`, like `set` for floats. `RPNQueue` and
+    /// `Expr`, which only calculate with floats, get it as `Value::to_f64`,
+    /// or don't know it if it has no float value, like an interval.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::{Calculator, Environment};
+    ///
+    /// let calculator = Calculator::new();
+    /// let mut environment = Environment::new();
+    /// environment.set_value("x", calculator.calculate("[1, 2]").unwrap());
+    /// assert_eq!(calculator.calculate_with("x * 2", &environment).unwrap().to_string(), "[2, 4]");
+    /// assert_eq!(environment.get("x"), None);
+    /// ```
+    pub fn set_value(&mut self, name: &str, value: Value) {
+        match value {
+            Value::Float(n) => self.set(name, n),
+            value => {
+                match value.to_f64() {
+                    n if n.is_nan() => self.variables.remove(name),
+                    n => self.variables.insert(name.to_string(), n),
+                };
+                self.values.insert(name.to_string(), value);
+            }
+        }
+    }
+
+    /// The last successful result, available to expressions as `ans`.
+    pub fn ans(&self) -> Option<f64> {
+        self.get(ANS)
+    }
+
+    /// Records `value` as the last result. `eval` does this on its own,
+    /// embedders running their own loop should call it after each success.
+    pub fn set_ans<V: Into<Value>>(&mut self, value: V) {
+        self.set_value(ANS, value.into());
+    }
+
+    /// Records `value` as the last result and as the next numbered one,
+    /// available as `$1` for the first, and returns its number. Like
+    /// `set_value` it is kept as it is, e.g. an interval stays one.
+    pub fn push_result<V: Into<Value>>(&mut self, value: V) -> usize {
+        let value = value.into();
+        self.results += 1;
+        self.set_value(&format!("${}", self.results), value.clone());
+        self.set_ans(value);
+        self.results
+    }
+
+    pub fn variables(&self) -> &HashMap<String, f64> {
+        &self.variables
+    }
+
+    /// The variables set with `set_value` to something other than a float.
+    pub fn values(&self) -> &HashMap<String, Value> {
+        &self.values
+    }
+
+    pub fn functions(&self) -> &HashMap<String, UserFunction> {
+        &self.functions
+    }
+
+    /// Defines (or replaces) the function `name`. Built-in functions can't be redefined.
+    pub fn define(&mut self, name: &str, parameters: Vec<String>, body: RPNQueue) -> Result<(), CalcError> {
+        self.define_with(name, parameters, body, Calculator::standard())
+    }
+
+    /// Like `define`, where the functions of `calculator` can't be redefined.
+    pub fn define_with(&mut self, name: &str, parameters: Vec<String>, body: RPNQueue, calculator: &Calculator)
+        -> Result<(), CalcError> {
+        if calculator.functions.contains_key(name) {
+            return Err(CalcError::BuiltinRedefinition(name.to_string()));
+        }
+        for (i, parameter) in parameters.iter().enumerate() {
+            if parameters[..i].contains(parameter) {
+                return Err(CalcError::DuplicateParameter(parameter.clone()));
+            }
+        }
+        self.functions.insert(name.to_string(), UserFunction { parameters, body });
+        Ok(())
+    }
+
+    /// Evaluates an expression, an assignment such as `x = 5` or a function
+    /// definition such as `f(x) = x ^ 2 + 1`. Returns the value of the expression
+    /// or the one being assigned, definitions don't have a value. The result
+    /// can be referred to as `ans` in the next expression.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::Environment;
+    ///
+    /// let mut environment = Environment::new();
+    /// environment.eval("x = 5").unwrap();
+    /// environment.eval("f(a) = a ^ 2 + 1").unwrap();
+    /// assert_eq!(environment.eval("f(x) * 2").unwrap(), Some(52.0));
+    /// ```
+    pub fn eval(&mut self, input: &str) -> Result<Option<f64>, CalcError> {
+        let (target, expression) = split_assignment(input)?;
+        let rpn = RPNQueue::from_infix_string(expression)?;
+        match target {
+            Some(Target::Function(name, parameters)) => {
+                let parameters = parameters.into_iter().map(String::from).collect();
+                self.define(name, parameters, rpn)?;
+                Ok(None)
+            }
+            Some(Target::Variable(name)) => {
+                let result = rpn.calculate_with(self)?;
+                self.set(name, result);
+                self.set_ans(result);
+                Ok(Some(result))
+            }
+            None => {
+                let result = rpn.calculate_with(self)?;
+                self.set_ans(result);
+                Ok(Some(result))
+            }
+        }
+    }
+
+    /// Like `eval`, parsing and calculating with `calculator`, so within its
+    /// `Limits` and with its mode, operators and functions. Results are kept
+    /// as they are, e.g. an interval stays one.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::{CalcError, Calculator, Environment, Limits, Value};
+    ///
+    /// let calculator = Calculator::builder().limits(Limits { max_tokens: Some(3), ..Limits::default() }).build();
+    /// let mut environment = Environment::new();
+    /// assert_eq!(environment.eval_with("x = 2 * 3", &calculator).unwrap(), Some(Value::Float(6.0)));
+    /// assert_eq!(environment.eval_with("1 + 2 + 3", &calculator).unwrap_err(), CalcError::TooManyTokens(3));
+    /// ```
+    pub fn eval_with(&mut self, input: &str, calculator: &Calculator) -> Result<Option<Value>, CalcError> {
+        let (target, expression) = split_assignment(input)?;
+        let rpn = calculator.parse(expression)?;
+        if let Some(Target::Function(name, parameters)) = target {
+            let parameters = parameters.into_iter().map(String::from).collect();
+            self.define_with(name, parameters, rpn, calculator)?;
+            return Ok(None);
+        }
+        let result = calculator.evaluate(&rpn, self)?;
+        if let Some(Target::Variable(name)) = target {
+            self.set_value(name, result.clone());
+        }
+        self.set_ans(result.clone());
+        Ok(Some(result))
+    }
+}
+
+/// The left hand side of an assignment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target<'a> {
+    /// `x = ...`
+    Variable(&'a str),
+    /// `f(x, y) = ...`
+    Function(&'a str, Vec<&'a str>),
+}
+
+fn is_name(name: &str) -> bool {
+    is_identifier(name) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_target(target: &str) -> Result<Target<'_>, CalcError> {
+    let invalid = || CalcError::InvalidAssignment(target.to_string());
+    if is_name(target) {
+        return Ok(Target::Variable(target));
+    }
+
+    let open = target.find('(').ok_or_else(invalid)?;
+    if !target.ends_with(')') {
+        return Err(invalid());
+    }
+    let name = target[..open].trim();
+    let parameters = &target[open + 1..target.len() - 1];
+    let parameters: Vec<&str> = if parameters.trim().is_empty() {
+        Vec::new()
+    } else {
+        parameters.split(',').map(str::trim).collect()
+    };
+    if !is_name(name) || !parameters.iter().all(|parameter| is_name(parameter)) {
+        return Err(invalid());
+    }
+    Ok(Target::Function(name, parameters))
+}
+
+/// Where the `=` in `input` are, leaving out those in `==`, `!=`, `<=` and `>=`.
+pub(crate) fn equals_signs(input: &str) -> Vec<usize> {
+    let bytes = input.as_bytes();
+    let mut signs = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'=' | b'!' | b'<' | b'>' if bytes.get(i + 1) == Some(&b'=') => i += 1,
+            b'=' => signs.push(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    signs
+}
+
+/// Splits `target = expression` into the assignment target and the expression.
+/// Input without an `=` is returned as an expression without a target, and
+/// so is a comparison such as `x == 1`.
+pub fn split_assignment(input: &str) -> Result<(Option<Target<'_>>, &str), CalcError> {
+    match equals_signs(input).first().cloned() {
+        Some(index) => {
+            let target = parse_target(input[..index].trim())?;
+            Ok((Some(target), &input[index + 1..]))
+        }
+        None => Ok((None, input)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assignments_are_remembered() {
+        let mut environment = Environment::new();
+        assert_eq!(environment.eval("x = 2 + 3").unwrap(), Some(5.0));
+        assert_eq!(environment.eval("  y=x*x ").unwrap(), Some(25.0));
+        assert_eq!(environment.get("y"), Some(25.0));
+        assert_eq!(environment.eval("x = x + 1").unwrap(), Some(6.0));
+        assert_eq!(environment.eval("x - y").unwrap(), Some(-19.0));
+    }
+
+    #[test]
+    fn comparisons_are_not_assignments() {
+        assert_eq!(split_assignment("x == 1").unwrap(), (None, "x == 1"));
+        assert_eq!(equals_signs("y = x >= 1 != (x <= 2)"), vec![2]);
+        let mut environment = Environment::new();
+        assert_eq!(environment.eval("y = 2 * 3 == 6").unwrap(), Some(1.0));
+    }
+
+    #[test]
+    fn invalid_assignments() {
+        let mut environment = Environment::new();
+        assert_eq!(environment.eval("z + 1").unwrap_err(), CalcError::UnknownVariable("z".to_string()));
+        assert!(environment.eval("2 = 3").is_err());
+        assert!(environment.eval("a b = 3").is_err());
+        assert!(environment.eval("= 3").is_err());
+        assert!(environment.get("a").is_none());
+    }
+
+    #[test]
+    fn eval_with_a_calculator() {
+        let calculator = Calculator::builder().function("tax", 1, |args| args[0] * 0.2).build();
+        let mut environment = Environment::new();
+        assert_eq!(environment.eval_with("x = tax(100)", &calculator).unwrap(), Some(Value::Float(20.0)));
+        assert_eq!(environment.eval_with("tax(x) = x", &calculator).unwrap_err(), CalcError::BuiltinRedefinition("tax".to_string()));
+        assert_eq!(environment.eval_with("f(a) = tax(a) + x", &calculator).unwrap(), None);
+        assert_eq!(environment.eval_with("f(10)", &calculator).unwrap(), Some(Value::Float(22.0)));
+        assert_eq!(environment.get("ans"), Some(22.0));
+    }
+
+    #[test]
+    fn split_assignment_targets() {
+        assert_eq!(split_assignment("f(x, y) = x").unwrap(), (Some(Target::Function("f", vec!["x", "y"])), " x"));
+        assert_eq!(split_assignment("g() = 1").unwrap(), (Some(Target::Function("g", vec![])), " 1"));
+        assert!(split_assignment("f(x, 2) = x").is_err());
+        assert!(split_assignment("f(x = x").is_err());
+    }
+
+    #[test]
+    fn user_defined_functions() {
+        let mut environment = Environment::new();
+        assert_eq!(environment.eval("f(x) = x^2 + 1").unwrap(), None);
+        assert_eq!(environment.eval("hyp(a, b) = sqrt(a^2 + b^2)").unwrap(), None);
+        assert_eq!(environment.eval("f(3) + hyp(3, 4)").unwrap(), Some(15.0));
+
+        // globals are visible inside the body, parameters shadow them
+        environment.eval("x = 10").unwrap();
+        environment.eval("offset(y) = x + y").unwrap();
+        assert_eq!(environment.eval("offset(1) + f(2)").unwrap(), Some(16.0));
+
+        assert!(environment.eval("f(1, 2)").is_err());
+        assert_eq!(environment.eval("sqrt(x) = x").unwrap_err(), CalcError::BuiltinRedefinition("sqrt".to_string()));
+        assert!(environment.eval("h(x, x) = x").is_err());
+
+        environment.eval("answer() = 42").unwrap();
+        assert_eq!(environment.eval("answer() / 2").unwrap(), Some(21.0));
+    }
+
+    #[test]
+    fn ans_holds_the_previous_result() {
+        let mut environment = Environment::new();
+        assert!(environment.eval("ans").is_err());
+        environment.eval("1 + 2").unwrap();
+        assert_eq!(environment.eval("ans * 2").unwrap(), Some(6.0));
+        environment.eval("x = ans + 1").unwrap();
+        assert_eq!(environment.ans(), Some(7.0));
+
+        // failures and definitions leave it untouched
+        assert!(environment.eval("1 / y").is_err());
+        environment.eval("f(x) = x").unwrap();
+        assert_eq!(environment.eval("ans").unwrap(), Some(7.0));
+    }
+
+    #[test]
+    fn recursion_is_bounded() {
+        let mut environment = Environment::new();
+        environment.eval("loop(x) = loop(x + 1)").unwrap();
+        assert_eq!(environment.eval("loop(0)").unwrap_err(), CalcError::RecursionLimit("loop".to_string()));
+    }
+
+    #[test]
+    fn numbered_results() {
+        let mut environment = Environment::new();
+        assert_eq!(environment.push_result(6.0), 1);
+        assert_eq!(environment.push_result(7.0), 2);
+        assert_eq!(environment.eval("$1 * $2 + ans").unwrap(), Some(49.0));
+        assert_eq!(environment.eval("$3").unwrap_err(), CalcError::UnknownVariable("$3".to_string()));
+        assert!(environment.eval("$1 = 2").is_err());
+
+        // the results aren't turned into floats
+        let calculator = Calculator::new();
+        assert_eq!(environment.push_result(calculator.calculate("[1, 2]").unwrap()), 3);
+        environment.push_result(calculator.calculate("10 ± 0.5").unwrap());
+        assert_eq!(calculator.calculate_with("$3 * 2", &environment).unwrap().to_string(), "[2, 4]");
+        assert_eq!(calculator.calculate_with("ans + $4", &environment).unwrap().to_string(), "20 ± 0.7071067811865476");
+        environment.push_result(Value::Integer(i64::MAX));
+        assert_eq!(environment.value("ans"), Some(Value::Integer(i64::MAX)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut environment = Environment::new();
+        environment.eval("x = 2").unwrap();
+        environment.eval("f(a, b) = a * b + x").unwrap();
+        environment.set_value("y", Calculator::new().calculate("[1, 2] * 3").unwrap());
+        environment.set_value("z", Calculator::new().calculate("3 ± 0.5").unwrap());
+        environment.set_value("n", Value::Integer(i64::MAX));
+        let json = serde_json::to_string(&environment).unwrap();
+        let restored: Environment = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, environment);
+        assert_eq!(restored.clone().eval("f(3, 4)").unwrap(), Some(14.0));
+    }
+}
@@ -0,0 +1,179 @@
+use std::fmt;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use environment::equals_signs;
+use {BinaryOp, CalcError, Calculator, Environment, Expr, UnaryOp};
+
+/// Two expressions that should be equal, such as `2 * x + 5 = 13`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Equation {
+    pub left: Expr,
+    pub right: Expr,
+}
+
+/// The value of the unknown that solves an `Equation`, shown as `x = 4`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+    pub variable: String,
+    pub value: f64,
+}
+
+impl fmt::Display for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} = {}", self.variable, self.value)
+    }
+}
+
+impl fmt::Display for Equation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} = {}", self.left, self.right)
+    }
+}
+
+/// `expr` as `a * variable + b`, if it is linear in `variable`.
+fn linear(expr: &Expr, variable: &str, environment: &Environment) -> Result<(f64, f64), CalcError> {
+    if !expr.variables().iter().any(|name| name == variable) {
+        return Ok((0.0, expr.calculate_with(environment)?));
+    }
+    let not_linear = || CalcError::InvalidEquation(format!("not linear in {}: {}", variable, expr));
+    match *expr {
+        Expr::Variable(_) => Ok((1.0, 0.0)),
+        Expr::Unary(UnaryOp::Neg, ref operand) => {
+            let (a, b) = linear(operand, variable, environment)?;
+            Ok((-a, -b))
+        }
+        Expr::Binary(op, ref left, ref right) => {
+            let (a, b) = linear(left, variable, environment)?;
+            let (c, d) = linear(right, variable, environment)?;
+            match op {
+                BinaryOp::Add => Ok((a + c, b + d)),
+                BinaryOp::Sub => Ok((a - c, b - d)),
+                // one side of a product or the bottom of a fraction has to be a number
+                BinaryOp::Mul if a == 0.0 => Ok((b * c, b * d)),
+                BinaryOp::Mul if c == 0.0 => Ok((a * d, b * d)),
+                BinaryOp::Div if c == 0.0 && d == 0.0 => Err(CalcError::DivisionByZero),
+                BinaryOp::Div if c == 0.0 => Ok((a / d, b / d)),
+                BinaryOp::Pow if c == 0.0 && d == 1.0 => Ok((a, b)),
+                _ => Err(not_linear()),
+            }
+        }
+        _ => Err(not_linear()),
+    }
+}
+
+impl Equation {
+    /// Parses an equation with one `=`, each side like `Expr::parse`.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::Equation;
+    ///
+    /// let equation = Equation::parse("2*x + 5 = 13").unwrap();
+    /// assert_eq!(equation.to_string(), "2 * x + 5 = 13");
+    /// ```
+    pub fn parse(input: &str) -> Result<Self, CalcError> {
+        match equals_signs(input)[..] {
+            [index] => {
+                let (left, right) = (&input[..index], &input[index + 1..]);
+                // positions in the right side count from the start of the input
+                let offset = left.chars().count() + 1;
+                let left = Expr::parse(left)?;
+                Ok(Equation { left, right: Expr::parse(right).map_err(|err| err.shifted(offset))? })
+            }
+            [] => Err(CalcError::InvalidEquation("missing =".to_string())),
+            _ => Err(CalcError::InvalidEquation("more than one =".to_string())),
+        }
+    }
+
+    /// Solves an equation that is linear in its only unknown, see `solve_with`.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::Equation;
+    ///
+    /// let solution = Equation::parse("2*x + 5 = 13").unwrap().solve().unwrap();
+    /// assert_eq!(solution.to_string(), "x = 4");
+    /// ```
+    pub fn solve(&self) -> Result<Solution, CalcError> {
+        self.solve_with(&Environment::new())
+    }
+
+    /// Solves an equation that is linear in its only unknown, the one name
+    /// that is neither a constant nor a variable of `environment`. Anything
+    /// else, such as `x ^ 2 = 4` or `x = x + 1`, is a
+    /// `CalcError::InvalidEquation`.
+    pub fn solve_with(&self, environment: &Environment) -> Result<Solution, CalcError> {
+        let constants = &Calculator::standard().constants;
+        let mut unknowns = self.left.variables();
+        for name in self.right.variables() {
+            if !unknowns.contains(&name) {
+                unknowns.push(name);
+            }
+        }
+        unknowns.retain(|name| environment.get(name).is_none() && !constants.contains_key(name));
+        let variable = match unknowns.len() {
+            1 => unknowns.remove(0),
+            0 => return Err(CalcError::InvalidEquation("no unknown".to_string())),
+            _ => return Err(CalcError::InvalidEquation(format!("more than one unknown: {}", unknowns.join(", ")))),
+        };
+
+        // left - right = 0, so a * variable + b = 0
+        let (a, b) = linear(&self.left, &variable, environment)?;
+        let (c, d) = linear(&self.right, &variable, environment)?;
+        let (a, b) = (a - c, b - d);
+        if a == 0.0 {
+            let which = if b == 0.0 { "any" } else { "no" };
+            return Err(CalcError::InvalidEquation(format!("{} value of {} solves it", which, variable)));
+        }
+        Ok(Solution { variable, value: -b / a })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Span;
+
+    use super::*;
+
+    fn solved(input: &str) -> String {
+        Equation::parse(input).unwrap().solve().unwrap().to_string()
+    }
+
+    fn invalid(input: &str) -> String {
+        match Equation::parse(input).and_then(|equation| equation.solve()) {
+            Err(CalcError::InvalidEquation(reason)) => reason,
+            result => panic!("{} gave {:?}", input, result),
+        }
+    }
+
+    #[test]
+    fn linear_equations() {
+        assert_eq!(solved("2*x + 5 = 13"), "x = 4");
+        assert_eq!(solved("3 = (y - 1) / 2"), "y = 7");
+        assert_eq!(solved("-(4 - t) * 3 = t + pi - pi"), "t = 6");
+        assert_eq!(solved("x ^ 1 * 2 = x + 1"), "x = 1");
+    }
+
+    #[test]
+    fn known_variables() {
+        let mut environment = Environment::new();
+        environment.set("a", 2.0);
+        let equation = Equation::parse("a * x = 10").unwrap();
+        assert_eq!(equation.solve_with(&environment).unwrap(), Solution { variable: "x".to_string(), value: 5.0 });
+    }
+
+    #[test]
+    fn unsolvable() {
+        assert_eq!(invalid("x ^ 2 = 4"), "not linear in x: x ^ 2");
+        assert_eq!(invalid("x * x = 4"), "not linear in x: x * x");
+        assert_eq!(invalid("2 * x + y = 1"), "more than one unknown: x, y");
+        assert_eq!(invalid("1 + 1 = 2"), "no unknown");
+        assert_eq!(invalid("x = x + 1"), "no value of x solves it");
+        assert_eq!(invalid("2 * x = x + x"), "any value of x solves it");
+        assert_eq!(invalid("x = 1 = 1"), "more than one =");
+        let err = Equation::parse("2 * x = 1 $").unwrap_err();
+        assert_eq!(err.span(), Some(Span::new(10, 11)));
+        assert_eq!(Equation::parse("x / (2 - 2) = 1").unwrap().solve().unwrap_err(), CalcError::DivisionByZero);
+    }
+}
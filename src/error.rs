@@ -0,0 +1,175 @@
+use std::error::Error;
+use std::fmt;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use Span;
+
+/// Everything that can go wrong while parsing or calculating an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CalcError {
+    /// A character or literal that can't appear in an expression
+    InvalidToken { token: String, span: Span },
+    /// A number literal too large for an `f64`, e.g. `1e400`
+    NumberOutOfRange { literal: String, span: Span },
+    /// An entry of an `RPNQueue` that is neither a number, a name nor an operator
+    InvalidRpnToken(String),
+    /// Something an `Expr` has no node for, such as an imaginary number
+    NotInTree(String),
+    /// An operator or function didn't have enough operands to work on
+    NotEnoughOperands,
+    /// An `RPNQueue` left more than one value behind
+    TooManyOperands,
+    /// Two operands follow each other without an operator between them
+    MissingOperator(Span),
+    /// An operator without its operand, e.g. `1 +`, `* 2` or `()`, where the
+    /// operand should have been
+    MissingOperand(Span),
+    /// An empty argument in a function call, e.g. `max(1, )`
+    MissingArgument(Span),
+    /// A function name used without brackets, e.g. `sqrt 4`
+    MissingBrackets { name: String, span: Span },
+    /// A `(` that is never closed or a `)` that closes nothing
+    UnbalancedParen { position: usize },
+    UnknownOperator(String),
+    UnknownVariable(String),
+    /// Every name `RPNQueue::calculate_with_vars` had no value for, in order of appearance
+    MissingVariables(Vec<String>),
+    UnknownFunction(String),
+    WrongArgumentCount { function: String, count: usize },
+    /// A user defined function kept calling itself
+    RecursionLimit(String),
+    /// Integer division or remainder by zero, dividing by an interval holding
+    /// zero, or any division by zero with `ZeroDivision::Error`
+    DivisionByZero,
+    /// An integer result didn't fit in an `i64` with `Overflow::Error`
+    Overflow,
+    /// Part of an expression gave NaN or an infinity with `NonFinite::Error`
+    NotFinite { value: String, expression: String },
+    /// An interval whose lower bound is above the upper one
+    InvalidInterval { lo: f64, hi: f64 },
+    /// Factorial of a negative or fractional number
+    InvalidFactorial(f64),
+    /// An operand of `&`, `|`, `xor` or `~` that isn't a whole number an
+    /// `i64` can hold
+    NotAnInteger(f64),
+    /// The left hand side of `=` is neither a name nor a function signature
+    InvalidAssignment(String),
+    DuplicateParameter(String),
+    /// Tried to define a function with the same name as a built-in one
+    BuiltinRedefinition(String),
+    /// `RPNQueue::from_bytes` was given something `to_bytes` didn't write
+    InvalidBytes(String),
+    /// Bytes from a newer version of `RPNQueue::to_bytes`
+    UnsupportedVersion(u8),
+    /// `Expr::differentiate` met a part of the expression it has no rule for
+    NotDifferentiable(String),
+    /// `derivative_at` couldn't settle on a slope, e.g. at a step, where
+    /// the expression is and at which point
+    NoDerivative(String),
+    /// `solve` gave up after this many iterations
+    NoConvergence(u32),
+    /// An `Equation` that can't be solved, and why
+    InvalidEquation(String),
+    /// The input was longer than `Limits::max_length` bytes
+    InputTooLong(usize),
+    /// The input had more than `Limits::max_tokens` tokens
+    TooManyTokens(usize),
+    /// More than `Limits::max_depth` brackets were open at once
+    NestingTooDeep(usize),
+    /// The `RPNQueue` would have had more than `Limits::max_output` entries
+    OutputTooLong(usize),
+    /// The `CancelToken` of the calculator was cancelled or ran out of time
+    Cancelled,
+    /// Reading the input of `RPNQueue::from_reader` failed
+    Io(String),
+    /// Cranelift couldn't compile an expression for `CompiledExpr::jit`
+    #[cfg(feature = "jit")]
+    Jit(String),
+}
+
+impl CalcError {
+    /// Where in the input the error was found. Only errors found while
+    /// parsing have a position, the ones found by calculating don't.
+    pub fn span(&self) -> Option<Span> {
+        match *self {
+            CalcError::InvalidToken { span, .. } |
+            CalcError::NumberOutOfRange { span, .. } |
+            CalcError::MissingOperator(span) |
+            CalcError::MissingOperand(span) |
+            CalcError::MissingArgument(span) |
+            CalcError::MissingBrackets { span, .. } => Some(span),
+            CalcError::UnbalancedParen { position } => Some(Span::new(position, position + 1)),
+            _ => None,
+        }
+    }
+
+    /// The same error with its position moved `by` chars to the right, for
+    /// errors in part of a longer input.
+    pub(crate) fn shifted(self, by: usize) -> CalcError {
+        let shift = |span: Span| Span::new(span.start + by, span.end + by);
+        match self {
+            CalcError::InvalidToken { token, span } => CalcError::InvalidToken { token, span: shift(span) },
+            CalcError::NumberOutOfRange { literal, span } => CalcError::NumberOutOfRange { literal, span: shift(span) },
+            CalcError::MissingOperator(span) => CalcError::MissingOperator(shift(span)),
+            CalcError::MissingOperand(span) => CalcError::MissingOperand(shift(span)),
+            CalcError::MissingArgument(span) => CalcError::MissingArgument(shift(span)),
+            CalcError::MissingBrackets { name, span } => CalcError::MissingBrackets { name, span: shift(span) },
+            CalcError::UnbalancedParen { position } => CalcError::UnbalancedParen { position: position + by },
+            err => err,
+        }
+    }
+}
+
+impl fmt::Display for CalcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CalcError::InvalidToken { ref token, .. } => write!(f, "Invalid token: {}", token),
+            CalcError::NumberOutOfRange { ref literal, .. } => write!(f, "Number out of range: {}", literal),
+            CalcError::InvalidRpnToken(ref token) => write!(f, "Invalid token: {}", token),
+            CalcError::NotInTree(ref token) => write!(f, "Cannot be part of an expression tree: {}", token),
+            CalcError::NotEnoughOperands => write!(f, "Not enough input"),
+            CalcError::TooManyOperands => write!(f, "Too much input"),
+            CalcError::MissingOperator(_) => write!(f, "Missing operator"),
+            CalcError::MissingOperand(_) => write!(f, "Missing operand"),
+            CalcError::MissingArgument(_) => write!(f, "Missing function argument"),
+            CalcError::MissingBrackets { ref name, .. } => write!(f, "Missing brackets after function: {}", name),
+            CalcError::UnbalancedParen { .. } => write!(f, "Unbalanced parenthesis"),
+            CalcError::UnknownOperator(ref op) => write!(f, "Invalid operator: {}", op),
+            CalcError::UnknownVariable(ref name) => write!(f, "Unknown variable: {}", name),
+            CalcError::MissingVariables(ref names) => write!(f, "Unknown variables: {}", names.join(", ")),
+            CalcError::UnknownFunction(ref name) => write!(f, "Unknown function: {}", name),
+            CalcError::WrongArgumentCount { ref function, count } => {
+                write!(f, "Wrong number of arguments for {}: {}", function, count)
+            }
+            CalcError::RecursionLimit(ref name) => write!(f, "Maximum recursion depth exceeded in {}", name),
+            CalcError::DivisionByZero => write!(f, "Division by zero"),
+            CalcError::Overflow => write!(f, "Integer overflow"),
+            CalcError::NotFinite { ref value, ref expression } => write!(f, "Not finite: {} gives {}", expression, value),
+            CalcError::InvalidInterval { lo, hi } => write!(f, "Invalid interval: [{}, {}]", lo, hi),
+            CalcError::InvalidFactorial(n) => {
+                write!(f, "Factorial is only defined for non-negative integers: {}", n)
+            }
+            CalcError::NotAnInteger(n) => write!(f, "Bitwise operators are only defined for integers: {}", n),
+            CalcError::InvalidAssignment(ref target) => write!(f, "Invalid assignment target: {}", target),
+            CalcError::DuplicateParameter(ref name) => write!(f, "Duplicate parameter: {}", name),
+            CalcError::BuiltinRedefinition(ref name) => write!(f, "Cannot redefine built-in function: {}", name),
+            CalcError::InvalidBytes(ref reason) => write!(f, "Invalid compiled expression: {}", reason),
+            CalcError::UnsupportedVersion(version) => write!(f, "Unsupported compiled expression version: {}", version),
+            CalcError::NotDifferentiable(ref expression) => write!(f, "Cannot differentiate: {}", expression),
+            CalcError::NoDerivative(ref at) => write!(f, "No derivative found for {}", at),
+            CalcError::NoConvergence(iterations) => write!(f, "No solution found after {} iterations", iterations),
+            CalcError::InvalidEquation(ref reason) => write!(f, "Cannot solve equation: {}", reason),
+            CalcError::InputTooLong(max) => write!(f, "Input longer than {} bytes", max),
+            CalcError::TooManyTokens(max) => write!(f, "More than {} tokens", max),
+            CalcError::NestingTooDeep(max) => write!(f, "More than {} nested brackets", max),
+            CalcError::OutputTooLong(max) => write!(f, "Expression longer than {} entries", max),
+            CalcError::Cancelled => write!(f, "Evaluation cancelled"),
+            CalcError::Io(ref reason) => write!(f, "Cannot read input: {}", reason),
+            #[cfg(feature = "jit")]
+            CalcError::Jit(ref reason) => write!(f, "Cannot compile to machine code: {}", reason),
+        }
+    }
+}
+
+impl Error for CalcError {}
@@ -0,0 +1,106 @@
+//! Functions for C and C++, declared in `include/infix_calculator.h`.
+//!
+//! A failing call returns non-zero and keeps its error for the calling
+//! thread, where `infix_calc_last_error` gives it. The library owns that
+//! string: it must not be freed, and it is only valid until the next call
+//! on the same thread, so callers that need it longer have to copy it.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use RPNQueue;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    // an error message can't have a NUL in it, but the input can
+    let message = CString::new(message.replace('\0', "\\0")).unwrap();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Calculates the NUL-terminated UTF-8 string `input`, storing the result
+/// in `*result`. Returns 0 on success, and -1 with `*result` left as it
+/// was if it fails, see `infix_calc_last_error`.
+///
+/// # Safety
+/// `input` has to be null or point to a NUL-terminated string, and `result`
+/// has to be null or point to a `double` that can be written.
+#[no_mangle]
+pub unsafe extern "C" fn infix_calc_eval(input: *const c_char, result: *mut f64) -> c_int {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+    if input.is_null() || result.is_null() {
+        set_last_error("null pointer passed to infix_calc_eval".to_string());
+        return -1;
+    }
+    let input = match CStr::from_ptr(input).to_str() {
+        Ok(input) => input,
+        Err(err) => {
+            set_last_error(format!("input is not UTF-8: {}", err));
+            return -1;
+        }
+    };
+    match RPNQueue::from_infix_string(input).and_then(|queue| queue.calculate()) {
+        Ok(value) => {
+            *result = value;
+            0
+        }
+        Err(err) => {
+            set_last_error(err.to_string());
+            -1
+        }
+    }
+}
+
+/// The message of the error of the last call on this thread that failed,
+/// or null if it succeeded. See the module documentation for how long the
+/// string lives.
+#[no_mangle]
+pub extern "C" fn infix_calc_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> Option<String> {
+        let message = infix_calc_last_error();
+        if message.is_null() {
+            None
+        } else {
+            Some(unsafe { CStr::from_ptr(message) }.to_str().unwrap().to_string())
+        }
+    }
+
+    #[test]
+    fn results_and_errors() {
+        let mut result = 0.0;
+        let input = CString::new("2 ^ 3 + sqrt(16)").unwrap();
+        assert_eq!(unsafe { infix_calc_eval(input.as_ptr(), &mut result) }, 0);
+        assert_eq!(result, 12.0);
+        assert_eq!(last_error(), None);
+
+        let input = CString::new("1 + x").unwrap();
+        assert_eq!(unsafe { infix_calc_eval(input.as_ptr(), &mut result) }, -1);
+        assert_eq!(result, 12.0);
+        assert_eq!(last_error().unwrap(), "Unknown variable: x");
+
+        assert_eq!(unsafe { infix_calc_eval(ptr::null(), &mut result) }, -1);
+        assert_eq!(last_error().unwrap(), "null pointer passed to infix_calc_eval");
+        let input = [0xffu8 as c_char, 0];
+        assert_eq!(unsafe { infix_calc_eval(input.as_ptr(), &mut result) }, -1);
+        assert!(last_error().unwrap().starts_with("input is not UTF-8"));
+    }
+
+    #[test]
+    fn errors_are_per_thread() {
+        let input = CString::new("1 +").unwrap();
+        assert_eq!(unsafe { infix_calc_eval(input.as_ptr(), &mut 0.0) }, -1);
+        assert!(std::thread::spawn(|| infix_calc_last_error().is_null()).join().unwrap());
+        assert_eq!(last_error().unwrap(), "Missing operand");
+    }
+}
@@ -0,0 +1,209 @@
+use std::fmt;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use CalcError;
+
+/// A closed range of numbers, written `[lo, hi]` in expressions.
+///
+/// Every operation gives an interval that is guaranteed to contain all the
+/// possible results: bounds that can't be represented exactly are rounded
+/// outwards, down for `lo` and up for `hi`.
+///
+/// # Example
+/// ```
+/// use infix_calculator::{Calculator, Interval, Value};
+///
+/// let result = Calculator::new().calculate("[1.0, 1.1] * 3").unwrap();
+/// match result {
+///     Value::Interval(interval) => {
+///         assert!(interval.lo() <= 3.0 && interval.hi() >= 3.3);
+///         assert!(interval.contains(3.15));
+///     }
+///     _ => unreachable!(),
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Interval {
+    lo: f64,
+    hi: f64,
+}
+
+impl Interval {
+    pub fn new(lo: f64, hi: f64) -> Result<Self, CalcError> {
+        if lo.is_nan() || hi.is_nan() || lo > hi {
+            return Err(CalcError::InvalidInterval { lo, hi });
+        }
+        Ok(Interval { lo, hi })
+    }
+
+    /// The interval holding just `n`.
+    pub fn point(n: f64) -> Self {
+        Interval { lo: n, hi: n }
+    }
+
+    pub fn lo(&self) -> f64 {
+        self.lo
+    }
+
+    pub fn hi(&self) -> f64 {
+        self.hi
+    }
+
+    pub fn contains(&self, n: f64) -> bool {
+        self.lo <= n && n <= self.hi
+    }
+
+    pub(crate) fn binary(self, op: &str, other: Interval) -> Result<Interval, CalcError> {
+        match op {
+            "+" => Ok(Interval { lo: add(self.lo, other.lo).0, hi: add(self.hi, other.hi).1 }),
+            "-" => Ok(Interval { lo: add(self.lo, -other.hi).0, hi: add(self.hi, -other.lo).1 }),
+            "*" => Ok(hull(&[
+                mul(self.lo, other.lo),
+                mul(self.lo, other.hi),
+                mul(self.hi, other.lo),
+                mul(self.hi, other.hi),
+            ])),
+            "/" => {
+                if other.contains(0.0) {
+                    return Err(CalcError::DivisionByZero);
+                }
+                Ok(hull(&[
+                    div(self.lo, other.lo),
+                    div(self.lo, other.hi),
+                    div(self.hi, other.lo),
+                    div(self.hi, other.hi),
+                ]))
+            }
+            "^" if other.lo == other.hi => self.pow(other.lo),
+            _ => Err(CalcError::UnknownOperator(op.to_string())),
+        }
+    }
+
+    /// Powers are only worked out for whole exponents or intervals that
+    /// aren't negative, where the bounds are known to come from the ends.
+    fn pow(self, exponent: f64) -> Result<Interval, CalcError> {
+        if exponent < 0.0 && self.contains(0.0) {
+            return Err(CalcError::DivisionByZero);
+        }
+        let (lo, hi) = (self.lo.powf(exponent), self.hi.powf(exponent));
+        let result = if self.lo >= 0.0 || exponent.fract() == 0.0 && exponent % 2.0 != 0.0 {
+            // monotonic, increasing for positive exponents
+            Interval { lo: lo.min(hi), hi: lo.max(hi) }
+        } else if exponent.fract() == 0.0 && exponent > 0.0 {
+            // even powers are smallest at zero
+            let smallest = if self.contains(0.0) { 0.0 } else { lo.min(hi) };
+            Interval { lo: smallest, hi: lo.max(hi) }
+        } else {
+            return Err(CalcError::UnknownOperator("^".to_string()));
+        };
+        Ok(result.widen())
+    }
+
+    pub(crate) fn negate(self) -> Interval {
+        Interval { lo: -self.hi, hi: -self.lo }
+    }
+
+    /// Applies a function that never decreases, such as `sqrt` or `exp`.
+    pub(crate) fn map_increasing(self, f: fn(f64) -> f64) -> Interval {
+        Interval { lo: f(self.lo), hi: f(self.hi) }.widen()
+    }
+
+    pub(crate) fn abs(self) -> Interval {
+        if self.lo >= 0.0 {
+            self
+        } else if self.hi <= 0.0 {
+            self.negate()
+        } else {
+            Interval { lo: 0.0, hi: self.hi.max(-self.lo) }
+        }
+    }
+
+    /// For results of library functions, which don't say which way they rounded.
+    fn widen(self) -> Interval {
+        Interval { lo: self.lo.next_down(), hi: self.hi.next_up() }
+    }
+}
+
+/// The smallest interval holding every one of the rounded `bounds`.
+fn hull(bounds: &[(f64, f64)]) -> Interval {
+    let lo = bounds.iter().map(|bound| bound.0).fold(f64::INFINITY, f64::min);
+    let hi = bounds.iter().map(|bound| bound.1).fold(f64::NEG_INFINITY, f64::max);
+    Interval { lo, hi }
+}
+
+/// Rounds `result` down and up, `error` is how far the exact result is above it.
+fn round(result: f64, error: f64) -> (f64, f64) {
+    let lo = if error < 0.0 { result.next_down() } else { result };
+    let hi = if error > 0.0 { result.next_up() } else { result };
+    (lo, hi)
+}
+
+/// `a + b` rounded down and up. The rounding error is found exactly with Knuth's two-sum.
+fn add(a: f64, b: f64) -> (f64, f64) {
+    let sum = a + b;
+    let b_part = sum - a;
+    let error = (a - (sum - b_part)) + (b - b_part);
+    round(sum, error)
+}
+
+/// `a * b` rounded down and up, a fused multiply-add gives the exact error.
+fn mul(a: f64, b: f64) -> (f64, f64) {
+    let product = a * b;
+    round(product, a.mul_add(b, -product))
+}
+
+/// `a / b` rounded down and up. The remainder `a - q * b` has the sign of the
+/// error when `b` is positive and the opposite one otherwise.
+fn div(a: f64, b: f64) -> (f64, f64) {
+    let quotient = a / b;
+    let remainder = (-quotient).mul_add(b, a);
+    round(quotient, if b < 0.0 { -remainder } else { remainder })
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match f.precision() {
+            Some(precision) => write!(f, "[{:.*}, {:.*}]", precision, self.lo, precision, self.hi),
+            None => write!(f, "[{}, {}]", self.lo, self.hi),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interval(lo: f64, hi: f64) -> Interval {
+        Interval::new(lo, hi).unwrap()
+    }
+
+    #[test]
+    fn exact_results_are_not_widened() {
+        assert_eq!(interval(1.0, 2.0).binary("+", interval(3.0, 4.0)), Ok(interval(4.0, 6.0)));
+        assert_eq!(interval(1.0, 2.0).binary("-", interval(3.0, 4.0)), Ok(interval(-3.0, -1.0)));
+        assert_eq!(interval(-1.0, 2.0).binary("*", interval(3.0, 4.0)), Ok(interval(-4.0, 8.0)));
+        assert_eq!(interval(1.0, 2.0).binary("/", interval(-4.0, -2.0)), Ok(interval(-1.0, -0.25)));
+        assert_eq!(interval(-2.0, 1.0).binary("^", Interval::point(2.0)), Ok(interval(0.0, 4.0).widen()));
+    }
+
+    #[test]
+    fn inexact_results_are_rounded_outwards() {
+        let tenth = Interval::point(0.1);
+        let sum = tenth.binary("+", Interval::point(0.2)).unwrap();
+        assert!(sum.lo() < 0.1 + 0.2 && sum.hi() == 0.1 + 0.2);
+        let third = Interval::point(1.0).binary("/", Interval::point(3.0)).unwrap();
+        assert_eq!(third.hi(), third.lo().next_up());
+        let product = interval(1.0, 1.1).binary("*", Interval::point(3.0)).unwrap();
+        assert!(product.lo() == 3.0 && product.hi() >= 1.1 * 3.0);
+    }
+
+    #[test]
+    fn invalid_intervals() {
+        assert_eq!(Interval::new(2.0, 1.0), Err(CalcError::InvalidInterval { lo: 2.0, hi: 1.0 }));
+        assert!(Interval::new(f64::NAN, 1.0).is_err());
+        assert_eq!(interval(1.0, 2.0).binary("/", interval(-1.0, 1.0)), Err(CalcError::DivisionByZero));
+        assert!(interval(-1.0, 2.0).binary("^", Interval::point(0.5)).is_err());
+    }
+}
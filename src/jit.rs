@@ -0,0 +1,346 @@
+use std::mem::{self, ManuallyDrop};
+use std::slice;
+
+use cranelift_codegen::ir::condcodes::FloatCC;
+use cranelift_codegen::ir::{types, AbiParam, Block, BlockArg, InstBuilder, MemFlagsData, StackSlotData, StackSlotKind, Type, Value as Register};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Linkage, Module};
+
+use bytecode::Op;
+use {compute_result, factorial, whole, BinaryOp, CalcError, CompiledExpr, Function, UnaryOp};
+
+/// The machine code, taking the values of the variables and where to put
+/// the first error.
+type Native = unsafe extern "C" fn(*const f64, *mut Option<CalcError>) -> f64;
+
+// What the machine code calls for anything that isn't a single instruction.
+
+extern "C" fn jit_rem(first: f64, second: f64) -> f64 {
+    first % second
+}
+
+extern "C" fn jit_pow(first: f64, second: f64) -> f64 {
+    first.powf(second)
+}
+
+/// The result, or NaN after keeping the error if it is the first one.
+unsafe fn record(result: Result<f64, CalcError>, error: *mut Option<CalcError>) -> f64 {
+    result.unwrap_or_else(|err| {
+        if (*error).is_none() {
+            *error = Some(err);
+        }
+        f64::NAN
+    })
+}
+
+unsafe extern "C" fn jit_factorial(n: f64, error: *mut Option<CalcError>) -> f64 {
+    record(factorial(n), error)
+}
+
+unsafe extern "C" fn jit_bit_and(first: f64, second: f64, error: *mut Option<CalcError>) -> f64 {
+    record(compute_result(first, second, "&"), error)
+}
+
+unsafe extern "C" fn jit_bit_or(first: f64, second: f64, error: *mut Option<CalcError>) -> f64 {
+    record(compute_result(first, second, "|"), error)
+}
+
+unsafe extern "C" fn jit_bit_xor(first: f64, second: f64, error: *mut Option<CalcError>) -> f64 {
+    record(compute_result(first, second, "xor"), error)
+}
+
+unsafe extern "C" fn jit_bit_not(n: f64, error: *mut Option<CalcError>) -> f64 {
+    record(whole(n).map(|n| !n as f64), error)
+}
+
+unsafe extern "C" fn jit_call(function: *const Function, arguments: *const f64, count: usize) -> f64 {
+    (*function).call(slice::from_raw_parts(arguments, count))
+}
+
+/// A `CompiledExpr` compiled to machine code, see `CompiledExpr::jit`.
+pub struct JitExpr {
+    module: ManuallyDrop<JITModule>,
+    native: Native,
+    /// Holds the functions the machine code calls
+    compiled: CompiledExpr,
+}
+
+fn jit_error<E: ToString>(err: E) -> CalcError {
+    CalcError::Jit(err.to_string())
+}
+
+impl CompiledExpr {
+    /// Compiles the bytecode further to machine code for the computer it is
+    /// running on, with [Cranelift](https://cranelift.dev). Compiling takes
+    /// far longer than calculating the bytecode once, but calculating the
+    /// result is then several times quicker.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::RPNQueue;
+    ///
+    /// let queue = RPNQueue::from_infix_string("sqrt(x ^ 2 + y ^ 2)").unwrap();
+    /// let jit = queue.compile(&["x", "y"]).unwrap().jit().unwrap();
+    /// assert_eq!(jit.calculate(&[3.0, 4.0]).unwrap(), 5.0);
+    /// ```
+    pub fn jit(&self) -> Result<JitExpr, CalcError> {
+        let mut flags = settings::builder();
+        flags.set("use_colocated_libcalls", "false").map_err(jit_error)?;
+        flags.set("is_pic", "false").map_err(jit_error)?;
+        let isa = cranelift_native::builder().map_err(jit_error)?
+            .finish(settings::Flags::new(flags))
+            .map_err(jit_error)?;
+        let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+        builder.symbol("jit_rem", jit_rem as *const u8);
+        builder.symbol("jit_pow", jit_pow as *const u8);
+        builder.symbol("jit_factorial", jit_factorial as *const u8);
+        builder.symbol("jit_bit_and", jit_bit_and as *const u8);
+        builder.symbol("jit_bit_or", jit_bit_or as *const u8);
+        builder.symbol("jit_bit_xor", jit_bit_xor as *const u8);
+        builder.symbol("jit_bit_not", jit_bit_not as *const u8);
+        builder.symbol("jit_call", jit_call as *const u8);
+        let mut module = JITModule::new(builder);
+
+        // the functions are kept in `JitExpr`, so their addresses don't change
+        let compiled = self.clone();
+        match generate(&compiled, &mut module) {
+            Ok(native) => Ok(JitExpr { module: ManuallyDrop::new(module), native, compiled }),
+            Err(err) => {
+                unsafe { module.free_memory() };
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Declares a function the machine code calls, returning an `f64`.
+fn import(module: &mut JITModule, name: &str, parameters: &[Type]) -> Result<FuncId, CalcError> {
+    let mut signature = module.make_signature();
+    signature.params.extend(parameters.iter().map(|&parameter| AbiParam::new(parameter)));
+    signature.returns.push(AbiParam::new(types::F64));
+    module.declare_function(name, Linkage::Import, &signature).map_err(jit_error)
+}
+
+fn generate(compiled: &CompiledExpr, module: &mut JITModule) -> Result<Native, CalcError> {
+    let pointer = module.target_config().pointer_type();
+    let rem = import(module, "jit_rem", &[types::F64, types::F64])?;
+    let pow = import(module, "jit_pow", &[types::F64, types::F64])?;
+    let factorial = import(module, "jit_factorial", &[types::F64, pointer])?;
+    let bit_and = import(module, "jit_bit_and", &[types::F64, types::F64, pointer])?;
+    let bit_or = import(module, "jit_bit_or", &[types::F64, types::F64, pointer])?;
+    let bit_xor = import(module, "jit_bit_xor", &[types::F64, types::F64, pointer])?;
+    let bit_not = import(module, "jit_bit_not", &[types::F64, pointer])?;
+    let call = import(module, "jit_call", &[pointer, pointer, pointer])?;
+
+    let mut context = module.make_context();
+    context.func.signature.params.push(AbiParam::new(pointer));
+    context.func.signature.params.push(AbiParam::new(pointer));
+    context.func.signature.returns.push(AbiParam::new(types::F64));
+    let id = module.declare_function("expression", Linkage::Local, &context.func.signature).map_err(jit_error)?;
+
+    let mut function_context = FunctionBuilderContext::new();
+    let mut builder = FunctionBuilder::new(&mut context.func, &mut function_context);
+    let block = builder.create_block();
+    builder.append_block_params_for_function_params(block);
+    builder.switch_to_block(block);
+    let (values, error) = (builder.block_params(block)[0], builder.block_params(block)[1]);
+
+    let mut call_helper = |builder: &mut FunctionBuilder, id: FuncId, arguments: &[Register]| {
+        let reference = module.declare_func_in_func(id, builder.func);
+        let instruction = builder.ins().call(reference, arguments);
+        builder.inst_results(instruction)[0]
+    };
+    // `compile` checked there is always enough on the stack
+    let mut stack: Vec<Register> = Vec::with_capacity(compiled.depth);
+    // the blocks where both ways through a `&&` or `||` meet, with where in
+    // the code that is
+    let mut merges: Vec<(usize, Block)> = Vec::new();
+    for (index, op) in compiled.code.iter().enumerate() {
+        let result = match *op {
+            Op::Constant(index) => builder.ins().f64const(compiled.constants[index]),
+            Op::Variable(index) => builder.ins().load(types::F64, MemFlagsData::trusted(), values, 8 * index as i32),
+            Op::Unary(UnaryOp::Neg) => {
+                let operand = stack.pop().unwrap();
+                builder.ins().fneg(operand)
+            }
+            Op::Unary(UnaryOp::Factorial) => {
+                let operand = stack.pop().unwrap();
+                call_helper(&mut builder, factorial, &[operand, error])
+            }
+            Op::Unary(UnaryOp::BitNot) => {
+                let operand = stack.pop().unwrap();
+                call_helper(&mut builder, bit_not, &[operand, error])
+            }
+            Op::Unary(UnaryOp::Not) => {
+                let operand = stack.pop().unwrap();
+                let zero = builder.ins().f64const(0.0);
+                let holds = builder.ins().fcmp(FloatCC::Equal, operand, zero);
+                let one = builder.ins().f64const(1.0);
+                builder.ins().select(holds, one, zero)
+            }
+            Op::ShortCircuit(op, end) => {
+                // the left operand stays on the stack for the operator
+                let left = *stack.last().unwrap();
+                let zero = builder.ins().f64const(0.0);
+                let truth = builder.ins().fcmp(FloatCC::NotEqual, left, zero);
+                let (right, merge) = (builder.create_block(), builder.create_block());
+                builder.append_block_param(merge, types::F64);
+                if op == BinaryOp::And {
+                    builder.ins().brif(truth, right, &[], merge, &[BlockArg::Value(zero)]);
+                } else {
+                    let one = builder.ins().f64const(1.0);
+                    builder.ins().brif(truth, merge, &[BlockArg::Value(one)], right, &[]);
+                }
+                builder.switch_to_block(right);
+                merges.push((end, merge));
+                continue;
+            }
+            Op::Binary(op) => {
+                let (second, first) = (stack.pop().unwrap(), stack.pop().unwrap());
+                match op {
+                    BinaryOp::Add => builder.ins().fadd(first, second),
+                    BinaryOp::Sub => builder.ins().fsub(first, second),
+                    BinaryOp::Mul => builder.ins().fmul(first, second),
+                    BinaryOp::Div => builder.ins().fdiv(first, second),
+                    BinaryOp::Rem => call_helper(&mut builder, rem, &[first, second]),
+                    BinaryOp::Pow => call_helper(&mut builder, pow, &[first, second]),
+                    BinaryOp::BitAnd => call_helper(&mut builder, bit_and, &[first, second, error]),
+                    BinaryOp::BitOr => call_helper(&mut builder, bit_or, &[first, second, error]),
+                    BinaryOp::BitXor => call_helper(&mut builder, bit_xor, &[first, second, error]),
+                    BinaryOp::And | BinaryOp::Or => {
+                        let zero = builder.ins().f64const(0.0);
+                        let first = builder.ins().fcmp(FloatCC::NotEqual, first, zero);
+                        let second = builder.ins().fcmp(FloatCC::NotEqual, second, zero);
+                        let holds = if op == BinaryOp::And {
+                            builder.ins().band(first, second)
+                        } else {
+                            builder.ins().bor(first, second)
+                        };
+                        let one = builder.ins().f64const(1.0);
+                        builder.ins().select(holds, one, zero)
+                    }
+                    op => {
+                        let condition = match op {
+                            BinaryOp::Eq => FloatCC::Equal,
+                            BinaryOp::Ne => FloatCC::NotEqual,
+                            BinaryOp::Lt => FloatCC::LessThan,
+                            BinaryOp::Le => FloatCC::LessThanOrEqual,
+                            BinaryOp::Gt => FloatCC::GreaterThan,
+                            _ => FloatCC::GreaterThanOrEqual,
+                        };
+                        let holds = builder.ins().fcmp(condition, first, second);
+                        let (one, zero) = (builder.ins().f64const(1.0), builder.ins().f64const(0.0));
+                        builder.ins().select(holds, one, zero)
+                    }
+                }
+            }
+            Op::Call(index, arity) => {
+                // the arguments are passed as an array on the stack
+                let slot = builder.create_sized_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 8 * arity as u32, 3));
+                let arguments = stack.split_off(stack.len() - arity);
+                for (i, &argument) in arguments.iter().enumerate() {
+                    builder.ins().stack_store(pointer, argument, slot, 8 * i as i32);
+                }
+                let address = builder.ins().stack_addr(pointer, slot, 0);
+                let function = &compiled.functions[index] as *const Function;
+                let function = builder.ins().iconst(pointer, function as i64);
+                let count = builder.ins().iconst(pointer, arity as i64);
+                call_helper(&mut builder, call, &[function, address, count])
+            }
+        };
+        let result = match merges.last() {
+            Some(&(end, merge)) if end == index + 1 => {
+                merges.pop();
+                builder.ins().jump(merge, &[BlockArg::Value(result)]);
+                builder.switch_to_block(merge);
+                builder.block_params(merge)[0]
+            }
+            _ => result,
+        };
+        stack.push(result);
+    }
+    let result = stack.pop().unwrap();
+    builder.ins().return_(&[result]);
+    builder.seal_all_blocks();
+    builder.finalize(module.target_config());
+
+    module.define_function(id, &mut context).map_err(jit_error)?;
+    module.clear_context(&mut context);
+    module.finalize_definitions().map_err(jit_error)?;
+    Ok(unsafe { mem::transmute::<*const u8, Native>(module.get_finalized_function(id)) })
+}
+
+impl JitExpr {
+    /// The names `calculate` takes values for, in order.
+    pub fn variables(&self) -> &[String] {
+        self.compiled.variables()
+    }
+
+    /// Calculates the result with `values` for the variables, like
+    /// `CompiledExpr::calculate`.
+    pub fn calculate(&self, values: &[f64]) -> Result<f64, CalcError> {
+        if values.len() != self.compiled.variables().len() {
+            return Err(CalcError::WrongArgumentCount { function: "compiled expression".to_string(), count: values.len() });
+        }
+        let mut error = None;
+        let result = unsafe { (self.native)(values.as_ptr(), &mut error) };
+        match error {
+            Some(err) => Err(err),
+            None => Ok(result),
+        }
+    }
+}
+
+impl Drop for JitExpr {
+    fn drop(&mut self) {
+        // nothing can call the machine code once `native` is gone
+        unsafe { ManuallyDrop::take(&mut self.module).free_memory() };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {CalcError, RPNQueue};
+
+    #[test]
+    fn same_as_bytecode() {
+        let queue = RPNQueue::from_infix_string("x * y - max(x, y, 2) / 3 + sqrt(abs(y)) % 2 ^ x - (x + 4)! + pow(y, 2) * -pi").unwrap();
+        let compiled = queue.compile(&["x", "y"]).unwrap();
+        let jit = compiled.jit().unwrap();
+        assert_eq!(jit.variables(), &["x", "y"]);
+        for &(x, y) in &[(-1.0, 2.0), (-3.0, -7.5), (0.0, 0.25), (2.5, 1.0)] {
+            assert_eq!(jit.calculate(&[x, y]), compiled.calculate(&[x, y]));
+        }
+        let compiled = RPNQueue::from_infix_string("(x < y) + (x <= y) * 2 + (x == y) * 4 + (x != y) * 8 + (x > y) * 16 + (x >= y) * 32")
+            .unwrap().compile(&["x", "y"]).unwrap();
+        let jit = compiled.jit().unwrap();
+        for &(x, y) in &[(1.0, 2.0), (2.0, 2.0), (3.0, 2.0), (f64::NAN, 2.0)] {
+            assert_eq!(jit.calculate(&[x, y]), compiled.calculate(&[x, y]));
+        }
+        // the factorial isn't calculated, and can't fail, unless it is needed
+        let compiled = RPNQueue::from_infix_string("x >= 0 && (x - 1)! >= y || !(x || y)").unwrap().compile(&["x", "y"]).unwrap();
+        let jit = compiled.jit().unwrap();
+        for &(x, y) in &[(-1.0, 2.0), (3.0, 2.0), (4.0, 7.0), (0.0, 0.0), (-0.5, 0.0)] {
+            assert_eq!(jit.calculate(&[x, y]), compiled.calculate(&[x, y]));
+        }
+        let compiled = RPNQueue::from_infix_string("x & y | ~x xor y").unwrap().compile(&["x", "y"]).unwrap();
+        let jit = compiled.jit().unwrap();
+        for &(x, y) in &[(12.0, 10.0), (-1.0, 255.0), (0.0, 0.0), (1.5, 1.0)] {
+            assert_eq!(jit.calculate(&[x, y]), compiled.calculate(&[x, y]));
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let jit = RPNQueue::from_infix_string("x! + (x - 1)!").unwrap().compile(&["x"]).unwrap().jit().unwrap();
+        assert_eq!(jit.calculate(&[4.0]).unwrap(), 30.0);
+        assert_eq!(jit.calculate(&[0.5]).unwrap_err(), CalcError::InvalidFactorial(0.5));
+        assert_eq!(jit.calculate(&[0.0]).unwrap_err(), CalcError::InvalidFactorial(-1.0));
+        assert_eq!(
+            jit.calculate(&[]).unwrap_err(),
+            CalcError::WrongArgumentCount { function: "compiled expression".to_string(), count: 0 }
+        );
+    }
+}
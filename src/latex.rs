@@ -0,0 +1,150 @@
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use ast::Level;
+use {BinaryOp, Expr, UnaryOp};
+
+/// Functions LaTeX has a command for, written upright like `\sin`.
+const NAMED_FUNCTIONS: &[&str] = &["sin", "cos", "tan", "ln", "log", "exp", "min", "max", "arg"];
+
+impl Expr {
+    /// Writes the expression as LaTeX math, for rendering it the way it was
+    /// parsed. Divisions become fractions and brackets are only added where
+    /// they are needed to keep the meaning.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::Expr;
+    ///
+    /// let expr = Expr::parse("(4 / 5) ^ 2 * sqrt(x + pi)").unwrap();
+    /// assert_eq!(expr.to_latex(), r"\left(\frac{4}{5}\right)^{2} \cdot \sqrt{x + \pi}");
+    /// ```
+    pub fn to_latex(&self) -> String {
+        match *self {
+            Expr::Number(number) => number.to_string(),
+            Expr::Variable(ref name) => latex_name(name),
+            Expr::Unary(UnaryOp::Neg, ref operand) => format!("-{}", operand.latex_bracketed(Level::Negation)),
+            Expr::Unary(UnaryOp::Factorial, ref operand) => format!("{}!", operand.latex_bracketed(Level::Atom)),
+            Expr::Unary(UnaryOp::Not, ref operand) => format!(r"\lnot {}", operand.latex_bracketed(Level::Negation)),
+            Expr::Unary(UnaryOp::BitNot, ref operand) => format!(r"\mathord{{\sim}}{}", operand.latex_bracketed(Level::Negation)),
+            Expr::Binary(BinaryOp::Div, ref left, ref right) => {
+                format!(r"\frac{{{}}}{{{}}}", left.to_latex(), right.to_latex())
+            }
+            Expr::Binary(BinaryOp::Pow, ref base, ref exponent) => power(base, exponent),
+            Expr::Binary(op, ref left, ref right) => {
+                let (level, symbol) = match op {
+                    BinaryOp::Add => (Level::Sum, "+"),
+                    BinaryOp::Sub => (Level::Sum, "-"),
+                    BinaryOp::Mul => (Level::Product, r"\cdot"),
+                    BinaryOp::Eq => (Level::Comparison, "="),
+                    BinaryOp::Ne => (Level::Comparison, r"\neq"),
+                    BinaryOp::Lt => (Level::Comparison, "<"),
+                    BinaryOp::Le => (Level::Comparison, r"\leq"),
+                    BinaryOp::Gt => (Level::Comparison, ">"),
+                    BinaryOp::Ge => (Level::Comparison, r"\geq"),
+                    BinaryOp::And => (Level::And, r"\land"),
+                    BinaryOp::Or => (Level::Or, r"\lor"),
+                    BinaryOp::BitAnd => (Level::BitAnd, r"\mathbin{\&}"),
+                    BinaryOp::BitOr => (Level::BitOr, r"\mathbin{|}"),
+                    BinaryOp::BitXor => (Level::BitXor, r"\oplus"),
+                    BinaryOp::PlusMinus => (Level::Measurement, r"\pm"),
+                    _ => (Level::Product, r"\bmod"),
+                };
+                // all of them are left associative, so the right operand needs
+                // brackets for the same level too, and `2 \cdot -3` reads badly
+                let right = match right.level() {
+                    Level::Negation => right.latex_bracketed(Level::Power),
+                    _ => right.latex_bracketed(level.next()),
+                };
+                format!("{} {} {}", left.latex_bracketed(level), symbol, right)
+            }
+            Expr::Call(ref name, ref arguments) => call(name, arguments),
+        }
+    }
+
+    /// The LaTeX of the expression, in brackets unless it binds at least as
+    /// tightly as `level`.
+    fn latex_bracketed(&self, level: Level) -> String {
+        if self.level() < level {
+            format!(r"\left({}\right)", self.to_latex())
+        } else {
+            self.to_latex()
+        }
+    }
+}
+
+fn power(base: &Expr, exponent: &Expr) -> String {
+    // the exponent is in braces, so it never needs brackets
+    format!("{}^{{{}}}", base.latex_bracketed(Level::Atom), exponent.to_latex())
+}
+
+fn call(name: &str, arguments: &[Expr]) -> String {
+    let list = |arguments: &[Expr]| arguments.iter().map(Expr::to_latex).collect::<Vec<String>>().join(", ");
+    match (name, arguments) {
+        ("sqrt", [argument]) => format!(r"\sqrt{{{}}}", argument.to_latex()),
+        ("abs", [argument]) => format!(r"\left|{}\right|", argument.to_latex()),
+        ("pow", [base, exponent]) => power(base, exponent),
+        ("interval", _) => format!(r"\left[{}\right]", list(arguments)),
+        _ => {
+            let name = if NAMED_FUNCTIONS.contains(&name) {
+                format!(r"\{}", name)
+            } else if name.chars().count() == 1 {
+                name.to_string()
+            } else {
+                format!(r"\operatorname{{{}}}", escape(name))
+            };
+            format!(r"{}\left({}\right)", name, list(arguments))
+        }
+    }
+}
+
+fn latex_name(name: &str) -> String {
+    match name {
+        "pi" | "π" => r"\pi".to_string(),
+        "tau" | "τ" => r"\tau".to_string(),
+        name if name.chars().count() == 1 => name.to_string(),
+        name => format!(r"\mathrm{{{}}}", escape(name)),
+    }
+}
+
+/// Names can have underscores, which are subscripts in LaTeX.
+fn escape(name: &str) -> String {
+    name.replace('_', r"\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn latex(input: &str) -> String {
+        Expr::parse(input).unwrap().to_latex()
+    }
+
+    #[test]
+    fn fractions_and_powers() {
+        assert_eq!(latex("1 / (2 + x) / 3"), r"\frac{\frac{1}{2 + x}}{3}");
+        assert_eq!(latex("2 ^ 3 ^ 2"), "2^{3^{2}}");
+        assert_eq!(latex("(2 ^ 3) ^ 2"), r"\left(2^{3}\right)^{2}");
+        assert_eq!(latex("-x ^ 2"), "-x^{2}");
+        assert_eq!(latex("(-x) ^ 2"), r"\left(-x\right)^{2}");
+        assert_eq!(latex("pow(x + 1, 2)"), r"\left(x + 1\right)^{2}");
+    }
+
+    #[test]
+    fn brackets() {
+        assert_eq!(latex("(1 + 2) * 3 - (4 - 5)"), r"\left(1 + 2\right) \cdot 3 - \left(4 - 5\right)");
+        assert_eq!(latex("-(a + b) % 2"), r"-\left(a + b\right) \bmod 2");
+        assert_eq!(latex("(n - 1)! * n!"), r"\left(n - 1\right)! \cdot n!");
+        assert_eq!(latex("2 * -3 - -x"), r"2 \cdot \left(-3\right) - \left(-x\right)");
+        assert_eq!(latex("x + 1 <= 2 * y != (a == b)"), r"x + 1 \leq 2 \cdot y \neq \left(a = b\right)");
+        assert_eq!(latex("!(a || b) && c > 0 || d"), r"\lnot \left(a \lor b\right) \land c > 0 \lor d");
+        assert_eq!(latex("(10 ± 1) ^ 2 * 3 ± 0.5"), r"\left(10 \pm 1\right)^{2} \cdot 3 \pm 0.5");
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(latex("sin(x) + abs(y) - max(1, 2)"), r"\sin\left(x\right) + \left|y\right| - \max\left(1, 2\right)");
+        assert_eq!(latex("f(x_1) * area(r) * tau"), r"f\left(\mathrm{x\_1}\right) \cdot \operatorname{area}\left(r\right) \cdot \tau");
+        assert_eq!(latex("[1, 2] * e"), r"\left[1, 2\right] \cdot e");
+    }
+}
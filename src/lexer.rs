@@ -0,0 +1,895 @@
+use std::borrow::Cow;
+use std::hash::{Hash, Hasher};
+use std::iter::{Enumerate, Peekable};
+use std::str::Chars;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use smallvec::SmallVec;
+
+use {hash_f64, CalcError};
+
+/// Operator characters as they appear in the input. Whether a `-` is
+/// binary or unary is up to the parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Operator {
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Caret,
+    Bang,
+    /// `±`, `10 ± 0.2` is an uncertain value
+    PlusMinus,
+    /// `==`
+    Equal,
+    /// `!=`
+    NotEqual,
+    Less,
+    /// `<=`
+    LessEqual,
+    Greater,
+    /// `>=`
+    GreaterEqual,
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+    /// `&`, bitwise and
+    Ampersand,
+    /// `|`, bitwise or
+    Pipe,
+    /// `xor`, bitwise exclusive or
+    Xor,
+    /// `~`, bitwise not
+    Tilde,
+}
+
+impl Operator {
+    /// The operator as one character, `≤` for `<=` and `⩵` for `==`, which
+    /// reads back as the same operator. `=` is left for assignments.
+    pub fn symbol(self) -> char {
+        match self {
+            Operator::Plus => '+',
+            Operator::Minus => '-',
+            Operator::Star => '*',
+            Operator::Slash => '/',
+            Operator::Percent => '%',
+            Operator::Caret => '^',
+            Operator::Bang => '!',
+            Operator::PlusMinus => '±',
+            Operator::Equal => '⩵',
+            Operator::NotEqual => '≠',
+            Operator::Less => '<',
+            Operator::LessEqual => '≤',
+            Operator::Greater => '>',
+            Operator::GreaterEqual => '≥',
+            Operator::And => '∧',
+            Operator::Or => '∨',
+            Operator::Ampersand => '&',
+            Operator::Pipe => '|',
+            Operator::Xor => '⊕',
+            Operator::Tilde => '~',
+        }
+    }
+
+    /// `symbol` as a string, the way it is written in an `RPNQueue`.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Star => "*",
+            Operator::Slash => "/",
+            Operator::Percent => "%",
+            Operator::Caret => "^",
+            Operator::Bang => "!",
+            Operator::PlusMinus => "±",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::Less => "<",
+            Operator::LessEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterEqual => ">=",
+            Operator::And => "&&",
+            Operator::Or => "||",
+            Operator::Ampersand => "&",
+            Operator::Pipe => "|",
+            Operator::Xor => "xor",
+            Operator::Tilde => "~",
+        }
+    }
+}
+
+/// Every way of writing an operator, the longest one matching is taken.
+/// Includes the typographic `×`, `·`, `÷` and `−` (U+2212) often found in
+/// pasted text, `⩵ ≠ ≤ ≥` for the comparisons, `∧ ∨` for `&& ||` and `⊕`
+/// for `xor`. `!=` is taken over a factorial followed by `=`, and `&&` over
+/// two `&`. The word `xor` is read with the names.
+const OPERATOR_SYMBOLS: &[(&str, Operator)] = &[
+    ("+", Operator::Plus),
+    ("-", Operator::Minus),
+    ("\u{2212}", Operator::Minus),
+    ("*", Operator::Star),
+    ("×", Operator::Star),
+    ("·", Operator::Star),
+    ("/", Operator::Slash),
+    ("÷", Operator::Slash),
+    ("%", Operator::Percent),
+    ("^", Operator::Caret),
+    ("!", Operator::Bang),
+    ("±", Operator::PlusMinus),
+    ("==", Operator::Equal),
+    ("⩵", Operator::Equal),
+    ("!=", Operator::NotEqual),
+    ("≠", Operator::NotEqual),
+    ("<", Operator::Less),
+    ("<=", Operator::LessEqual),
+    ("≤", Operator::LessEqual),
+    (">", Operator::Greater),
+    (">=", Operator::GreaterEqual),
+    ("≥", Operator::GreaterEqual),
+    ("&&", Operator::And),
+    ("∧", Operator::And),
+    ("||", Operator::Or),
+    ("∨", Operator::Or),
+    ("&", Operator::Ampersand),
+    ("|", Operator::Pipe),
+    ("⊕", Operator::Xor),
+    ("~", Operator::Tilde),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Token {
+    Number(f64),
+    Op(Operator),
+    /// An operator registered with `CalculatorBuilder::operator`
+    CustomOp(String),
+    LParen,
+    RParen,
+    /// `[`, opens an interval such as `[1, 2]`
+    LBracket,
+    RBracket,
+    Comma,
+    /// A function, variable or constant name
+    Ident(String),
+    /// An imaginary number literal such as `2i`
+    #[cfg(feature = "complex")]
+    Imaginary(f64),
+}
+
+impl Hash for Token {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        ::std::mem::discriminant(self).hash(state);
+        match *self {
+            Token::Number(n) => hash_f64(n, state),
+            #[cfg(feature = "complex")]
+            Token::Imaginary(n) => hash_f64(n, state),
+            Token::Op(op) => op.hash(state),
+            Token::CustomOp(ref name) | Token::Ident(ref name) => name.hash(state),
+            Token::LParen | Token::RParen | Token::LBracket | Token::RBracket | Token::Comma => {}
+        }
+    }
+}
+
+/// A range of characters in the input, `end` is exclusive. Positions are
+/// counted in chars rather than bytes so they line up with what is on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+}
+
+/// Splits an infix expression into tokens, skipping whitespace.
+///
+/// # Example
+/// ```
+/// use infix_calculator::{tokenize, Operator, Token};
+///
+/// let tokens = tokenize("sqrt(x) * 2.5").unwrap();
+/// assert_eq!(tokens, vec![
+///     Token::Ident("sqrt".to_string()),
+///     Token::LParen,
+///     Token::Ident("x".to_string()),
+///     Token::RParen,
+///     Token::Op(Operator::Star),
+///     Token::Number(2.5),
+/// ]);
+/// ```
+pub fn tokenize(input: &str) -> Result<Vec<Token>, CalcError> {
+    Ok(tokenize_spanned(input)?.into_iter().map(|(token, _)| token).collect())
+}
+
+/// Like `tokenize`, but also returns where in the input each token was found.
+///
+/// # Example
+/// ```
+/// use infix_calculator::{tokenize_spanned, Span, Token};
+///
+/// let tokens = tokenize_spanned(" pi").unwrap();
+/// assert_eq!(tokens, vec![(Token::Ident("pi".to_string()), Span::new(1, 3))]);
+/// ```
+pub fn tokenize_spanned(input: &str) -> Result<Vec<(Token, Span)>, CalcError> {
+    let mut errors = Vec::new();
+    let tokens = scan(input, &[], &mut errors);
+    match errors.into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(tokens.into_iter().map(|(token, span)| (token.to_token(), span)).collect()),
+    }
+}
+
+/// Like `tokenize_spanned`, but for input that may be incomplete or wrong,
+/// such as a line that is still being typed. Never fails: characters that
+/// can't be part of a token are skipped and malformed numbers become NaN,
+/// and each of them is in the errors, with where it is.
+///
+/// # Example
+/// ```
+/// use infix_calculator::{tokenize_lossy, CalcError, Span, Token};
+///
+/// let (tokens, errors) = tokenize_lossy("2 $ (x");
+/// assert_eq!(tokens.len(), 3);
+/// assert_eq!(tokens[2], (Token::Ident("x".to_string()), Span::new(5, 6)));
+/// assert_eq!(errors, vec![CalcError::InvalidToken { token: "$".to_string(), span: Span::new(2, 3) }]);
+/// ```
+pub fn tokenize_lossy(input: &str) -> (Vec<(Token, Span)>, Vec<CalcError>) {
+    let mut errors = Vec::new();
+    let tokens = scan(input, &[], &mut errors);
+    (tokens.into_iter().map(|(token, span)| (token.to_token(), span)).collect(), errors)
+}
+
+/// A `Token` borrowing its names from the input, which is what the
+/// parser works on so it doesn't allocate for every token.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Lexeme<'a> {
+    /// The number, and the literal as written if the number isn't exactly
+    /// it, e.g. `9007199254740993` or `0.1000000000000000000001`
+    Number(f64, Option<&'a str>),
+    Op(Operator),
+    CustomOp(&'a str),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Ident(&'a str),
+    #[cfg(feature = "complex")]
+    Imaginary(f64),
+}
+
+impl<'a> Lexeme<'a> {
+    pub(crate) fn to_token(self) -> Token {
+        match self {
+            Lexeme::Number(n, _) => Token::Number(n),
+            Lexeme::Op(op) => Token::Op(op),
+            Lexeme::CustomOp(symbol) => Token::CustomOp(symbol.to_string()),
+            Lexeme::LParen => Token::LParen,
+            Lexeme::RParen => Token::RParen,
+            Lexeme::LBracket => Token::LBracket,
+            Lexeme::RBracket => Token::RBracket,
+            Lexeme::Comma => Token::Comma,
+            Lexeme::Ident(name) => Token::Ident(name.to_string()),
+            #[cfg(feature = "complex")]
+            Lexeme::Imaginary(n) => Token::Imaginary(n),
+        }
+    }
+}
+
+impl<'a> From<&'a Token> for Lexeme<'a> {
+    fn from(token: &'a Token) -> Self {
+        match *token {
+            Token::Number(n) => Lexeme::Number(n, None),
+            Token::Op(op) => Lexeme::Op(op),
+            Token::CustomOp(ref symbol) => Lexeme::CustomOp(symbol),
+            Token::LParen => Lexeme::LParen,
+            Token::RParen => Lexeme::RParen,
+            Token::LBracket => Lexeme::LBracket,
+            Token::RBracket => Lexeme::RBracket,
+            Token::Comma => Lexeme::Comma,
+            Token::Ident(ref name) => Lexeme::Ident(name),
+            #[cfg(feature = "complex")]
+            Token::Imaginary(n) => Lexeme::Imaginary(n),
+        }
+    }
+}
+
+/// What `scan` gives, short expressions fit without going to the heap.
+pub(crate) type Lexemes<'a> = SmallVec<[(Lexeme<'a>, Span); 16]>;
+
+/// Tokenizes the whole input, adding every problem found to `errors`
+/// instead of stopping at the first one. Invalid characters are skipped and
+/// malformed numbers are replaced by NaN so the parser can carry on.
+/// `custom` are the symbols of extra operators, built-in ones win a tie.
+pub(crate) fn scan<'a>(input: &'a str, custom: &[&str], errors: &mut Vec<CalcError>) -> Lexemes<'a> {
+    scan_at_most(input, custom, usize::MAX, errors)
+}
+
+/// Like `scan`, stopping with a `CalcError::TooManyTokens` once there are
+/// more than `max_tokens`.
+pub(crate) fn scan_at_most<'a>(input: &'a str, custom: &[&str], max_tokens: usize, errors: &mut Vec<CalcError>) -> Lexemes<'a> {
+    let mut tokens = Lexemes::new();
+    let offsets: Vec<usize> = input.char_indices().map(|(i, _)| i).collect();
+    let length = offsets.len();
+    // the input from the `start`th char up to the `end`th
+    let slice = |start: usize, end: usize| -> &'a str { &input[offsets[start]..offsets.get(end).map_or(input.len(), |&i| i)] };
+    let mut chars = input.chars().enumerate().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        let token = match c {
+            white_space if white_space.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '(' | ')' | '[' | ']' | ',' => {
+                chars.next();
+                match c {
+                    '(' => Lexeme::LParen,
+                    ')' => Lexeme::RParen,
+                    '[' => Lexeme::LBracket,
+                    ']' => Lexeme::RBracket,
+                    _ => Lexeme::Comma,
+                }
+            }
+            '.' | '0'..='9' => {
+                while let Some(&(_, '.' | '0'..='9' | '_')) = chars.peek() {
+                    chars.next();
+                }
+                let radix = match chars.peek() {
+                    Some(&(_, 'x' | 'X')) => 16,
+                    Some(&(_, 'b' | 'B')) => 2,
+                    Some(&(_, 'o' | 'O')) => 8,
+                    _ => 10,
+                };
+                let value = if slice(start, position(&mut chars, length)) == "0" && radix != 10 {
+                    // prefixed integer literals such as `0xFF`, of any size
+                    chars.next();
+                    while let Some(&(_, c)) = chars.peek() {
+                        if !(c.is_ascii_alphanumeric() || c == '_') {
+                            break;
+                        }
+                        chars.next();
+                    }
+                    strip_separators(&slice(start, position(&mut chars, length))[2..], radix)
+                        .filter(|digits| !digits.is_empty() && digits.chars().all(|c| c.is_digit(radix)))
+                        .and_then(|digits| decimal_digits(&digits, radix).parse::<f64>().ok())
+                } else {
+                    // an exponent such as `e3` or `E-2`, a missing exponent is left to fail parsing
+                    if let Some(&(_, 'e' | 'E')) = chars.peek() {
+                        chars.next();
+                        if let Some(&(_, '+' | '-')) = chars.peek() {
+                            chars.next();
+                        }
+                        while let Some(&(_, '0'..='9' | '_')) = chars.peek() {
+                            chars.next();
+                        }
+                    }
+                    strip_separators(slice(start, position(&mut chars, length)), 10).and_then(|number| number.parse::<f64>().ok())
+                };
+                let imaginary = cfg!(feature = "complex") && is_imaginary_suffix(&chars);
+                if imaginary {
+                    chars.next();
+                }
+                let span = Span::new(start, position(&mut chars, length));
+                match value {
+                    // `1e400` would otherwise be an infinity, which has no literal
+                    Some(value) if value.is_infinite() => {
+                        errors.push(CalcError::NumberOutOfRange { literal: slice(span.start, span.end).to_string(), span });
+                        Lexeme::Number(f64::NAN, None)
+                    }
+                    #[cfg(feature = "complex")]
+                    Some(value) if imaginary => Lexeme::Imaginary(value),
+                    Some(value) => {
+                        let literal = slice(span.start, span.end);
+                        Lexeme::Number(value, Some(literal).filter(|&literal| !is_exactly(value, literal)))
+                    }
+                    None => {
+                        errors.push(CalcError::InvalidToken { token: slice(span.start, span.end).to_string(), span });
+                        Lexeme::Number(f64::NAN, None)
+                    }
+                }
+            }
+            // `$3`, a numbered result of the REPL, is a variable like any other
+            '$' if input[offsets[start] + 1..].starts_with(|c: char| c.is_ascii_digit()) => {
+                chars.next();
+                while let Some(&(_, '0'..='9')) = chars.peek() {
+                    chars.next();
+                }
+                Lexeme::Ident(slice(start, position(&mut chars, length)))
+            }
+            'a'..='z' | 'A'..='Z' | '_' => {
+                while let Some(&(_, c)) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    chars.next();
+                }
+                let name = slice(start, position(&mut chars, length));
+                // `_100` is a misplaced digit separator rather than a name
+                if name.starts_with('_') && name[1..].starts_with(|c: char| c.is_ascii_digit()) {
+                    let span = Span::new(start, start + name.len());
+                    errors.push(CalcError::InvalidToken { token: name.to_string(), span });
+                    continue;
+                }
+                if name == "xor" {
+                    Lexeme::Op(Operator::Xor)
+                } else {
+                    Lexeme::Ident(name)
+                }
+            }
+            // a superscript exponent such as `x²` or `2⁻¹` is read as `^` followed by the exponent
+            c if superscript(c).is_some() => {
+                let mut exponent = String::new();
+                while let Some(normal) = chars.peek().and_then(|&(_, c)| superscript(c)) {
+                    chars.next();
+                    exponent.push(normal);
+                }
+                let span = Span::new(start, position(&mut chars, length));
+                match exponent.parse::<f64>() {
+                    Ok(value) => {
+                        tokens.push((Lexeme::Op(Operator::Caret), span));
+                        Lexeme::Number(value, None)
+                    }
+                    Err(_) => {
+                        errors.push(CalcError::InvalidToken { token: slice(span.start, span.end).to_string(), span });
+                        continue;
+                    }
+                }
+            }
+            // `+-` is an ASCII spelling of `±` only with white space around it,
+            // as `1+-2` is `1 + -2`
+            '+' if start > 0 && slice(start - 1, start).starts_with(char::is_whitespace)
+                && slice(start, length).strip_prefix("+-").is_some_and(|rest| rest.starts_with(char::is_whitespace)) => {
+                chars.next();
+                chars.next();
+                Lexeme::Op(Operator::PlusMinus)
+            }
+            // the constant symbols are names of their own
+            'π' | 'τ' => {
+                chars.next();
+                Lexeme::Ident(slice(start, start + 1))
+            }
+            other => match operator(&input[offsets[start]..], custom) {
+                Some((token, length)) => {
+                    for _ in 0..length {
+                        chars.next();
+                    }
+                    token
+                }
+                None => {
+                    chars.next();
+                    let span = Span::new(start, start + 1);
+                    errors.push(CalcError::InvalidToken { token: other.to_string(), span });
+                    continue;
+                }
+            },
+        };
+        tokens.push((token, Span::new(start, position(&mut chars, length))));
+        if tokens.len() > max_tokens {
+            errors.push(CalcError::TooManyTokens(max_tokens));
+            break;
+        }
+    }
+    tokens
+}
+
+/// How many chars have been read.
+fn position(chars: &mut Peekable<Enumerate<Chars>>, length: usize) -> usize {
+    chars.peek().map_or(length, |&(i, _)| i)
+}
+
+/// The longest operator `rest` starts with, and its length in chars.
+fn operator<'a>(rest: &'a str, custom: &[&str]) -> Option<(Lexeme<'a>, usize)> {
+    let builtin = OPERATOR_SYMBOLS.iter().map(|&(symbol, op)| (symbol, Some(op)));
+    let custom = custom.iter().map(|&symbol| (symbol, None));
+    let mut longest: Option<(&str, Option<Operator>)> = None;
+    for (symbol, op) in builtin.chain(custom) {
+        if rest.starts_with(symbol) && longest.is_none_or(|longest| symbol.len() > longest.0.len()) {
+            longest = Some((symbol, op));
+        }
+    }
+    longest.map(|(symbol, op)| {
+        let token = op.map_or(Lexeme::CustomOp(&rest[..symbol.len()]), Lexeme::Op);
+        (token, symbol.chars().count())
+    })
+}
+
+/// Whether the number just read is followed by an `i` that isn't the start of a name.
+fn is_imaginary_suffix<I: Iterator<Item = (usize, char)> + Clone>(chars: &::std::iter::Peekable<I>) -> bool {
+    let mut lookahead = chars.clone();
+    if lookahead.next().map(|(_, c)| c) != Some('i') {
+        return false;
+    }
+    !lookahead.peek().is_some_and(|&(_, c)| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The plain character for a superscript digit or minus.
+fn superscript(c: char) -> Option<char> {
+    match c {
+        '⁰' => Some('0'),
+        '¹' => Some('1'),
+        '²' => Some('2'),
+        '³' => Some('3'),
+        '⁴'..='⁹' => char::from_u32(c as u32 - '⁴' as u32 + '4' as u32),
+        '⁻' => Some('-'),
+        _ => None,
+    }
+}
+
+/// Removes the `_` digit separators from a number literal, or returns None
+/// if one isn't placed between two digits, e.g. `1__0` or `100_`.
+fn strip_separators(literal: &str, radix: u32) -> Option<Cow<'_, str>> {
+    if !literal.contains('_') {
+        return Some(Cow::Borrowed(literal));
+    }
+    let chars: Vec<char> = literal.chars().collect();
+    for (i, &c) in chars.iter().enumerate() {
+        let between_digits = i > 0 && chars[i - 1].is_digit(radix) && chars.get(i + 1).is_some_and(|c| c.is_digit(radix));
+        if c == '_' && !between_digits {
+            return None;
+        }
+    }
+    Some(Cow::Owned(chars.into_iter().filter(|&c| c != '_').collect()))
+}
+
+/// `literal`, a number literal the lexer accepted, in the digits an
+/// `RPNQueue` keeps: without separators, and in base 10 for the prefixed
+/// integers such as `0xFF`.
+pub(crate) fn exact_literal(literal: &str) -> String {
+    let radix = match literal.get(..2) {
+        Some("0x" | "0X") => 16,
+        Some("0b" | "0B") => 2,
+        Some("0o" | "0O") => 8,
+        _ => return literal.replace('_', ""),
+    };
+    decimal_digits(&literal[2..].replace('_', ""), radix)
+}
+
+/// Whole number `digits` in `radix` as decimal digits, without the limit of
+/// a `u64`.
+fn decimal_digits(digits: &str, radix: u32) -> String {
+    // little endian, each limb holding nine decimal digits
+    let mut limbs: Vec<u64> = vec![0];
+    for digit in digits.chars().filter_map(|c| c.to_digit(radix)) {
+        let mut carry = digit as u64;
+        for limb in limbs.iter_mut() {
+            let product = *limb * radix as u64 + carry;
+            *limb = product % 1_000_000_000;
+            carry = product / 1_000_000_000;
+        }
+        if carry > 0 {
+            limbs.push(carry);
+        }
+    }
+    let mut decimal = limbs.pop().unwrap().to_string();
+    for limb in limbs.iter().rev() {
+        decimal.push_str(&format!("{:09}", limb));
+    }
+    decimal
+}
+
+/// Whether `value` is exactly the number `literal` is, i.e. the shortest
+/// digits for it are the ones of the literal.
+fn is_exactly(value: f64, literal: &str) -> bool {
+    significand(&value.to_string()) == significand(&exact_literal(literal))
+}
+
+/// The significant digits of a decimal `number` and the power of 10 they
+/// are multiplied by, so `1.50` and `15e-1` are the same.
+fn significand(number: &str) -> (String, i64) {
+    let (mantissa, exponent) = match number.find(['e', 'E']) {
+        Some(e) => (&number[..e], number[e + 1..].parse().unwrap_or(0)),
+        None => (number, 0),
+    };
+    let point = mantissa.find('.').unwrap_or(mantissa.len());
+    let (whole, fraction) = (&mantissa[..point], mantissa.get(point + 1..).unwrap_or(""));
+    let digits = format!("{}{}", whole, fraction);
+    let significant = digits.trim_start_matches('0').trim_end_matches('0');
+    if significant.is_empty() {
+        return (String::new(), 0);
+    }
+    let trailing = digits.len() - digits.trim_end_matches('0').len();
+    (significant.to_string(), exponent - fraction.len() as i64 + trailing as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_works() {
+        assert_eq!(tokenize(" 1.5+x_2 !").unwrap(), vec![
+            Token::Number(1.5),
+            Token::Op(Operator::Plus),
+            Token::Ident("x_2".to_string()),
+            Token::Op(Operator::Bang),
+        ]);
+        assert_eq!(tokenize("max(1,2)").unwrap(), vec![
+            Token::Ident("max".to_string()),
+            Token::LParen,
+            Token::Number(1.0),
+            Token::Comma,
+            Token::Number(2.0),
+            Token::RParen,
+        ]);
+        assert_eq!(tokenize("[1,2]").unwrap(), vec![
+            Token::LBracket,
+            Token::Number(1.0),
+            Token::Comma,
+            Token::Number(2.0),
+            Token::RBracket,
+        ]);
+        assert_eq!(tokenize("").unwrap(), vec![]);
+        assert_eq!(tokenize("1 $ 2").unwrap_err(), CalcError::InvalidToken {
+            token: "$".to_string(),
+            span: Span::new(2, 3),
+        });
+        assert_eq!(tokenize("1 + 1.2.3").unwrap_err(), CalcError::InvalidToken {
+            token: "1.2.3".to_string(),
+            span: Span::new(4, 9),
+        });
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let tokens = tokenize_spanned("(x ± 1)").unwrap();
+        let json = serde_json::to_string(&tokens).unwrap();
+        assert_eq!(json, concat!(r#"[["l_paren",{"start":0,"end":1}],[{"ident":"x"},{"start":1,"end":2}],"#,
+                                 r#"[{"op":"plus_minus"},{"start":3,"end":4}],[{"number":1.0},{"start":5,"end":6}],"#,
+                                 r#"["r_paren",{"start":6,"end":7}]]"#));
+        assert_eq!(serde_json::from_str::<Vec<(Token, Span)>>(&json).unwrap(), tokens);
+    }
+
+    #[test]
+    fn scientific_notation() {
+        assert_eq!(tokenize("1.5e3 + 2E-2").unwrap(), vec![
+            Token::Number(1500.0),
+            Token::Op(Operator::Plus),
+            Token::Number(0.02),
+        ]);
+        assert_eq!(tokenize("1e+2").unwrap(), vec![Token::Number(100.0)]);
+        assert_eq!(tokenize("1e").unwrap_err(), CalcError::InvalidToken {
+            token: "1e".to_string(),
+            span: Span::new(0, 2),
+        });
+        assert_eq!(tokenize("2 * 1e+").unwrap_err(), CalcError::InvalidToken {
+            token: "1e+".to_string(),
+            span: Span::new(4, 7),
+        });
+        // without a leading number `e` is still the constant
+        assert_eq!(tokenize("e2").unwrap(), vec![Token::Ident("e2".to_string())]);
+        assert_eq!(tokenize("2 * 1e400").unwrap_err(), CalcError::NumberOutOfRange {
+            literal: "1e400".to_string(),
+            span: Span::new(4, 9),
+        });
+        assert_eq!(tokenize("1e308").unwrap(), vec![Token::Number(1e308)]);
+    }
+
+    #[test]
+    fn prefixed_integers() {
+        assert_eq!(tokenize("0xFF + 0b1010 + 0o17").unwrap(), vec![
+            Token::Number(255.0),
+            Token::Op(Operator::Plus),
+            Token::Number(10.0),
+            Token::Op(Operator::Plus),
+            Token::Number(15.0),
+        ]);
+        assert_eq!(tokenize("0x1e").unwrap(), vec![Token::Number(30.0)]);
+        // more than a u64 holds
+        assert_eq!(tokenize("0x1_0000_0000_0000_0000").unwrap(), vec![Token::Number(18446744073709551616.0)]);
+        assert_eq!(exact_literal("0x1_0000_0000_0000_0001"), "18446744073709551617");
+        assert_eq!(exact_literal("0b1111"), "15");
+        assert_eq!(exact_literal("1_000.5e3"), "1000.5e3");
+        assert_eq!(tokenize("0b102").unwrap_err(), CalcError::InvalidToken {
+            token: "0b102".to_string(),
+            span: Span::new(0, 5),
+        });
+        assert_eq!(tokenize("1 + 0x").unwrap_err(), CalcError::InvalidToken {
+            token: "0x".to_string(),
+            span: Span::new(4, 6),
+        });
+    }
+
+    #[test]
+    fn digit_separators() {
+        assert_eq!(tokenize("1_000_000 * 1.5").unwrap(), vec![
+            Token::Number(1_000_000.0),
+            Token::Op(Operator::Star),
+            Token::Number(1.5),
+        ]);
+        assert_eq!(tokenize("0xFF_FF").unwrap(), vec![Token::Number(65535.0)]);
+        assert_eq!(tokenize("1_0.2_5e1_0").unwrap(), vec![Token::Number(10.25e10)]);
+        for literal in &["_100", "1__0", "100_", "1_.5", "0x_F"] {
+            assert_eq!(tokenize(literal).unwrap_err(), CalcError::InvalidToken {
+                token: literal.to_string(),
+                span: Span::new(0, literal.len()),
+            });
+        }
+    }
+
+    #[test]
+    fn unicode_operators() {
+        assert_eq!(tokenize("6 × 2 ÷ 3 \u{2212} 1·1").unwrap(), tokenize("6 * 2 / 3 - 1*1").unwrap());
+        let spans: Vec<Span> = tokenize_spanned("2×x").unwrap().into_iter().map(|(_, span)| span).collect();
+        assert_eq!(spans, vec![Span::new(0, 1), Span::new(1, 2), Span::new(2, 3)]);
+    }
+
+    #[test]
+    fn names_are_borrowed() {
+        let input = "2 * radius + τ";
+        let tokens = scan(input, &[], &mut Vec::new());
+        match (tokens[2].0, tokens[4].0) {
+            (Lexeme::Ident(radius), Lexeme::Ident(tau)) => {
+                assert_eq!(radius.as_ptr(), input[4..].as_ptr());
+                assert_eq!(tau, "τ");
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(tokens[1].1, Span::new(2, 3));
+        assert_eq!(tokens[4].1, Span::new(13, 14));
+    }
+
+    #[test]
+    fn custom_operators() {
+        let mut errors = Vec::new();
+        let tokens: Vec<Lexeme> = scan("1 // 2 @ 3 / 4", &["//", "@"], &mut errors).into_iter().map(|(token, _)| token).collect();
+        assert!(errors.is_empty());
+        assert_eq!(tokens, vec![
+            Lexeme::Number(1.0, None),
+            Lexeme::CustomOp("//"),
+            Lexeme::Number(2.0, None),
+            Lexeme::CustomOp("@"),
+            Lexeme::Number(3.0, None),
+            Lexeme::Op(Operator::Slash),
+            Lexeme::Number(4.0, None),
+        ]);
+        // built-in operators can't be replaced
+        let tokens = scan("1 + 2", &["+"], &mut errors);
+        assert_eq!(tokens[1], (Lexeme::Op(Operator::Plus), Span::new(2, 3)));
+        assert!(tokenize("1 @ 2").is_err());
+    }
+
+    #[test]
+    fn plus_minus() {
+        let expected = vec![Token::Number(10.0), Token::Op(Operator::PlusMinus), Token::Number(0.2)];
+        assert_eq!(tokenize("10 ± 0.2").unwrap(), expected);
+        assert_eq!(tokenize("10 +- 0.2").unwrap(), expected);
+        assert_eq!(tokenize_spanned("10 \t+-\n0.2").unwrap()[1], (Token::Op(Operator::PlusMinus), Span::new(4, 6)));
+        // otherwise it's a plus and a unary minus
+        for input in &["1+-2", "1 +-2", "1+- 2"] {
+            assert!(!tokenize(input).unwrap().contains(&Token::Op(Operator::PlusMinus)), "{}", input);
+            assert_eq!(::RPNQueue::from_infix_string(input).unwrap().calculate(), Ok(-1.0), "{}", input);
+        }
+    }
+
+    #[test]
+    fn constant_symbols() {
+        assert_eq!(tokenize("2×π×r").unwrap(), vec![
+            Token::Number(2.0),
+            Token::Op(Operator::Star),
+            Token::Ident("π".to_string()),
+            Token::Op(Operator::Star),
+            Token::Ident("r".to_string()),
+        ]);
+        assert_eq!(tokenize("ττ").unwrap(), vec![Token::Ident("τ".to_string()), Token::Ident("τ".to_string())]);
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(tokenize("1<=2 != 3≥x").unwrap(), vec![
+            Token::Number(1.0),
+            Token::Op(Operator::LessEqual),
+            Token::Number(2.0),
+            Token::Op(Operator::NotEqual),
+            Token::Number(3.0),
+            Token::Op(Operator::GreaterEqual),
+            Token::Ident("x".to_string()),
+        ]);
+        assert_eq!(tokenize("3! == 6").unwrap()[1..3], [Token::Op(Operator::Bang), Token::Op(Operator::Equal)]);
+    }
+
+    #[test]
+    fn symbols_read_back() {
+        for &(_, op) in OPERATOR_SYMBOLS {
+            assert_eq!(tokenize(&op.symbol().to_string()).unwrap(), vec![Token::Op(op)], "{:?}", op);
+        }
+    }
+
+    #[test]
+    fn logical_operators() {
+        assert_eq!(tokenize("!a&&b ∨ c").unwrap(), vec![
+            Token::Op(Operator::Bang),
+            Token::Ident("a".to_string()),
+            Token::Op(Operator::And),
+            Token::Ident("b".to_string()),
+            Token::Op(Operator::Or),
+            Token::Ident("c".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        assert_eq!(tokenize("~a&b | c xor xorb ⊕ 1").unwrap(), vec![
+            Token::Op(Operator::Tilde),
+            Token::Ident("a".to_string()),
+            Token::Op(Operator::Ampersand),
+            Token::Ident("b".to_string()),
+            Token::Op(Operator::Pipe),
+            Token::Ident("c".to_string()),
+            Token::Op(Operator::Xor),
+            Token::Ident("xorb".to_string()),
+            Token::Op(Operator::Xor),
+            Token::Number(1.0),
+        ]);
+        assert_eq!(tokenize("a&&b").unwrap()[1], Token::Op(Operator::And));
+    }
+
+    #[test]
+    fn numbered_results() {
+        assert_eq!(tokenize("$12+$1").unwrap(), vec![
+            Token::Ident("$12".to_string()),
+            Token::Op(Operator::Plus),
+            Token::Ident("$1".to_string()),
+        ]);
+        assert!(tokenize("$x").is_err());
+    }
+
+    #[test]
+    fn superscript_exponents() {
+        assert_eq!(tokenize("x²").unwrap(), tokenize("x^2").unwrap());
+        assert_eq!(tokenize("3³ + 2⁻¹").unwrap(), vec![
+            Token::Number(3.0),
+            Token::Op(Operator::Caret),
+            Token::Number(3.0),
+            Token::Op(Operator::Plus),
+            Token::Number(2.0),
+            Token::Op(Operator::Caret),
+            Token::Number(-1.0),
+        ]);
+        assert_eq!(tokenize("10⁴⁵⁶⁷⁸⁹⁰").unwrap()[2], Token::Number(4567890.0));
+        let spans: Vec<Span> = tokenize_spanned("x¹⁰").unwrap().into_iter().map(|(_, span)| span).collect();
+        assert_eq!(spans, vec![Span::new(0, 1), Span::new(1, 3), Span::new(1, 3)]);
+        assert_eq!(tokenize("2²⁻").unwrap_err(), CalcError::InvalidToken {
+            token: "²⁻".to_string(),
+            span: Span::new(1, 3),
+        });
+    }
+
+    #[cfg(feature = "complex")]
+    #[test]
+    fn imaginary_literals() {
+        assert_eq!(tokenize("1 + 2.5i").unwrap(), vec![Token::Number(1.0), Token::Op(Operator::Plus), Token::Imaginary(2.5)]);
+        assert_eq!(tokenize_spanned("3i").unwrap(), vec![(Token::Imaginary(3.0), Span::new(0, 2))]);
+        assert_eq!(tokenize("i").unwrap(), vec![Token::Ident("i".to_string())]);
+        // not an imaginary literal but a name following a number
+        assert_eq!(tokenize("2in").unwrap(), vec![Token::Number(2.0), Token::Ident("in".to_string())]);
+    }
+
+    #[test]
+    fn spans_count_chars() {
+        let spans: Vec<Span> = tokenize_spanned("é + max(10,x)").unwrap_err().span().into_iter().collect();
+        assert_eq!(spans, vec![Span::new(0, 1)]);
+
+        let spans: Vec<Span> = tokenize_spanned("ab + max(10,x)").unwrap().into_iter().map(|(_, span)| span).collect();
+        assert_eq!(spans, vec![
+            Span::new(0, 2),
+            Span::new(3, 4),
+            Span::new(5, 8),
+            Span::new(8, 9),
+            Span::new(9, 11),
+            Span::new(11, 12),
+            Span::new(12, 13),
+            Span::new(13, 14),
+        ]);
+    }
+}
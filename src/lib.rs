@@ -1,26 +1,532 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+compile_error!("either the `std` or the `alloc` feature is needed");
+
+#[cfg(not(feature = "std"))]
 #[macro_use]
-extern crate lazy_static;
+extern crate alloc;
+#[cfg(not(feature = "std"))]
+extern crate hashbrown;
+#[cfg(not(feature = "std"))]
+extern crate once_cell;
+#[cfg(feature = "bigint")]
+extern crate num_bigint;
+#[cfg(feature = "complex")]
+extern crate num_complex;
+#[cfg(feature = "jit")]
+extern crate cranelift_codegen;
+#[cfg(feature = "jit")]
+extern crate cranelift_frontend;
+#[cfg(feature = "jit")]
+extern crate cranelift_jit;
+#[cfg(feature = "jit")]
+extern crate cranelift_module;
+#[cfg(feature = "jit")]
+extern crate cranelift_native;
+#[cfg(any(feature = "bigint", feature = "decimal", not(feature = "std")))]
+extern crate num_traits;
+#[cfg(feature = "decimal")]
+extern crate rust_decimal;
+#[cfg(feature = "python")]
+extern crate pyo3;
+// the code pyo3's macros write uses `::core` paths, which in this edition
+// start from the crate root
+#[cfg(feature = "python")]
+extern crate core;
+#[cfg(feature = "serde")]
+extern crate serde;
+extern crate smallvec;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
 
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::sync::Arc;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use smallvec::SmallVec;
+
+use calculator::OperatorDefinition;
+use lexer::{exact_literal, Lexeme, Lexemes};
+
+mod ast;
+mod bytecode;
+mod bytes;
+mod cache;
+mod cancel;
+mod calculator;
+mod calculus;
+mod compile;
+mod dot;
+mod environment;
+mod equation;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod interval;
+#[cfg(feature = "jit")]
+mod jit;
+mod latex;
+mod lexer;
+mod mathml;
+mod num;
+mod optimize;
+mod prefix;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "std")]
+mod reader;
+mod sexpr;
+mod tree;
+#[cfg(not(feature = "std"))]
+#[path = "no_std.rs"]
+mod std;
+mod uncertain;
+mod value;
+mod visit;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use ast::{BinaryOp, Expr, UnaryOp};
+pub use bytecode::CompiledExpr;
+pub use cache::CachedCalculator;
+pub use cancel::CancelToken;
+pub use calculator::{Calculator, CalculatorBuilder, Limits, Mode, NonFinite, Overflow, ZeroDivision};
+pub use calculus::{derivative_at, integrate, integrate_with, solve, solve_with, Accuracy, Integral, Iterations};
+pub use environment::{split_assignment, Environment, Target, UserFunction};
+pub use equation::{Equation, Solution};
+pub use error::CalcError;
+pub use interval::Interval;
+#[cfg(feature = "jit")]
+pub use jit::JitExpr;
+pub use lexer::{tokenize, tokenize_lossy, tokenize_spanned, Operator, Span, Token};
+pub use num::CalcNum;
+#[cfg(feature = "std")]
+pub use reader::Expressions;
+pub use uncertain::Uncertain;
+pub use value::Value;
+pub use visit::{fold, walk, Fold, Visitor};
 
 /// A vector/queue of strings to represent Reverse Polish Notation
-pub struct RPNQueue(pub Vec<String>);
-
-lazy_static! {
-    static ref PRECEDENCE: HashMap<String, u8> = {
-        let mut result = HashMap::new();
-        result.insert("+".to_string(), 1);
-        result.insert("-".to_string(), 1);
-        result.insert("*".to_string(), 2);
-        result.insert("/".to_string(), 2);
-        result
+///
+/// With the `serde` feature it is serialized as the list of its tokens, e.g.
+/// `["1", "2", "+"]`, and checked like `TryFrom<Vec<String>>` when deserialized.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "Vec<String>", into = "Vec<String>"))]
+pub struct RPNQueue(#[deprecated(note = "use `iter`, `tokens` or `into_tokens` instead")] pub Vec<String>);
+
+/// Which way a chain of operators with the same precedence groups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Associativity {
+    /// `8 - 3 + 2` is `(8 - 3) + 2`
+    Left,
+    /// `2 ^ 3 ^ 2` is `2 ^ (3 ^ 2)`
+    Right,
+}
+
+/// How an operator on the stack binds, higher precedence binds tighter.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct OperatorInfo {
+    precedence: i32,
+    associativity: Associativity,
+}
+
+impl OperatorInfo {
+    pub(crate) const fn new(precedence: i32, associativity: Associativity) -> Self {
+        OperatorInfo { precedence, associativity }
+    }
+}
+
+/// The built-in operators, `neg` being the unary minus, `not` the prefix `!`
+/// and `~` the bitwise not.
+const OPERATORS: &[(&str, OperatorInfo)] = &[
+    ("+", OperatorInfo::new(1, Associativity::Left)),
+    ("-", OperatorInfo::new(1, Associativity::Left)),
+    ("*", OperatorInfo::new(2, Associativity::Left)),
+    ("/", OperatorInfo::new(2, Associativity::Left)),
+    ("%", OperatorInfo::new(2, Associativity::Left)),
+    ("neg", OperatorInfo::new(3, Associativity::Right)),
+    ("not", OperatorInfo::new(3, Associativity::Right)),
+    ("~", OperatorInfo::new(3, Associativity::Right)),
+    ("^", OperatorInfo::new(4, Associativity::Right)),
+    // binds tightest so `2 * 10 ± 1` is twice `10 ± 1`
+    ("±", OperatorInfo::new(5, Associativity::Left)),
+    // the bitwise operators are ordered as in Rust: `&`, `xor`, `|`, all
+    // above the comparisons so `x & 1 == 0` tests the lowest bit
+    ("&", OperatorInfo::new(0, Associativity::Left)),
+    ("xor", OperatorInfo::new(-1, Associativity::Left)),
+    ("|", OperatorInfo::new(-2, Associativity::Left)),
+    // `3 * 4 >= 10` compares `3 * 4`
+    ("==", OperatorInfo::new(-3, Associativity::Left)),
+    ("!=", OperatorInfo::new(-3, Associativity::Left)),
+    ("<", OperatorInfo::new(-3, Associativity::Left)),
+    ("<=", OperatorInfo::new(-3, Associativity::Left)),
+    (">", OperatorInfo::new(-3, Associativity::Left)),
+    (">=", OperatorInfo::new(-3, Associativity::Left)),
+    // below the comparisons, so `x > 0 && x < 10` needs no brackets
+    ("&&", OperatorInfo::new(-4, Associativity::Left)),
+    ("||", OperatorInfo::new(-5, Associativity::Left)),
+];
+
+const CONSTANTS: &[(&str, f64)] = &[
+    ("pi", std::f64::consts::PI),
+    ("e", std::f64::consts::E),
+    ("tau", std::f64::consts::TAU),
+    ("π", std::f64::consts::PI),
+    ("τ", std::f64::consts::TAU),
+];
+
+fn builtin_functions() -> Vec<(&'static str, Function)> {
+    #[allow(unused_mut)]
+    let mut result = vec![
+        ("sqrt", Function::Unary(f64::sqrt)),
+        ("sin", Function::Unary(f64::sin)),
+        ("cos", Function::Unary(f64::cos)),
+        ("tan", Function::Unary(f64::tan)),
+        ("ln", Function::Unary(f64::ln)),
+        ("log", Function::Unary(f64::log10)),
+        ("exp", Function::Unary(f64::exp)),
+        ("abs", Function::Unary(f64::abs)),
+        ("pow", Function::Binary(f64::powf)),
+        ("min", Function::Variadic(|args| args.iter().cloned().fold(f64::INFINITY, f64::min))),
+        ("max", Function::Variadic(|args| args.iter().cloned().fold(f64::NEG_INFINITY, f64::max))),
+    ];
+    // the real versions of the complex number functions
+    #[cfg(feature = "complex")]
+    result.extend(vec![
+        ("arg", Function::Unary(|x| if x < 0.0 { std::f64::consts::PI } else { 0.0 })),
+        ("conj", Function::Unary(|x| x)),
+        ("re", Function::Unary(|x| x)),
+        ("im", Function::Unary(|_| 0.0)),
+    ]);
+    result
+}
+
+pub(crate) type NativeFunction = dyn Fn(&[f64]) -> f64 + Send + Sync;
+
+/// A function a `Calculator` knows, grouped by the number of arguments it takes.
+#[derive(Clone)]
+pub(crate) enum Function {
+    Unary(fn(f64) -> f64),
+    Binary(fn(f64, f64) -> f64),
+    /// Takes one or more arguments
+    Variadic(fn(&[f64]) -> f64),
+    /// Registered with `CalculatorBuilder::function`
+    Native(usize, Arc<NativeFunction>),
+}
+
+impl Function {
+    fn accepts(&self, arity: usize) -> bool {
+        match *self {
+            Function::Unary(_) => arity == 1,
+            Function::Binary(_) => arity == 2,
+            Function::Variadic(_) => arity >= 1,
+            Function::Native(expected, _) => arity == expected,
+        }
+    }
+
+    fn call(&self, args: &[f64]) -> f64 {
+        match *self {
+            Function::Unary(f) => f(args[0]),
+            Function::Binary(f) => f(args[0], args[1]),
+            Function::Variadic(f) => f(args),
+            Function::Native(_, ref f) => f(args),
+        }
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Function::Unary(_) => write!(f, "Unary"),
+            Function::Binary(_) => write!(f, "Binary"),
+            Function::Variadic(_) => write!(f, "Variadic"),
+            Function::Native(arity, _) => write!(f, "Native({})", arity),
+        }
+    }
+}
+
+/// Function calls are written to the RPN as `name(arity)`, e.g. `max(3)`,
+/// so the evaluator knows how many operands to take.
+pub(crate) fn parse_call(token: &str) -> Option<(&str, usize)> {
+    if !token.ends_with(')') {
+        return None;
+    }
+    let open = token.find('(')?;
+    let arity = token[open + 1..token.len() - 1].parse().ok()?;
+    Some((&token[..open], arity))
+}
+
+/// Hashes `n` so that numbers comparing equal, `0.0` and `-0.0`, hash the same.
+pub(crate) fn hash_f64<H: Hasher>(n: f64, state: &mut H) {
+    let n = if n == 0.0 { 0.0 } else { n };
+    n.to_bits().hash(state);
+}
+
+pub(crate) fn is_identifier(token: &str) -> bool {
+    token.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == 'π' || c == 'τ')
+        || token.strip_prefix('$').is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Whether an entry of an `RPNQueue` is the name of a variable or constant.
+fn is_variable(token: &str) -> bool {
+    !matches!(token, "neg" | "not" | "xor") && parse_call(token).is_none() && is_identifier(token)
+}
+
+/// Brackets are kept on the operator stack as `(`, `[` or `name(` for a call.
+fn is_open_bracket(entry: &str) -> bool {
+    entry.ends_with('(') || entry == "["
+}
+
+/// Whether the operator on top of the stack has to be moved to the output
+/// before `incoming` can be pushed. Brackets on the stack are never popped.
+fn should_pop(top: &str, incoming: &str, operators: &HashMap<String, OperatorDefinition>) -> bool {
+    let top = match operators.get(top) {
+        Some(top) => top.info,
+        None => return false,
     };
+    let incoming = operators[incoming].info;
+    match incoming.associativity {
+        Associativity::Left => top.precedence >= incoming.precedence,
+        Associativity::Right => top.precedence > incoming.precedence,
+    }
+}
+
+/// What the shunting-yard algorithm writes out, in the order it is calculated.
+pub(crate) enum Entry<'a> {
+    /// The number, and its literal if the number isn't exactly it, see `Lexeme::Number`
+    Number(f64, Option<&'a str>),
+    #[cfg(feature = "complex")]
+    Imaginary(f64),
+    /// A variable or constant
+    Name(&'a str),
+    /// An operator by its token in an `RPNQueue`, such as `neg` or `+`
+    Operator(&'a str),
+    /// A call and the number of arguments it has
+    Call(&'a str, usize),
+}
+
+/// Where `parse` writes what it has parsed. `RPNQueue` keeps every entry as
+/// its token, `Expr::parse` builds the tree out of them.
+pub(crate) trait Output {
+    fn push(&mut self, entry: Entry);
+    /// The number of entries so far, for `Limits::max_output`.
+    fn len(&self) -> usize;
+}
+
+impl Output for Vec<String> {
+    fn push(&mut self, entry: Entry) {
+        let token = match entry {
+            // the literal itself, so `Mode::Integer` and `Mode::Decimal` get every digit
+            Entry::Number(_, Some(literal)) => exact_literal(literal),
+            Entry::Number(number, None) => number.to_string(),
+            #[cfg(feature = "complex")]
+            Entry::Imaginary(number) => format!("{}i", number),
+            Entry::Name(name) | Entry::Operator(name) => name.to_string(),
+            Entry::Call(name, arity) => format!("{}({})", name, arity),
+        };
+        Vec::push(self, token);
+    }
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+}
+
+/// The shunting-yard algorithm itself, writing what it parses to `output`.
+/// Problems are added to `errors` and parsing carries on as if they
+/// weren't there. Operators and functions are the ones `calculator` knows.
+pub(crate) fn parse<O: Output>(tokens: Lexemes, calculator: &Calculator, errors: &mut Vec<CalcError>, output: &mut O) {
+    // inline for short expressions, only calls, kept as `name(`, need the heap
+    let mut stack: SmallVec<[Cow<str>; 16]> = SmallVec::new();
+    // true when the next token has to be an operand, which is how a
+    // unary minus is told apart from a binary one
+    let mut expect_operand = true;
+    // number of arguments seen so far, one entry per open function call
+    let mut arities: SmallVec<[usize; 8]> = SmallVec::new();
+    // set right after the opening bracket of a call, `f()` has no arguments
+    let mut call_opened = false;
+    // where each bracket still on the stack was opened
+    let mut open_positions: SmallVec<[usize; 8]> = SmallVec::new();
+    let mut tokens = tokens.into_iter().peekable();
+    // an operator at the very end is missing its right operand
+    let mut last = None;
+    while let Some((token, span)) = tokens.next() {
+        last = Some((token, span));
+        let empty_call = call_opened;
+        call_opened = false;
+        let is_operand = match token {
+            Lexeme::Number(..) | Lexeme::Ident(_) | Lexeme::LParen | Lexeme::LBracket => true,
+            #[cfg(feature = "complex")]
+            Lexeme::Imaginary(_) => true,
+            _ => false,
+        };
+        if is_operand && !expect_operand {
+            errors.push(CalcError::MissingOperator(span));
+        }
+        match token {
+            Lexeme::Number(number, literal) => {
+                expect_operand = false;
+                output.push(Entry::Number(number, literal));
+            }
+            #[cfg(feature = "complex")]
+            Lexeme::Imaginary(number) => {
+                expect_operand = false;
+                output.push(Entry::Imaginary(number));
+            }
+            Lexeme::Ident(name) if tokens.peek().is_some_and(|next| next.0 == Lexeme::LParen) => {
+                // the call is kept on the stack as `name(`
+                let (_, open) = tokens.next().unwrap();
+                stack.push(Cow::Owned(format!("{}(", name)));
+                open_positions.push(open.start);
+                arities.push(1);
+                call_opened = true;
+            }
+            Lexeme::Ident(name) => {
+                if calculator.functions.contains_key(name) {
+                    errors.push(CalcError::MissingBrackets { name: name.to_string(), span });
+                }
+                expect_operand = false;
+                output.push(Entry::Name(name));
+            }
+            Lexeme::Op(Operator::Minus) if expect_operand => {
+                // prefix operator, there is no left operand to pop for
+                stack.push(Cow::Borrowed("neg"));
+            }
+            Lexeme::Op(Operator::Bang) if expect_operand => {
+                // a prefix `!` is a logical not, binding like a unary minus
+                stack.push(Cow::Borrowed("not"));
+            }
+            Lexeme::Op(Operator::Tilde) if expect_operand => {
+                stack.push(Cow::Borrowed("~"));
+            }
+            Lexeme::Op(Operator::Tilde) => {
+                errors.push(CalcError::InvalidToken { token: "~".to_string(), span });
+            }
+            Lexeme::Op(Operator::Bang) => {
+                // postfix with the highest precedence: its operand is
+                // already complete, so it goes straight to the output
+                output.push(Entry::Operator("!"));
+            }
+            Lexeme::Op(_) | Lexeme::CustomOp(_) => {
+                if expect_operand {
+                    errors.push(CalcError::MissingOperand(span));
+                }
+                expect_operand = true;
+                let op = match token {
+                    Lexeme::Op(op) => Cow::Borrowed(op.as_str()),
+                    Lexeme::CustomOp(symbol) => Cow::Borrowed(symbol),
+                    _ => unreachable!(),
+                };
+                while !stack.is_empty() && should_pop(stack.last().unwrap(), &op, &calculator.operators) {
+                    let popped = stack.pop().unwrap();
+                    output.push(Entry::Operator(&popped));
+                }
+                stack.push(op);
+            }
+            Lexeme::LParen => {
+                expect_operand = true;
+                stack.push(Cow::Borrowed("("));
+                open_positions.push(span.start);
+            }
+            Lexeme::LBracket => {
+                // `[lo, hi]` becomes a call to `interval`
+                expect_operand = true;
+                stack.push(Cow::Borrowed("["));
+                open_positions.push(span.start);
+                arities.push(1);
+                call_opened = true;
+            }
+            Lexeme::Comma => {
+                if expect_operand {
+                    errors.push(CalcError::MissingArgument(span));
+                }
+                while !stack.is_empty() && !is_open_bracket(stack.last().unwrap()) {
+                    let popped = stack.pop().unwrap();
+                    output.push(Entry::Operator(&popped))
+                }
+                expect_operand = true;
+                if stack.last().is_none_or(|top| top == "(") {
+                    errors.push(CalcError::InvalidToken { token: ",".to_string(), span });
+                    continue;
+                }
+                *arities.last_mut().unwrap() += 1;
+            }
+            Lexeme::RParen | Lexeme::RBracket => {
+                while !stack.is_empty() && !is_open_bracket(stack.last().unwrap()) {
+                    let popped = stack.pop().unwrap();
+                    output.push(Entry::Operator(&popped))
+                }
+                let open = match stack.pop() {
+                    Some(open) => open,
+                    None => {
+                        errors.push(CalcError::UnbalancedParen { position: span.start });
+                        continue;
+                    }
+                };
+                open_positions.pop();
+                if (open == "[") != (token == Lexeme::RBracket) {
+                    errors.push(CalcError::UnbalancedParen { position: span.start });
+                }
+                if open.len() > 1 || open == "[" {
+                    let mut arity = arities.pop().unwrap();
+                    if empty_call {
+                        arity = 0;
+                    } else if expect_operand {
+                        errors.push(CalcError::MissingArgument(span));
+                    }
+                    let function = if open == "[" { "interval" } else { &open[..open.len() - 1] };
+                    // user defined functions are only known once evaluated
+                    if calculator.functions.get(function).is_some_and(|f| !f.accepts(arity)) || open == "[" && arity != 2 {
+                        errors.push(CalcError::WrongArgumentCount { function: function.to_string(), count: arity });
+                    }
+                    output.push(Entry::Call(function, arity));
+                } else if expect_operand {
+                    errors.push(CalcError::MissingOperand(span));
+                }
+                expect_operand = false;
+            }
+        }
+        if let Some(err) = calculator.limits().exceeded(open_positions.len(), output.len()) {
+            // stop before anything else is allocated
+            errors.push(err);
+            return;
+        }
+    }
+    if let Some((Lexeme::Op(_) | Lexeme::CustomOp(_), span)) = last {
+        if expect_operand {
+            errors.push(CalcError::MissingOperand(span));
+        }
+    }
+
+    while let Some(op) = stack.pop() {
+        if is_open_bracket(&op) {
+            errors.push(CalcError::UnbalancedParen { position: open_positions.pop().unwrap() });
+        } else {
+            output.push(Entry::Operator(&op));
+        }
+    }
+    if let Some(err) = calculator.limits().exceeded(0, output.len()) {
+        errors.push(err);
+    }
 }
 
 impl RPNQueue {
     /// Constructs a new `Result<RPNQueue>` using a string with infix notation
     /// with [shunting-yard algorithm](https://en.wikipedia.org/wiki/Shunting-yard_algorithm).
-    /// Supports decimals and round brackets only.
+    /// Supports decimals, round brackets, the `+ - * / % ^` operators, unary minus, postfix factorial `!`
+    /// and calls to the built-in functions `sqrt`, `sin`, `cos`, `tan`, `ln`, `log`, `exp`, `abs`,
+    /// `pow`, `min` and `max`. Arguments are separated by commas. Other names, either
+    /// variables or calls to user defined functions, are looked up when the queue is calculated.
     ///
     /// # Examples
     /// ```
@@ -28,55 +534,113 @@ impl RPNQueue {
     ///
     /// let queue = RPNQueue::from_infix_string(&"1.0 + 3 - (4 / 5)");
     /// ```
-    pub fn from_infix_string(input: &str) -> Result<Self, String> {
-        let mut output = RPNQueue(Vec::new());
-        let mut stack = Vec::new();
-        let mut buffer = String::new();
-        for token in input.chars() {
-            match token {
-                white_space if white_space.is_whitespace() => {
-                    if !buffer.is_empty() {
-                        output.0.push(buffer);
-                        buffer = String::new();
-                    }
-                }
-                '+' | '-' | '*' | '/' => {
-                    if !buffer.is_empty() {
-                        output.0.push(buffer);
-                        buffer = String::new();
-                    }
-                    while !stack.is_empty() && PRECEDENCE.get(stack.last().unwrap()).unwrap_or(&0) >
-                        PRECEDENCE.get(&token.to_string()).unwrap() {
-                        let op = stack.pop().unwrap();
-                        output.0.push(op);
-                    }
-                    stack.push(token.to_string());
-                }
-                '(' => stack.push(token.to_string()),
-                ')' => {
-                    if !buffer.is_empty() {
-                        output.0.push(buffer);
-                        buffer = String::new();
-                    }
-                    while !stack.is_empty() && stack.last().unwrap() != "(" {
-                        let popped = stack.pop().unwrap();
-                        output.0.push(popped)
-                    }
-                    stack.pop();
-                }
-                '.' | '0'...'9' => {
-                    buffer.push(token);
-                }
-                invalid => {
-                    return Err(format!("Invalid token: {}", invalid))
-                }
-            }
-        }
+    pub fn from_infix_string(input: &str) -> Result<Self, CalcError> {
+        Calculator::standard().parse(input)
+    }
 
-        while !stack.is_empty() {
-            output.0.push(stack.pop().unwrap());
-        }
-        Ok(output)
+    /// Like `from_infix_string`, but keeps going after a mistake and returns
+    /// every problem found in `input`, in the order they appear.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::RPNQueue;
+    ///
+    /// let errors = RPNQueue::from_infix_string_all_errors("1 $ 2 + sqrt").err().unwrap();
+    /// assert_eq!(errors.len(), 3);
+    /// ```
+    pub fn from_infix_string_all_errors(input: &str) -> Result<Self, Vec<CalcError>> {
+        Calculator::standard().parse_all(input)
+    }
+
+    /// Constructs a new `Result<RPNQueue>` from the output of `tokenize_spanned`,
+    /// see `from_infix_string`.
+    pub fn from_tokens(tokens: Vec<(Token, Span)>) -> Result<Self, CalcError> {
+        Calculator::standard().parse_tokens(tokens.iter().map(|&(ref token, span)| (Lexeme::from(token), span)).collect())
+    }
+
+    /// Constructs a new `Result<RPNQueue>` from tokens already in Reverse Polish
+    /// Notation, separated by whitespace. Tokens are written the way the
+    /// `Display` of a queue writes them: `neg` for a unary minus and
+    /// `name(arity)` for a call. Fails unless every operator has its operands
+    /// and exactly one value is left at the end.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::{CalcError, RPNQueue};
+    ///
+    /// let queue = RPNQueue::from_rpn_string("3 4 2 * 1 5 - 2 3 ^ ^ / +").unwrap();
+    /// assert_eq!(queue.calculate().unwrap(), 3.0001220703125);
+    ///
+    /// assert_eq!(RPNQueue::from_rpn_string("1 2").err(), Some(CalcError::TooManyOperands));
+    /// ```
+    pub fn from_rpn_string(input: &str) -> Result<Self, CalcError> {
+        RPNQueue::try_from(input.split_whitespace().map(String::from).collect::<Vec<String>>())
+    }
+
+    /// Writes the queue as an infix expression again, with only the brackets
+    /// needed for it to be parsed into the same queue. Fails if the tokens
+    /// aren't a single expression.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::RPNQueue;
+    ///
+    /// let queue = RPNQueue::from_infix_string("((1 + 2)) * (3 ^ 2) - (4 - 5)").unwrap();
+    /// assert_eq!(queue.to_infix().unwrap(), "(1 + 2) * 3 ^ 2 - (4 - 5)");
+    /// ```
+    pub fn to_infix(&self) -> Result<String, CalcError> {
+        to_infix(self.tokens(), &Calculator::standard().operators)
+    }
+
+    /// Wraps tokens known to be in Reverse Polish Notation already.
+    #[allow(deprecated)]
+    pub(crate) fn from_vec(tokens: Vec<String>) -> Self {
+        RPNQueue(tokens)
+    }
+
+    /// The tokens in Reverse Polish Notation.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::RPNQueue;
+    ///
+    /// let queue = RPNQueue::from_infix_string("1 + 2 * 3").unwrap();
+    /// assert_eq!(queue.tokens(), ["1", "2", "3", "*", "+"]);
+    /// ```
+    #[allow(deprecated)]
+    pub fn tokens(&self) -> &[String] {
+        &self.0
+    }
+
+    #[allow(deprecated)]
+    pub fn into_tokens(self) -> Vec<String> {
+        self.0
+    }
+
+    /// Iterates over the tokens in the order they are calculated.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.tokens().iter())
+    }
+
+    /// The number of tokens.
+    pub fn len(&self) -> usize {
+        self.tokens().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens().is_empty()
+    }
+
+    /// The token at `index`, if there is one.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.tokens().get(index).map(String::as_str)
+    }
+
+    /// `tokens` as a queue, see the `parse` function.
+    pub(crate) fn parse(tokens: Lexemes, calculator: &Calculator, errors: &mut Vec<CalcError>) -> Self {
+        let mut output: Vec<String> = Vec::with_capacity(tokens.len());
+        parse(tokens, calculator, errors, &mut output);
+        RPNQueue::from_vec(output)
     }
 
     /// Calculate result for given RPNQueue.
@@ -85,40 +649,562 @@ impl RPNQueue {
     /// ```
     /// use infix_calculator::RPNQueue;
     ///
-    /// let mut queue = RPNQueue::from_infix_string(&"1.0 + 3 - (4 / 5)").unwrap();
+    /// let queue = RPNQueue::from_infix_string(&"1.0 + 3 - (4 / 5)").unwrap();
     /// assert_eq!(queue.calculate().unwrap(), 3.2);
     /// ```
-    pub fn calculate(&mut self) -> Result<f64, String> {
-        let mut numbers = Vec::new();
-        for x in self.0.iter() {
-            match x.as_ref() {
-                "+" | "-" | "*" | "/" => {
-                    let second = numbers.pop().ok_or("not enough input".to_string())?;
-                    let first = numbers.pop().ok_or("not enough input".to_string())?;
+    pub fn calculate(&self) -> Result<f64, CalcError> {
+        self.compile(&[])?.calculate(&[])
+    }
+
+    /// Calculate result for given RPNQueue with any `CalcNum` type instead of `f64`.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::RPNQueue;
+    ///
+    /// let queue = RPNQueue::from_infix_string(&"0.1 + 0.2").unwrap();
+    /// assert_eq!(queue.calculate_as::<f32>().unwrap(), 0.3);
+    /// ```
+    pub fn calculate_as<T: CalcNum>(&self) -> Result<T, CalcError> {
+        self.evaluate(&Scope::new(&HashMap::new(), &HashMap::new()))
+    }
+
+    /// Calculate result for given RPNQueue, resolving names through the
+    /// variables of `environment`.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::{Environment, RPNQueue};
+    ///
+    /// let mut environment = Environment::new();
+    /// environment.set("x", 5.0);
+    ///
+    /// let queue = RPNQueue::from_infix_string(&"x * 2 + 1").unwrap();
+    /// assert_eq!(queue.calculate_with(&environment).unwrap(), 11.0);
+    /// ```
+    pub fn calculate_with(&self, environment: &Environment) -> Result<f64, CalcError> {
+        self.evaluate(&Scope::new(environment.variables(), environment.functions()))
+    }
+
+    /// Calculate result for given RPNQueue, resolving names through `constants`
+    /// before falling back to the built-in `pi`, `e` and `tau`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::collections::HashMap;
+    /// use infix_calculator::RPNQueue;
+    ///
+    /// let mut constants = HashMap::new();
+    /// constants.insert("r".to_string(), 2.0);
+    ///
+    /// let queue = RPNQueue::from_infix_string(&"tau * r").unwrap();
+    /// assert_eq!(queue.calculate_with_constants(&constants).unwrap(), 4.0 * std::f64::consts::PI);
+    /// ```
+    pub fn calculate_with_constants(&self, constants: &HashMap<String, f64>) -> Result<f64, CalcError> {
+        self.evaluate(&Scope::new(constants, &HashMap::new()))
+    }
+
+    /// Calculate result for given RPNQueue with the variables in `vars`, so a
+    /// queue can be parsed once and calculated for many values. Every name
+    /// that is neither in `vars` nor a constant is listed in the error.
+    ///
+    /// # Example
+    /// ```
+    /// use std::collections::HashMap;
+    /// use infix_calculator::{CalcError, RPNQueue};
+    ///
+    /// let queue = RPNQueue::from_infix_string(&"a * x + b").unwrap();
+    /// let mut vars = HashMap::new();
+    /// vars.insert("x".to_string(), 2.0);
+    /// assert_eq!(queue.calculate_with_vars(&vars).unwrap_err(),
+    ///            CalcError::MissingVariables(vec!["a".to_string(), "b".to_string()]));
+    ///
+    /// vars.insert("a".to_string(), 3.0);
+    /// vars.insert("b".to_string(), 1.0);
+    /// assert_eq!(queue.calculate_with_vars(&vars).unwrap(), 7.0);
+    /// ```
+    pub fn calculate_with_vars(&self, vars: &HashMap<String, f64>) -> Result<f64, CalcError> {
+        let functions = HashMap::new();
+        let scope = Scope::new(vars, &functions);
+        let mut missing: Vec<String> = Vec::new();
+        for name in self.iter().filter(|token| is_variable(token)) {
+            if scope.lookup(name).is_err() && !missing.iter().any(|known| known == name) {
+                missing.push(name.to_string());
+            }
+        }
+        if !missing.is_empty() {
+            return Err(CalcError::MissingVariables(missing));
+        }
+        self.evaluate(&scope)
+    }
 
-                    let result = compute_result(first, second, x)?;
-                    numbers.push(result);
+    fn evaluate<T: CalcNum>(&self, scope: &Scope) -> Result<T, CalcError> {
+        let mut numbers: Vec<T> = Vec::new();
+        let skips = right_operands(self.tokens(), &scope.calculator.operators);
+        let mut i = 0;
+        while let Some(x) = self.get(i) {
+            scope.calculator.check_cancelled()?;
+            if let (Some(end), Some(left)) = (skips[i], numbers.last()) {
+                let left = left.to_f64() != 0.0;
+                if short_circuits(&self.tokens()[end], left) {
+                    numbers.pop();
+                    numbers.push(T::from_f64(left as u8 as f64));
+                    i = end + 1;
+                    continue;
+                }
+            }
+            i += 1;
+            match x {
+                op if is_binary_operator(op) => {
+                    let second = numbers.pop().ok_or(CalcError::NotEnoughOperands)?;
+                    let first = numbers.pop().ok_or(CalcError::NotEnoughOperands)?;
+
+                    // `±` needs a `Calculator`
+                    let op = BinaryOp::from_symbol(op).ok_or_else(|| CalcError::UnknownOperator(op.to_string()))?;
+                    numbers.push(first.binary(op, second)?);
+                }
+                "neg" => {
+                    let operand = numbers.pop().ok_or(CalcError::NotEnoughOperands)?;
+                    numbers.push(operand.negate());
+                }
+                "not" => {
+                    let operand = numbers.pop().ok_or(CalcError::NotEnoughOperands)?;
+                    numbers.push(T::from_f64((operand.to_f64() == 0.0) as u8 as f64));
+                }
+                "~" => {
+                    let operand = numbers.pop().ok_or(CalcError::NotEnoughOperands)?;
+                    numbers.push(T::from_f64(!whole(operand.to_f64())? as f64));
+                }
+                "!" => {
+                    let operand = numbers.pop().ok_or(CalcError::NotEnoughOperands)?;
+                    numbers.push(operand.factorial()?);
+                }
+                call if parse_call(call).is_some() => {
+                    let (name, arity) = parse_call(call).unwrap();
+                    if numbers.len() < arity {
+                        return Err(CalcError::NotEnoughOperands);
+                    }
+                    let arguments = numbers.split_off(numbers.len() - arity);
+                    if let Some(result) = T::call(name, &arguments) {
+                        numbers.push(result?);
+                        continue;
+                    }
+                    let arguments = arguments.iter().map(T::to_f64).collect();
+                    numbers.push(T::from_f64(scope.call(name, arguments)?));
+                }
+                name if is_identifier(name) => {
+                    numbers.push(T::from_f64(scope.lookup(name)?));
                 }
                 number => {
-                    let number: f64 = number.parse::<f64>().or(Err(format!("Invalid token: {}", number)))?;
+                    let number = T::parse(number).ok_or_else(|| CalcError::InvalidRpnToken(number.to_string()))?;
                     numbers.push(number);
                 }
             }
         }
 
-        let result = numbers.pop().ok_or("not enough input".to_string())?;
+        let result = numbers.pop().ok_or(CalcError::NotEnoughOperands)?;
         Ok(result)
     }
 }
 
-fn compute_result(first: f64, second: f64, op: &str) -> Result<f64, String> {
+/// Parses an infix expression, see `RPNQueue::from_infix_string`.
+///
+/// # Example
+/// ```
+/// use infix_calculator::RPNQueue;
+///
+/// let queue: RPNQueue = "2 * (3 + 4)".parse().unwrap();
+/// assert_eq!(queue.calculate().unwrap(), 14.0);
+/// ```
+impl FromStr for RPNQueue {
+    type Err = CalcError;
+
+    fn from_str(input: &str) -> Result<Self, CalcError> {
+        RPNQueue::from_infix_string(input)
+    }
+}
+
+impl<'a> TryFrom<&'a str> for RPNQueue {
+    type Error = CalcError;
+
+    fn try_from(input: &'a str) -> Result<Self, CalcError> {
+        RPNQueue::from_infix_string(input)
+    }
+}
+
+/// Takes tokens already in Reverse Polish Notation, checking that they are
+/// ones an `RPNQueue` can have and that every operator has its operands.
+///
+/// # Example
+/// ```
+/// use std::convert::TryFrom;
+/// use infix_calculator::{CalcError, RPNQueue};
+///
+/// let tokens = vec!["2".to_string(), "x".to_string(), "max(2)".to_string()];
+/// assert!(RPNQueue::try_from(tokens).is_ok());
+/// assert_eq!(RPNQueue::try_from(vec!["+".to_string()]).err(), Some(CalcError::NotEnoughOperands));
+/// ```
+impl TryFrom<Vec<String>> for RPNQueue {
+    type Error = CalcError;
+
+    fn try_from(tokens: Vec<String>) -> Result<Self, CalcError> {
+        validate(&tokens, &Calculator::standard().operators)?;
+        Ok(RPNQueue::from_vec(tokens))
+    }
+}
+
+/// Checks that `tokens` are one expression in Reverse Polish Notation
+/// using the operators in `operators`.
+pub(crate) fn validate(tokens: &[String], operators: &HashMap<String, OperatorDefinition>) -> Result<(), CalcError> {
+    let mut depth: usize = 0;
+    for token in tokens {
+        let operands = arity(token, operators)?;
+        depth = depth.checked_sub(operands).ok_or(CalcError::NotEnoughOperands)? + 1;
+    }
+    match depth {
+        0 => Err(CalcError::NotEnoughOperands),
+        1 => Ok(()),
+        _ => Err(CalcError::TooManyOperands),
+    }
+}
+
+/// The number of operands an entry of an `RPNQueue` takes, zero for numbers
+/// and names.
+pub(crate) fn arity(token: &str, operators: &HashMap<String, OperatorDefinition>) -> Result<usize, CalcError> {
+    match token {
+        "neg" | "not" | "~" | "!" => Ok(1),
+        token if operators.contains_key(token) => Ok(2),
+        token if parse_call(token).is_some() => Ok(parse_call(token).unwrap().1),
+        token if is_variable(token) || token.parse::<f64>().is_ok() => Ok(0),
+        token => Err(CalcError::InvalidRpnToken(token.to_string())),
+    }
+}
+
+/// For every entry of `tokens`, the `&&` or `||` whose right operand starts
+/// there. Evaluating jumps from there to past the operator when the left
+/// operand, on top of the stack by then, decides the result on its own.
+pub(crate) fn right_operands(tokens: &[String], operators: &HashMap<String, OperatorDefinition>) -> Vec<Option<usize>> {
+    let mut starts = vec![None; tokens.len()];
+    for (end, token) in tokens.iter().enumerate() {
+        if !is_logical(token) {
+            continue;
+        }
+        let (mut start, mut needed) = (end, 1);
+        while needed > 0 && start > 0 {
+            start -= 1;
+            needed = needed - 1 + arity(&tokens[start], operators).unwrap_or(0);
+        }
+        if needed == 0 {
+            starts[start] = Some(end);
+        }
+    }
+    starts
+}
+
+/// Iterator over the tokens of an `RPNQueue`, see `RPNQueue::iter`.
+#[derive(Debug, Clone)]
+pub struct Iter<'a>(std::slice::Iter<'a, String>);
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        self.0.next().map(String::as_str)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for Iter<'a> {
+    fn next_back(&mut self) -> Option<&'a str> {
+        self.0.next_back().map(String::as_str)
+    }
+}
+
+impl<'a> ExactSizeIterator for Iter<'a> {}
+
+impl<'a> IntoIterator for &'a RPNQueue {
+    type Item = &'a str;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl From<RPNQueue> for Vec<String> {
+    fn from(queue: RPNQueue) -> Self {
+        queue.into_tokens()
+    }
+}
+
+impl IntoIterator for RPNQueue {
+    type Item = String;
+    type IntoIter = std::vec::IntoIter<String>;
+
+    fn into_iter(self) -> std::vec::IntoIter<String> {
+        self.into_tokens().into_iter()
+    }
+}
+
+/// Space separated, or written as an infix expression with `{:#}`.
+///
+/// # Example
+/// ```
+/// use infix_calculator::RPNQueue;
+///
+/// let queue = RPNQueue::from_infix_string("(1 + 2) * -max(3, 4)!").unwrap();
+/// assert_eq!(queue.to_string(), "1 2 + 3 4 max(2) ! neg *");
+/// assert_eq!(format!("{:#}", queue), "(1 + 2) * -max(3, 4)!");
+/// ```
+impl fmt::Display for RPNQueue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            if let Ok(infix) = self.to_infix() {
+                return f.write_str(&infix);
+            }
+        }
+        f.write_str(&self.tokens().join(" "))
+    }
+}
+
+/// How tightly the postfix `!` and operands such as numbers and calls bind,
+/// above any operator.
+const POSTFIX_PRECEDENCE: i32 = i32::MAX - 1;
+const OPERAND_PRECEDENCE: i32 = i32::MAX;
+
+/// Part of an expression being written as infix.
+struct InfixOperand {
+    infix: String,
+    /// Of its outermost operator
+    precedence: i32,
+    /// Starts with a unary minus or `!`, which needs no brackets after an operator
+    negated: bool,
+}
+
+impl InfixOperand {
+    fn new(infix: String, precedence: i32) -> Self {
+        InfixOperand { infix, precedence, negated: false }
+    }
+
+    /// The operand with brackets unless it binds at least as tight as `min`.
+    fn bracketed(self, min: i32) -> String {
+        if self.precedence < min {
+            format!("({})", self.infix)
+        } else {
+            self.infix
+        }
+    }
+}
+
+/// Writes RPN `tokens` as an infix expression, with only the brackets the
+/// precedence of `operators` needs. Symbols that aren't in `operators` are
+/// taken to be binary operators binding looser than all of them.
+pub(crate) fn to_infix(tokens: &[String], operators: &HashMap<String, OperatorDefinition>) -> Result<String, CalcError> {
+    let mut operands: Vec<InfixOperand> = Vec::new();
+    let neg = operators["neg"].info.precedence;
+    for token in tokens {
+        let operand = match token.as_str() {
+            "neg" | "not" | "~" => {
+                let operand = operands.pop().ok_or(CalcError::NotEnoughOperands)?;
+                let prefix = match token.as_str() {
+                    "neg" => "-",
+                    "not" => "!",
+                    _ => "~",
+                };
+                InfixOperand { infix: format!("{}{}", prefix, operand.bracketed(neg)), precedence: neg, negated: true }
+            }
+            "!" => {
+                let operand = operands.pop().ok_or(CalcError::NotEnoughOperands)?;
+                InfixOperand::new(format!("{}!", operand.bracketed(POSTFIX_PRECEDENCE)), POSTFIX_PRECEDENCE)
+            }
+            token if parse_call(token).is_some() => {
+                let (name, arity) = parse_call(token).unwrap();
+                if operands.len() < arity {
+                    return Err(CalcError::NotEnoughOperands);
+                }
+                let arguments: Vec<String> = operands.split_off(operands.len() - arity).into_iter().map(|argument| argument.infix).collect();
+                if name == "interval" {
+                    InfixOperand::new(format!("[{}]", arguments.join(", ")), OPERAND_PRECEDENCE)
+                } else {
+                    InfixOperand::new(format!("{}({})", name, arguments.join(", ")), OPERAND_PRECEDENCE)
+                }
+            }
+            // a negative number, such as from `Expr::to_rpn`, reads like a negation
+            token if token.starts_with('-') && token.parse::<f64>().is_ok() => {
+                InfixOperand { infix: token.to_string(), precedence: neg, negated: true }
+            }
+            token if is_identifier(token) && !operators.contains_key(token) || token.parse::<f64>().is_ok() => {
+                InfixOperand::new(token.to_string(), OPERAND_PRECEDENCE)
+            }
+            symbol => {
+                let info = operators.get(symbol).map_or(OperatorInfo::new(i32::MIN, Associativity::Left), |op| op.info);
+                let precedence = info.precedence;
+                let right = operands.pop().ok_or(CalcError::NotEnoughOperands)?;
+                let left = operands.pop().ok_or(CalcError::NotEnoughOperands)?;
+                // the other side of the one the operator groups towards needs
+                // brackets for the same precedence too
+                let (left_min, right_min) = match info.associativity {
+                    Associativity::Left => (precedence, precedence + 1),
+                    Associativity::Right => (precedence + 1, precedence),
+                };
+                // `2 ^ -1` is read as `2 ^ (-1)` anyway
+                let right_min = if right.negated { 0 } else { right_min };
+                InfixOperand::new(format!("{} {} {}", left.bracketed(left_min), symbol, right.bracketed(right_min)), precedence)
+            }
+        };
+        operands.push(operand);
+    }
+    match operands.len() {
+        0 => Err(CalcError::NotEnoughOperands),
+        1 => Ok(operands.pop().unwrap().infix),
+        _ => Err(CalcError::TooManyOperands),
+    }
+}
+
+/// Writes `input` again with canonical spacing and only the brackets it
+/// needs, for tidying up or comparing stored formulas. Numbers are written
+/// the shortest way, so `1.50` becomes `1.5`.
+///
+/// # Example
+/// ```
+/// use infix_calculator::format_expression;
+///
+/// assert_eq!(format_expression("1+2*(3)").unwrap(), "1 + 2 * 3");
+/// assert_eq!(format_expression("-(x)²÷max( a ,(b) )").unwrap(), "-x ^ 2 / max(a, b)");
+/// ```
+pub fn format_expression(input: &str) -> Result<String, CalcError> {
+    RPNQueue::from_infix_string(input)?.to_infix()
+}
+
+/// Calls deeper than this are assumed to be runaway recursion.
+const MAX_CALL_DEPTH: usize = 64;
+
+/// Everything names in an expression can refer to while it is evaluated.
+pub(crate) struct Scope<'a> {
+    variables: &'a HashMap<String, f64>,
+    functions: &'a HashMap<String, UserFunction>,
+    /// Has the constants and functions
+    calculator: &'a Calculator,
+    /// Parameters of the user defined function being evaluated
+    arguments: HashMap<String, f64>,
+    depth: usize,
+}
+
+impl<'a> Scope<'a> {
+    pub(crate) fn new(variables: &'a HashMap<String, f64>, functions: &'a HashMap<String, UserFunction>) -> Self {
+        Scope { variables, functions, calculator: Calculator::standard(), arguments: HashMap::new(), depth: 0 }
+    }
+
+    pub(crate) fn lookup(&self, name: &str) -> Result<f64, CalcError> {
+        self.arguments.get(name)
+            .or_else(|| self.variables.get(name))
+            .or_else(|| self.calculator.constants.get(name))
+            .cloned()
+            .ok_or_else(|| CalcError::UnknownVariable(name.to_string()))
+    }
+
+    pub(crate) fn call(&self, name: &str, arguments: Vec<f64>) -> Result<f64, CalcError> {
+        if let Some(function) = self.calculator.functions.get(name) {
+            if !function.accepts(arguments.len()) {
+                return Err(CalcError::WrongArgumentCount { function: name.to_string(), count: arguments.len() });
+            }
+            return Ok(function.call(&arguments));
+        }
+
+        let function = self.functions.get(name).ok_or_else(|| CalcError::UnknownFunction(name.to_string()))?;
+        if function.parameters.len() != arguments.len() {
+            return Err(CalcError::WrongArgumentCount { function: name.to_string(), count: arguments.len() });
+        }
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(CalcError::RecursionLimit(name.to_string()));
+        }
+        function.body.evaluate(&Scope {
+            variables: self.variables,
+            functions: self.functions,
+            calculator: self.calculator,
+            arguments: function.parameters.iter().cloned().zip(arguments).collect(),
+            depth: self.depth + 1,
+        })
+    }
+}
+
+pub(crate) fn compute_result(first: f64, second: f64, op: &str) -> Result<f64, CalcError> {
     match op {
         "+" => Ok(first + second),
         "-" => Ok(first - second),
         "*" => Ok(first * second),
         "/" => Ok(first / second),
-        _ => Err(format!("invalid operator: {}", op))
+        // truncated remainder, the result takes the sign of `first`
+        "%" => Ok(first % second),
+        "^" => Ok(first.powf(second)),
+        // comparisons give 1 for true and 0 for false
+        "==" => Ok((first == second) as u8 as f64),
+        "!=" => Ok((first != second) as u8 as f64),
+        "<" => Ok((first < second) as u8 as f64),
+        "<=" => Ok((first <= second) as u8 as f64),
+        ">" => Ok((first > second) as u8 as f64),
+        ">=" => Ok((first >= second) as u8 as f64),
+        // anything but 0 is true, NaN included
+        "&&" => Ok((first != 0.0 && second != 0.0) as u8 as f64),
+        "||" => Ok((first != 0.0 || second != 0.0) as u8 as f64),
+        "&" => Ok((whole(first)? & whole(second)?) as f64),
+        "|" => Ok((whole(first)? | whole(second)?) as f64),
+        "xor" => Ok((whole(first)? ^ whole(second)?) as f64),
+        _ => Err(CalcError::UnknownOperator(op.to_string()))
+    }
+}
+
+/// Whether `op` is one of the built-in operators taking two operands.
+pub(crate) fn is_binary_operator(op: &str) -> bool {
+    matches!(op, "+" | "-" | "*" | "/" | "%" | "^" | "±") || is_comparison(op) || is_logical(op) || is_bitwise(op)
+}
+
+/// Whether `op` is `&`, `|` or `xor`.
+pub(crate) fn is_bitwise(op: &str) -> bool {
+    matches!(op, "&" | "|" | "xor")
+}
+
+/// `n` as an `i64` for the bitwise operators, which only take whole numbers.
+pub(crate) fn whole(n: f64) -> Result<i64, CalcError> {
+    // i64::MAX rounds up to 2^63 as a float, which is already out of range
+    if n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64 {
+        Ok(n as i64)
+    } else {
+        Err(CalcError::NotAnInteger(n))
+    }
+}
+
+/// Whether `op` is `&&` or `||`.
+pub(crate) fn is_logical(op: &str) -> bool {
+    op == "&&" || op == "||"
+}
+
+/// Whether the left operand of the `&&` or `||` `op` decides its result by
+/// being `left`, so the right one isn't calculated.
+pub(crate) fn short_circuits(op: &str, left: bool) -> bool {
+    left == (op == "||")
+}
+
+/// Whether `op` is one of the comparisons, `==`, `!=`, `<`, `<=`, `>` and `>=`.
+pub(crate) fn is_comparison(op: &str) -> bool {
+    matches!(op, "==" | "!=" | "<" | "<=" | ">" | ">=")
+}
+
+pub(crate) fn factorial(n: f64) -> Result<f64, CalcError> {
+    if n < 0.0 || n.fract() != 0.0 {
+        return Err(CalcError::InvalidFactorial(n));
+    }
+    let mut result: f64 = 1.0;
+    let mut i = 2.0;
+    // anything above 170! overflows to infinity, no need to keep multiplying
+    while i <= n && result.is_finite() {
+        result *= i;
+        i += 1.0;
     }
+    Ok(result)
 }
 
 #[cfg(test)]
@@ -131,6 +1217,389 @@ mod tests {
         assert_eq!(compute_result(5.0, 5.0, "-").unwrap(), 0.0);
         assert_eq!(compute_result(5.0, 5.0, "*").unwrap(), 25.0);
         assert_eq!(compute_result(5.0, 5.0, "/").unwrap(), 1.0);
+        assert_eq!(compute_result(2.0, 3.0, "^").unwrap(), 8.0);
+        assert_eq!(compute_result(17.0, 5.0, "%").unwrap(), 2.0);
+        assert_eq!(compute_result(-17.0, 5.0, "%").unwrap(), -2.0);
+        assert_eq!(compute_result(17.0, -5.0, "%").unwrap(), 2.0);
         assert!(compute_result(5.0, 5.0, "o").is_err());
     }
+
+    #[test]
+    fn conversions() {
+        let queue: RPNQueue = "1 + 2".parse().unwrap();
+        assert_eq!(queue.tokens(), vec!["1", "2", "+"]);
+        assert!(RPNQueue::try_from("(1 + 2").is_err());
+
+        let tokens = |tokens: &[&str]| tokens.iter().map(|token| token.to_string()).collect::<Vec<String>>();
+        assert_eq!(RPNQueue::try_from(tokens(&["4", "x", "neg", "!", "^", "pi", "min(2)"])).unwrap().tokens(),
+                   vec!["4", "x", "neg", "!", "^", "pi", "min(2)"]);
+        assert_eq!(RPNQueue::try_from(tokens(&[])).err(), Some(CalcError::NotEnoughOperands));
+        assert_eq!(RPNQueue::try_from(tokens(&["1", "2", "3", "+"])).err(), Some(CalcError::TooManyOperands));
+        assert_eq!(RPNQueue::try_from(tokens(&["1", "max(2)"])).err(), Some(CalcError::NotEnoughOperands));
+        assert_eq!(RPNQueue::try_from(tokens(&["1", "2", "$"])).err(), Some(CalcError::InvalidRpnToken("$".to_string())));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let queue = RPNQueue::from_infix_string("max(x, 2) ^ 2").unwrap();
+        let json = serde_json::to_string(&queue).unwrap();
+        assert_eq!(json, r#"["x","2","max(2)","2","^"]"#);
+        assert_eq!(serde_json::from_str::<RPNQueue>(&json).unwrap(), queue);
+        assert!(serde_json::from_str::<RPNQueue>(r#"["1","+"]"#).is_err());
+    }
+
+    #[test]
+    fn iteration() {
+        let queue = RPNQueue::from_infix_string("2 * x").unwrap();
+        assert_eq!(queue.len(), 3);
+        assert!(!queue.is_empty());
+        assert_eq!(queue.get(1), Some("x"));
+        assert_eq!(queue.get(3), None);
+        assert_eq!(queue.iter().rev().collect::<Vec<&str>>(), vec!["*", "x", "2"]);
+        assert_eq!((&queue).into_iter().len(), 3);
+        assert_eq!(queue.into_iter().collect::<Vec<String>>(), vec!["2", "x", "*"]);
+    }
+
+    #[test]
+    fn queues_can_be_keys() {
+        let mut results = HashMap::new();
+        results.insert(RPNQueue::from_infix_string("1 + 2").unwrap(), 3.0);
+        assert_eq!(results.get(&"(1) + 2".parse().unwrap()), Some(&3.0));
+        assert_eq!(results.get(&"2 + 1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn from_rpn_string() {
+        let queue = RPNQueue::from_infix_string("-max(x, 2)! * (1 - y) ± 0.5").unwrap();
+        assert_eq!(RPNQueue::from_rpn_string(&queue.to_string()).unwrap(), queue);
+        assert_eq!(RPNQueue::from_rpn_string("  2\t3\n+ ").unwrap().tokens(), vec!["2", "3", "+"]);
+
+        assert_eq!(RPNQueue::from_rpn_string("").err(), Some(CalcError::NotEnoughOperands));
+        assert_eq!(RPNQueue::from_rpn_string("1 +").err(), Some(CalcError::NotEnoughOperands));
+        assert_eq!(RPNQueue::from_rpn_string("1 2 3 max(2)").err(), Some(CalcError::TooManyOperands));
+        assert_eq!(RPNQueue::from_rpn_string("1 2 plus").err(), Some(CalcError::TooManyOperands));
+        assert_eq!(RPNQueue::from_rpn_string("1 2 #").err(), Some(CalcError::InvalidRpnToken("#".to_string())));
+    }
+
+    #[test]
+    fn to_infix_round_trip() {
+        for input in &["3 ^ (-x * 2)", "(2 ^ -x) * 2", "-(-x)!", "1 - (2 + 3) - 4", "2 ^ 3 ^ -(4 - 1)",
+                       "(1 ± 2) ^ 2 ± 3", "max(-1, [x - 1, x + 1] * 2)", "(a % b) * c / (d * e)", "~(a | b) & c xor d"] {
+            let queue = RPNQueue::from_infix_string(input).unwrap();
+            let infix = queue.to_infix().unwrap();
+            assert_eq!(RPNQueue::from_infix_string(&infix).unwrap(), queue, "{} => {}", input, infix);
+        }
+        assert_eq!(RPNQueue::from_infix_string("((x))").unwrap().to_infix().unwrap(), "x");
+        assert_eq!(RPNQueue::from_vec(vec!["1".to_string(), "+".to_string()]).to_infix(), Err(CalcError::NotEnoughOperands));
+    }
+
+    #[test]
+    fn format_is_canonical() {
+        let cases = &[
+            ("((1))+((2))", "1 + 2"),
+            ("2*(3*4)", "2 * (3 * 4)"),
+            ("(2*3)*4", "2 * 3 * 4"),
+            ("1.50e1 % 0x10", "15 % 16"),
+            ("[1,2]  ±0.5", "[1, 2] ± 0.5"),
+        ];
+        for &(input, expected) in cases {
+            assert_eq!(format_expression(input).unwrap(), expected);
+            assert_eq!(format_expression(expected).unwrap(), expected);
+        }
+        assert!(format_expression("1 +* 2").is_err());
+    }
+
+    #[test]
+    fn display() {
+        let queue = RPNQueue::from_infix_string("2 ^ 3 ^ 2").unwrap();
+        assert_eq!(queue.to_string(), "2 3 2 ^ ^");
+
+        for input in &["(2 ^ 3) ^ 2", "8 - (3 - 2) + 1", "-2 ^ 2", "(-2) ^ 2", "--x", "2 ^ -1", "2 * -(1 + x)", "-x * 2 + 3",
+                       "-x! ^ -2 ± y",
+                       "(x - 1)! * [1, 2]", "(1 + 2) ± 0.5 * 2", "f(x + 1, y) / (2 * z)"] {
+            assert_eq!(format!("{:#}", RPNQueue::from_infix_string(input).unwrap()), *input);
+        }
+        assert_eq!(format!("{:#}", RPNQueue::from_infix_string("((1)) + (2 * 3)").unwrap()), "1 + 2 * 3");
+
+        // not a single expression, so left as it is
+        let queue = RPNQueue::from_vec(vec!["1".to_string(), "2".to_string()]);
+        assert_eq!(format!("{:#}", queue), "1 2");
+    }
+
+    #[test]
+    fn queues_are_shared_between_threads() {
+        let queue = std::sync::Arc::new(RPNQueue::from_infix_string("x ^ 2").unwrap());
+        let handles: Vec<_> = (1..=4).map(|x| {
+            let queue = queue.clone();
+            std::thread::spawn(move || {
+                let mut vars = HashMap::new();
+                vars.insert("x".to_string(), x as f64);
+                queue.calculate_with_vars(&vars).unwrap()
+            })
+        }).collect();
+        let results: Vec<f64> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+        assert_eq!(results, vec![1.0, 4.0, 9.0, 16.0]);
+    }
+
+    #[test]
+    fn power_is_right_associative() {
+        let queue = RPNQueue::from_infix_string("2 ^ 3 ^ 2").unwrap();
+        assert_eq!(queue.tokens(), vec!["2", "3", "2", "^", "^"]);
+        assert_eq!(queue.calculate().unwrap(), 512.0);
+
+        let queue = RPNQueue::from_infix_string("2 * 3 ^ 2").unwrap();
+        assert_eq!(queue.calculate().unwrap(), 18.0);
+    }
+
+    #[test]
+    fn chained_operators_group_by_associativity() {
+        let cases: &[(&str, &[&str], f64)] = &[
+            ("8 - 3 + 2", &["8", "3", "-", "2", "+"], 7.0),
+            ("8 + 3 - 2", &["8", "3", "+", "2", "-"], 9.0),
+            ("10 - 4 - 3", &["10", "4", "-", "3", "-"], 3.0),
+            ("8 / 4 * 2", &["8", "4", "/", "2", "*"], 4.0),
+            ("8 * 4 / 2", &["8", "4", "*", "2", "/"], 16.0),
+            ("64 / 4 / 2", &["64", "4", "/", "2", "/"], 8.0),
+            ("17 % 5 * 2", &["17", "5", "%", "2", "*"], 4.0),
+            ("9 / 3 % 2", &["9", "3", "/", "2", "%"], 1.0),
+            ("1 - 2 * 3 - 4", &["1", "2", "3", "*", "-", "4", "-"], -9.0),
+            ("2 ^ 3 ^ 2", &["2", "3", "2", "^", "^"], 512.0),
+            ("2 ^ 2 * 3", &["2", "2", "^", "3", "*"], 12.0),
+            ("--3", &["3", "neg", "neg"], 3.0),
+            ("-2 ^ 2", &["2", "2", "^", "neg"], -4.0),
+            ("2 ^ -1 - 1", &["2", "1", "neg", "^", "1", "-"], -0.5),
+            ("3 * 4 >= 10", &["3", "4", "*", "10", ">="], 1.0),
+            ("1 < 2 == 0", &["1", "2", "<", "0", "=="], 0.0),
+            ("2 != -2 + 4", &["2", "2", "neg", "4", "+", "!="], 0.0),
+            ("1 || 0 && 0", &["1", "0", "0", "&&", "||"], 1.0),
+            ("1 > 2 || 3 == 3 && !0", &["1", "2", ">", "3", "3", "==", "0", "not", "&&", "||"], 1.0),
+            ("!2 ^ 2 == 0", &["2", "2", "^", "not", "0", "=="], 1.0),
+            ("6 | 1 xor 3 & 5 + 2", &["6", "1", "3", "5", "2", "+", "&", "xor", "|"], 6.0),
+            ("5 & 1 == 1", &["5", "1", "&", "1", "=="], 1.0),
+            ("~1 + 1", &["1", "~", "1", "+"], -1.0),
+        ];
+        for &(input, rpn, result) in cases {
+            let queue = RPNQueue::from_infix_string(input).unwrap();
+            assert_eq!(queue.tokens(), rpn, "{}", input);
+            assert_eq!(queue.calculate().unwrap(), result, "{}", input);
+        }
+    }
+
+    #[test]
+    fn superscript_exponents() {
+        let queue = RPNQueue::from_infix_string("-3² + 2⁻¹").unwrap();
+        assert_eq!(queue.tokens(), vec!["3", "2", "^", "neg", "2", "-1", "^", "+"]);
+        assert_eq!(queue.calculate().unwrap(), -8.5);
+        assert_eq!(RPNQueue::from_infix_string("2³²").unwrap().calculate().unwrap(), 2f64.powi(32));
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        let queue = RPNQueue::from_infix_string("x >= 0 && (x - 1)! >= 1 || x == -1").unwrap();
+        let compiled = queue.compile(&["x"]).unwrap();
+        let mut vars = HashMap::new();
+        for (x, result) in [(-1.0, Ok(1.0)), (3.0, Ok(1.0)), (-2.0, Ok(0.0)), (0.0, Err(CalcError::InvalidFactorial(-1.0)))] {
+            vars.insert("x".to_string(), x);
+            assert_eq!(queue.calculate_with_vars(&vars), result, "{}", x);
+            assert_eq!(compiled.calculate(&[x]), result, "{}", x);
+        }
+        assert_eq!(RPNQueue::from_infix_string("0 && (-1)!").unwrap().calculate_as::<f32>(), Ok(0.0));
+    }
+
+    #[test]
+    fn bitwise_operators_take_integers() {
+        assert_eq!(RPNQueue::from_infix_string("0xF0 | 0x0F xor ~0").unwrap().calculate(), Ok(-16.0));
+        assert_eq!(RPNQueue::from_infix_string("1.5 & 1").unwrap().calculate(), Err(CalcError::NotAnInteger(1.5)));
+        assert_eq!(RPNQueue::from_infix_string("~2^63").unwrap().calculate(), Err(CalcError::NotAnInteger(2f64.powi(63))));
+        assert_eq!(RPNQueue::from_infix_string("~-(2^63)").unwrap().calculate(), Ok(i64::MAX as f64));
+        assert_eq!(RPNQueue::from_infix_string("3 & 6").unwrap().calculate_as::<f32>(), Ok(2.0));
+        assert!(matches!(RPNQueue::from_infix_string("1 ~ 2").err(), Some(CalcError::InvalidToken { .. })));
+    }
+
+    #[test]
+    fn unary_minus() {
+        let queue = RPNQueue::from_infix_string("-5 + 3").unwrap();
+        assert_eq!(queue.tokens(), vec!["5", "neg", "3", "+"]);
+        assert_eq!(queue.calculate().unwrap(), -2.0);
+
+        let queue = RPNQueue::from_infix_string("2 * (-4)").unwrap();
+        assert_eq!(queue.calculate().unwrap(), -8.0);
+
+        let queue = RPNQueue::from_infix_string("-2 ^ 2").unwrap();
+        assert_eq!(queue.calculate().unwrap(), -4.0);
+
+        let queue = RPNQueue::from_infix_string("2 ^ -1 - -1").unwrap();
+        assert_eq!(queue.calculate().unwrap(), 1.5);
+    }
+
+    #[test]
+    fn modulo_shares_multiplicative_precedence() {
+        let queue = RPNQueue::from_infix_string("1 + 17 % 5 * 2").unwrap();
+        assert_eq!(queue.calculate().unwrap(), 5.0);
+
+        let queue = RPNQueue::from_infix_string("-17 % 5").unwrap();
+        assert_eq!(queue.calculate().unwrap(), -2.0);
+    }
+
+    #[test]
+    fn factorial_is_postfix() {
+        let queue = RPNQueue::from_infix_string("5!").unwrap();
+        assert_eq!(queue.tokens(), vec!["5", "!"]);
+        assert_eq!(queue.calculate().unwrap(), 120.0);
+
+        let queue = RPNQueue::from_infix_string("(3+2)! - 2 ^ 3!").unwrap();
+        assert_eq!(queue.calculate().unwrap(), 56.0);
+
+        let queue = RPNQueue::from_infix_string("-3!").unwrap();
+        assert_eq!(queue.calculate().unwrap(), -6.0);
+
+        // in front of its operand it is a logical not
+        assert_eq!(RPNQueue::from_infix_string("!3!").unwrap().tokens(), vec!["3", "!", "not"]);
+        assert!(RPNQueue::from_infix_string("(0 - 3)!").unwrap().calculate().is_err());
+        assert!(RPNQueue::from_infix_string("2.5!").unwrap().calculate().is_err());
+    }
+
+    #[test]
+    fn function_calls() {
+        let queue = RPNQueue::from_infix_string("sqrt(16) + abs(-2)").unwrap();
+        assert_eq!(queue.tokens(), vec!["16", "sqrt(1)", "2", "neg", "abs(1)", "+"]);
+        assert_eq!(queue.calculate().unwrap(), 6.0);
+
+        let queue = RPNQueue::from_infix_string("2 * log(1000 / 10) + ln(exp(0))").unwrap();
+        assert_eq!(queue.calculate().unwrap(), 4.0);
+
+        let queue = RPNQueue::from_infix_string("-cos(0) ^ 2").unwrap();
+        assert_eq!(queue.calculate().unwrap(), -1.0);
+
+        assert!(RPNQueue::from_infix_string("foo(1)").unwrap().calculate().is_err());
+        assert!(RPNQueue::from_infix_string("sqrt 4").is_err());
+        assert_eq!(RPNQueue::from_infix_string("sqrt (4)").unwrap().calculate().unwrap(), 2.0);
+    }
+
+    #[test]
+    fn all_errors_are_collected() {
+        let errors = RPNQueue::from_infix_string_all_errors("1 $ 2 + max(1, ) # sqrt").err().unwrap();
+        assert_eq!(errors, vec![
+            CalcError::InvalidToken { token: "$".to_string(), span: Span::new(2, 3) },
+            CalcError::MissingOperator(Span::new(4, 5)),
+            CalcError::MissingArgument(Span::new(15, 16)),
+            CalcError::InvalidToken { token: "#".to_string(), span: Span::new(17, 18) },
+            CalcError::MissingOperator(Span::new(19, 23)),
+            CalcError::MissingBrackets { name: "sqrt".to_string(), span: Span::new(19, 23) },
+        ]);
+
+        let queue = RPNQueue::from_infix_string_all_errors("1 + 2").ok().unwrap();
+        assert_eq!(queue.calculate().unwrap(), 3.0);
+
+        // the first error is the one from_infix_string reports
+        assert_eq!(RPNQueue::from_infix_string("1 $ 2 +").err().unwrap(), CalcError::InvalidToken {
+            token: "$".to_string(),
+            span: Span::new(2, 3),
+        });
+    }
+
+    #[test]
+    fn literals_keep_their_digits() {
+        let queue = RPNQueue::from_infix_string("9007199254740993 + 0x20_0000_0000_0001 * 1.50 - 1e3").unwrap();
+        assert_eq!(queue.tokens(), ["9007199254740993", "9007199254740993", "1.5", "*", "+", "1000", "-"]);
+        // which is 2^53 as an f64
+        let nearest = 2f64.powi(53);
+        assert_eq!(queue.calculate(), Ok(nearest + nearest * 1.5 - 1000.0));
+        assert_eq!(RPNQueue::from_bytes(&queue.to_bytes()), Ok(queue));
+    }
+
+    #[test]
+    fn missing_operands_are_parse_errors() {
+        for &(input, span) in &[("1 +", Span::new(2, 3)), ("* 2", Span::new(0, 1)), ("1 * * 2", Span::new(4, 5)),
+                                ("+", Span::new(0, 1)), ("()", Span::new(1, 2)), ("2 ^ -", Span::new(4, 5)), ("2 * (3 -)", Span::new(8, 9))] {
+            assert_eq!(RPNQueue::from_infix_string(input).err(), Some(CalcError::MissingOperand(span)), "{}", input);
+            assert_eq!(Expr::parse(input).err(), Some(CalcError::MissingOperand(span)), "{}", input);
+        }
+        assert_eq!(RPNQueue::from_infix_string("3! + -2").unwrap().calculate(), Ok(4.0));
+        assert_eq!(RPNQueue::from_infix_string("f()").unwrap().tokens(), ["f(0)"]);
+    }
+
+    #[test]
+    fn unbalanced_parentheses() {
+        assert_eq!(RPNQueue::from_infix_string("(1 + 2").err().unwrap(), CalcError::UnbalancedParen { position: 0 });
+        assert_eq!(RPNQueue::from_infix_string("1 + 2)").err().unwrap(), CalcError::UnbalancedParen { position: 5 });
+        assert_eq!(RPNQueue::from_infix_string("max(1, (2)").err().unwrap(), CalcError::UnbalancedParen { position: 3 });
+
+        let errors = RPNQueue::from_infix_string_all_errors("((1) + 2)) * (3").err().unwrap();
+        assert_eq!(errors, vec![
+            CalcError::UnbalancedParen { position: 9 },
+            CalcError::UnbalancedParen { position: 13 },
+        ]);
+    }
+
+    #[test]
+    fn intervals() {
+        let queue = RPNQueue::from_infix_string("[1, 1.1] * 3").unwrap();
+        assert_eq!(queue.tokens(), vec!["1", "1.1", "interval(2)", "3", "*"]);
+        let queue = RPNQueue::from_infix_string("[-x, max(x, 1)]").unwrap();
+        assert_eq!(queue.tokens(), vec!["x", "neg", "x", "1", "max(2)", "interval(2)"]);
+
+        assert_eq!(RPNQueue::from_infix_string("[1, 2, 3]").err().unwrap(),
+                   CalcError::WrongArgumentCount { function: "interval".to_string(), count: 3 });
+        assert_eq!(RPNQueue::from_infix_string("[]").err().unwrap(),
+                   CalcError::WrongArgumentCount { function: "interval".to_string(), count: 0 });
+        assert_eq!(RPNQueue::from_infix_string("[1, 2)").err().unwrap(), CalcError::UnbalancedParen { position: 5 });
+        assert_eq!(RPNQueue::from_infix_string("(1, 2]").err().unwrap(),
+                   CalcError::InvalidToken { token: ",".to_string(), span: Span::new(2, 3) });
+    }
+
+    #[test]
+    fn operands_need_an_operator_between_them() {
+        assert_eq!(RPNQueue::from_infix_string("2 3").err().unwrap(), CalcError::MissingOperator(Span::new(2, 3)));
+        assert_eq!(RPNQueue::from_infix_string("2x").err().unwrap(), CalcError::MissingOperator(Span::new(1, 2)));
+        assert!(RPNQueue::from_infix_string("(1) (2)").is_err());
+    }
+
+    #[test]
+    fn multi_argument_function_calls() {
+        let queue = RPNQueue::from_infix_string("max(1, 2 * 3, min(4, 5)) + pow(2, 10)").unwrap();
+        assert_eq!(queue.tokens(), vec!["1", "2", "3", "*", "4", "5", "min(2)", "max(3)", "2", "10", "pow(2)", "+"]);
+        assert_eq!(queue.calculate().unwrap(), 1030.0);
+
+        assert!(RPNQueue::from_infix_string("pow(2)").is_err());
+        assert!(RPNQueue::from_infix_string("sqrt(1, 2)").is_err());
+        assert!(RPNQueue::from_infix_string("max(1, )").is_err());
+        assert!(RPNQueue::from_infix_string("(1, 2)").is_err());
+        assert!(RPNQueue::from_infix_string("-(1, 2)").is_err());
+        assert!(RPNQueue::from_vec(vec!["1".to_string(), "max(2)".to_string()]).calculate().is_err());
+    }
+
+    #[test]
+    fn constants() {
+        let queue = RPNQueue::from_infix_string("2 * pi * r").unwrap();
+        assert_eq!(queue.tokens(), vec!["2", "pi", "*", "r", "*"]);
+        assert!(queue.calculate().is_err());
+
+        let mut constants = HashMap::new();
+        constants.insert("r".to_string(), 0.5);
+        assert_eq!(queue.calculate_with_constants(&constants).unwrap(), std::f64::consts::PI);
+
+        // user supplied values take priority over the built-in ones
+        constants.insert("e".to_string(), 3.0);
+        let queue = RPNQueue::from_infix_string("e + tau / pi").unwrap();
+        assert_eq!(queue.calculate_with_constants(&constants).unwrap(), 5.0);
+
+        let queue = RPNQueue::from_infix_string("2 × π × r").unwrap();
+        assert_eq!(queue.calculate_with_constants(&constants).unwrap(), std::f64::consts::PI);
+        assert_eq!(RPNQueue::from_infix_string("τ / π").unwrap().calculate().unwrap(), 2.0);
+    }
+
+    #[test]
+    fn calculate_with_vars() {
+        let queue = RPNQueue::from_infix_string("sqrt(x) + y * x - pi").unwrap();
+        let mut vars = HashMap::new();
+        assert_eq!(queue.calculate_with_vars(&vars).unwrap_err(),
+                   CalcError::MissingVariables(vec!["x".to_string(), "y".to_string()]));
+        for &(x, y, expected) in &[(4.0, 1.0, 6.0), (9.0, 0.5, 7.5)] {
+            vars.insert("x".to_string(), x);
+            vars.insert("y".to_string(), y);
+            assert_eq!(queue.calculate_with_vars(&vars).unwrap(), expected - std::f64::consts::PI);
+        }
+    }
 }
\ No newline at end of file
@@ -1,30 +1,799 @@
 extern crate infix_calculator;
+#[cfg(feature = "readline")]
+extern crate rustyline;
+#[cfg(feature = "session")]
+extern crate serde;
+#[cfg(feature = "session")]
+extern crate serde_json;
 
-use std::io::{self, Write};
-use infix_calculator::RPNQueue;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+use infix_calculator::{
+    split_assignment, CalcError, Calculator, Environment, Equation, Expr, RPNQueue, Target, Value,
+};
+#[cfg(feature = "readline")]
+use rustyline::error::ReadlineError;
+#[cfg(feature = "readline")]
+use rustyline::history::FileHistory;
+#[cfg(feature = "readline")]
+use rustyline::{ColorMode, CompletionType, Editor};
 
-fn main() {
-    let mut buffer = String::new();
-    loop {
-        buffer.clear();
-        print!("> ");
-        io::stdout().flush().unwrap();
+mod commands;
+mod config;
+#[cfg(feature = "readline")]
+mod editor;
 
-        io::stdin().read_line(&mut buffer).unwrap();
+use commands::Flow;
+use config::Config;
 
-        let rpn = RPNQueue::from_infix_string(&buffer);
-        if rpn.is_err() {
-            println!("{}", rpn.err().unwrap());
+#[cfg(feature = "readline")]
+use editor::ReplHelper;
+
+const PROMPT: &str = "> ";
+/// The prompt for the lines of an expression after its first one.
+const CONTINUATION_PROMPT: &str = "... ";
+
+/// The exit status for a file that can't be read or written.
+const FAILURE: i32 = 1;
+/// The exit status when an expression can't be parsed.
+const PARSE_ERROR: i32 = 2;
+/// The exit status when an expression parses but can't be calculated.
+const EVALUATION_ERROR: i32 = 3;
+/// The exit status for a command line that can't be used, such as an option
+/// without its value or one the binary doesn't know, `EX_USAGE` from sysexits.h.
+const USAGE_ERROR: i32 = 64;
+
+/// The options that pick what the binary does, which come before the rest of
+/// the arguments.
+const MODES: &[&str] = &["--dot", "--ast", "--rpn-only", "-e", "--eval", "--file"];
+
+/// What `--help` prints.
+const USAGE: &str = "Usage: infix-calculator [options] [--dot|--ast|--rpn-only|-e|--file] [expression|path]
+
+Without -e or --file, expressions are read line by line in a REPL.
+
+  --dot <expression>          print the expression tree for Graphviz
+  --ast <expression>          print the expression tree
+  --rpn-only <expression>     print the RPN without calculating it
+  -e, --eval <expression>     calculate an expression
+  --file <path>               calculate every line of a file
+  --format latex|mathml|sexpr convert expressions without calculating them
+  --output text|json          how -e and --file print results
+  --precision <places>        round results to a number of decimal places
+  --show-rpn                  print the RPN of each expression
+  --quiet                     print just the results
+  --history <path>            where the REPL keeps its history
+  --config <path>             the configuration file to read
+  --help                      print this";
+
+/// How `-e` and `--file` print each expression, picked with `--output`.
+#[derive(Clone, Copy, PartialEq)]
+enum Output {
+    /// `expression = result`, or just the result for `-e`
+    Text,
+    /// one JSON object per expression, see `to_json`
+    Json,
+}
+
+/// How numbers are written, picked with `:format`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "session", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "session", serde(rename_all = "snake_case"))]
+enum Notation {
+    /// `12345.6`
+    Plain,
+    /// `1.23456e4`
+    Scientific,
+    /// `12.3456e3`, the exponent a multiple of 3
+    Engineering,
+}
+
+/// What the REPL keeps from one line to the next, for the commands to change.
+pub struct Session {
+    calculator: Calculator,
+    environment: Environment,
+    options: Options,
+    /// whether the input is a terminal, otherwise errors go to stderr
+    interactive: bool,
+    /// the exit status, from the errors when the input isn't a terminal
+    status: i32,
+}
+
+/// The command line options for how results are printed.
+#[derive(Clone, Copy)]
+struct Options {
+    output: Output,
+    /// the decimal places of results, from `--precision` or `:precision`
+    precision: Option<usize>,
+    /// how integer and float results are written, from `:format`
+    notation: Notation,
+    /// whether the REPL prints the RPN of each expression, from `--show-rpn`
+    /// or `:rpn on`
+    show_rpn: bool,
+    /// whether errors, and the line being typed, are coloured on a terminal,
+    /// from `color` in the configuration
+    colour: bool,
+    /// whether the REPL prints how long parsing and calculating each
+    /// expression took, from `:timing on`
+    timing: bool,
+    /// whether the REPL prints just the results, without `[1] =` or
+    /// `Defined:`, from `--quiet` or when the input isn't a terminal
+    quiet: bool,
+}
+
+impl Options {
+    /// `value` in `notation` with `precision` decimal places, if there is
+    /// one. Intervals and the like are always plain.
+    fn show(&self, value: &Value) -> String {
+        let number = match *value {
+            Value::Integer(n) => Some(n as f64),
+            Value::Float(n) => Some(n),
+            _ => None,
+        };
+        match (self.notation, number, self.precision) {
+            (Notation::Scientific, Some(n), Some(precision)) => format!("{:.*e}", precision, n),
+            (Notation::Scientific, Some(n), None) => format!("{:e}", n),
+            (Notation::Engineering, Some(n), precision) => engineering(n, precision),
+            (_, _, Some(precision)) => format!("{:.*}", precision, value),
+            (_, _, None) => value.to_string(),
+        }
+    }
+}
+
+/// `n` in engineering notation, a mantissa from 1 to 1000 (exclusive) with
+/// `precision` decimal places and an exponent that is a multiple of 3.
+/// It moves the point of the scientific notation, so the digits are the
+/// same as `{:e}` gives.
+fn engineering(n: f64, precision: Option<usize>) -> String {
+    if !n.is_finite() {
+        return n.to_string();
+    }
+    let exponent_of = |text: &str| text[text.find('e').unwrap() + 1..].parse::<i32>().unwrap();
+    // the digits moved before the point need as many more places
+    let scientific = |exponent: i32| match precision {
+        Some(precision) => format!("{:.*e}", precision + exponent.rem_euclid(3) as usize, n),
+        None => format!("{:e}", n),
+    };
+    let mut exponent = exponent_of(&format!("{:e}", n));
+    let mut text = scientific(exponent);
+    // rounding can carry over to the next power of 10, e.g. 999.96 with 1 place
+    if exponent_of(&text) != exponent {
+        exponent = exponent_of(&text);
+        text = scientific(exponent);
+    }
+    let shift = exponent.rem_euclid(3) as usize;
+    let mantissa = &text[..text.find('e').unwrap()];
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+    let mut digits = mantissa.replace('.', "");
+    while digits.len() <= shift {
+        digits.push('0');
+    }
+    let (whole, fraction) = digits.split_at(shift + 1);
+    let point = if fraction.is_empty() { "" } else { "." };
+    format!("{}{}{}{}e{}", sign, whole, point, fraction, exponent - shift as i32)
+}
+
+/// `text` as a JSON string, with quotes.
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// What calculating `input` gave, as a line of JSON: either
+/// `{"input": "1+2", "rpn": ["1", "2", "+"], "result": 3}` or, e.g.
+/// `{"input": "1+x", "rpn": ["1", "x", "+"], "error": {"kind": "evaluate",
+/// "message": "Unknown variable: x"}}`. Results that aren't a finite real
+/// number, such as intervals, are strings. Parse errors have no `rpn`, and
+/// a `span` if they know where in the input they are.
+fn to_json(input: &str, rpn: Option<&RPNQueue>, result: &Result<Value, CalcError>, options: &Options) -> String {
+    let mut json = format!("{{\"input\": {}", json_string(input));
+    if let Some(rpn) = rpn {
+        let tokens: Vec<String> = rpn.tokens().iter().map(|token| json_string(token)).collect();
+        json.push_str(&format!(", \"rpn\": [{}]", tokens.join(", ")));
+    }
+    match *result {
+        Ok(ref value @ Value::Integer(_)) => json.push_str(&format!(", \"result\": {}", value)),
+        Ok(ref value @ Value::Float(n)) if n.is_finite() => {
+            json.push_str(&format!(", \"result\": {}", options.show(value)))
+        }
+        Ok(ref value) => json.push_str(&format!(", \"result\": {}", json_string(&options.show(value)))),
+        Err(ref err) => {
+            let kind = if rpn.is_some() { "evaluate" } else { "parse" };
+            json.push_str(&format!(", \"error\": {{\"kind\": \"{}\", \"message\": {}", kind, json_string(&err.to_string())));
+            if let Some(span) = err.span() {
+                json.push_str(&format!(", \"span\": [{}, {}]", span.start, span.end));
+            }
+            json.push('}');
+        }
+    }
+    json.push('}');
+    json
+}
+
+/// `expr` as JSON, with an object for each node, e.g.
+/// `{"binary": "+", "left": {"number": 1}, "right": {"variable": "x"}}`.
+/// Unary operators have an `operand` and calls a list of `arguments`.
+fn ast_json(expr: &Expr) -> String {
+    match *expr {
+        Expr::Number(number) if number.is_finite() => format!("{{\"number\": {}}}", number),
+        Expr::Number(number) => format!("{{\"number\": {}}}", json_string(&number.to_string())),
+        Expr::Variable(ref name) => format!("{{\"variable\": {}}}", json_string(name)),
+        Expr::Unary(op, ref operand) => {
+            format!("{{\"unary\": {}, \"operand\": {}}}", json_string(op.written()), ast_json(operand))
+        }
+        Expr::Binary(op, ref left, ref right) => format!(
+            "{{\"binary\": {}, \"left\": {}, \"right\": {}}}",
+            json_string(op.symbol()),
+            ast_json(left),
+            ast_json(right)
+        ),
+        Expr::Call(ref name, ref arguments) => {
+            let arguments: Vec<String> = arguments.iter().map(ast_json).collect();
+            format!("{{\"call\": {}, \"arguments\": [{}]}}", json_string(name), arguments.join(", "))
+        }
+    }
+}
+
+/// Calculates `expression`, printing it in JSON with `Output::Json`.
+/// Prints nothing for `Output::Text`, leaving that to the caller. Errors
+/// come with the exit status for them.
+fn calculate(calculator: &Calculator, expression: &str, options: &Options) -> Result<Value, (i32, CalcError)> {
+    let rpn = calculator.parse(expression);
+    let result = rpn.as_ref().map_err(Clone::clone).and_then(|rpn| calculator.evaluate(rpn, &Environment::new()));
+    if options.output == Output::Json {
+        println!("{}", to_json(expression, rpn.as_ref().ok(), &result, options));
+    }
+    let status = if rpn.is_ok() { EVALUATION_ERROR } else { PARSE_ERROR };
+    result.map_err(|err| (status, err))
+}
+
+/// `err` as `Error: ...`, after `line` with a caret line like `^~~` under
+/// the part the error is about, if it knows. `expression` is the part of
+/// `line` that was parsed. The caret and `Error` are red with `colour`.
+fn describe_error(line: &str, expression: &str, err: &CalcError, colour: bool) -> String {
+    let (red, reset) = if colour { ("\x1b[1;31m", "\x1b[0m") } else { ("", "") };
+    let mut description = String::new();
+    if let Some(span) = err.span() {
+        let offset = line[..line.len() - expression.len()].chars().count() + span.start;
+        let marker = format!("^{}", "~".repeat(span.end.saturating_sub(span.start + 1)));
+        description.push_str(&format!("{}\n{}{}{}{}\n", line.trim_end(), " ".repeat(offset), red, marker, reset));
+    }
+    description.push_str(&format!("{}Error{}: {}", red, reset, err));
+    description
+}
+
+/// Prints the tree of `expression` as a Graphviz graph, for `--dot`.
+fn print_dot(expression: &str) {
+    match Expr::parse(expression) {
+        Ok(expr) => print!("{}", expr.to_dot()),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(PARSE_ERROR);
+        }
+    }
+}
+
+/// Prints the tree of `expression` without calculating it, for `--ast`.
+fn print_ast(expression: &str, options: &Options) {
+    match Expr::parse(expression) {
+        Ok(expr) if options.output == Output::Json => println!("{}", ast_json(&expr)),
+        Ok(expr) => print!("{}", expr.to_tree()),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(PARSE_ERROR);
+        }
+    }
+}
+
+/// Prints the RPN of `expression` without calculating it, for
+/// `--rpn-only`. In JSON it is a list of tokens.
+fn print_rpn(calculator: &Calculator, expression: &str, options: &Options) {
+    match calculator.parse(expression) {
+        Ok(rpn) if options.output == Output::Json => {
+            let tokens: Vec<String> = rpn.tokens().iter().map(|token| json_string(token)).collect();
+            println!("[{}]", tokens.join(", "));
+        }
+        Ok(rpn) => println!("{}", rpn),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(PARSE_ERROR);
+        }
+    }
+}
+
+/// Prints `expression` converted by `format` without calculating it, for
+/// `--format`. Without an expression every line of stdin is converted, and
+/// the status is `PARSE_ERROR` if any of them can't be parsed.
+fn convert(expression: &str, format: fn(&Expr) -> String) -> ! {
+    let mut status = 0;
+    let mut convert_line = |line: &str| match Expr::parse(line) {
+        Ok(expr) => println!("{}", format(&expr)),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            status = PARSE_ERROR;
+        }
+    };
+    if !expression.trim().is_empty() {
+        convert_line(expression);
+    } else {
+        for line in io::stdin().lock().lines() {
+            let line = line.unwrap_or_else(|err| {
+                eprintln!("Cannot read input: {}", err);
+                std::process::exit(FAILURE);
+            });
+            if !line.trim().is_empty() {
+                convert_line(&line);
+            }
+        }
+    }
+    std::process::exit(status);
+}
+
+/// Prints the result of `expression` and exits, for `-e`. The status is
+/// `PARSE_ERROR` or `EVALUATION_ERROR` if it can't be calculated.
+fn evaluate_once(calculator: &Calculator, expression: &str, options: &Options) -> ! {
+    match calculate(calculator, expression, options) {
+        Ok(result) => {
+            if options.output == Output::Text {
+                println!("{}", options.show(&result));
+            }
+            std::process::exit(0);
+        }
+        Err((status, err)) => {
+            if options.output == Output::Text {
+                eprintln!("{}", describe_error(expression, expression, &err, options.colour && io::stderr().is_terminal()));
+            }
+            std::process::exit(status);
+        }
+    }
+}
+
+/// Prints every line of the file at `path` with its result, then how many
+/// failed, for `--file`. Errors go to stderr. The status is
+/// `EVALUATION_ERROR` if any line couldn't be calculated, or else
+/// `PARSE_ERROR` if any couldn't be parsed. In JSON there is just a line
+/// for each expression.
+fn evaluate_file(calculator: &Calculator, path: &str, options: &Options) -> ! {
+    let file = File::open(path).unwrap_or_else(|err| {
+        eprintln!("Cannot open {}: {}", path, err);
+        std::process::exit(FAILURE);
+    });
+    let (mut count, mut failed, mut status) = (0, 0, 0);
+    for line in BufReader::new(file).lines() {
+        let line = line.unwrap_or_else(|err| {
+            eprintln!("Cannot read {}: {}", path, err);
+            std::process::exit(FAILURE);
+        });
+        let expression = line.trim();
+        if expression.is_empty() {
             continue;
         }
-        let mut rpn: RPNQueue = rpn.unwrap();
-        println!("RPN Notation: {}", rpn.0.join(", "));
+        count += 1;
+        match calculate(calculator, expression, options) {
+            Ok(result) if options.output == Output::Text => println!("{} = {}", expression, options.show(&result)),
+            Ok(_) => {}
+            Err((error_status, err)) => {
+                failed += 1;
+                status = std::cmp::max(status, error_status);
+                if options.output == Output::Text {
+                    eprintln!("{} = Error: {}", expression, err);
+                }
+            }
+        }
+    }
+    if options.output == Output::Text {
+        println!("{} of {} failed", failed, count);
+    }
+    std::process::exit(status);
+}
+
+/// Takes `--name value` out of `arguments`, giving the value if it was
+/// there. Exits with `usage` if the value is missing or `parse` rejects it.
+fn take_option<T, F>(arguments: &mut Vec<String>, name: &str, usage: &str, parse: F) -> Option<T>
+where
+    F: FnOnce(&str) -> Option<T>,
+{
+    let index = arguments.iter().position(|argument| argument == name)?;
+    match arguments.get(index + 1).and_then(|value| parse(value)) {
+        Some(value) => {
+            arguments.drain(index..index + 2);
+            Some(value)
+        }
+        None => {
+            eprintln!("Usage: {}", usage);
+            std::process::exit(USAGE_ERROR);
+        }
+    }
+}
+
+/// The first of `arguments` that looks like an option, `--` and a letter,
+/// once the known options have been taken out of them. A mode is only
+/// known as the first argument, and not at all with `--format`.
+fn unknown_option(arguments: &[String], modes: bool) -> Option<&str> {
+    let rest = match arguments.first() {
+        Some(first) if modes && MODES.contains(&first.as_str()) => &arguments[1..],
+        _ => arguments,
+    };
+    rest.iter().map(String::as_str).find(|argument| {
+        argument.starts_with("--") && argument[2..].starts_with(|c: char| c.is_ascii_alphabetic())
+    })
+}
+
+/// Takes `flag` out of `arguments`, giving whether it was there.
+fn take_flag(arguments: &mut Vec<String>, flag: &str) -> bool {
+    let count = arguments.len();
+    arguments.retain(|argument| argument != flag);
+    arguments.len() != count
+}
+
+/// Where the history of the REPL is kept without `--history`,
+/// `~/.infix_calculator_history`.
+fn default_history() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(".infix_calculator_history"))
+}
+
+/// Where the REPL reads its lines from.
+enum Input {
+    /// A terminal, with line editing and the lines entered so far, in this
+    /// and earlier sessions, a press of the up arrow away. The history is
+    /// loaded from and saved to the file, if there is one.
+    #[cfg(feature = "readline")]
+    Editor(Box<Editor<ReplHelper, FileHistory>>, Option<PathBuf>),
+    /// One line at a time from stdin, printing the prompt first if `prompt`.
+    Plain { prompt: bool },
+}
+
+impl Input {
+    /// The history keeps `config.history_size` lines, and the line is only
+    /// coloured if `config.colour`.
+    fn new(interactive: bool, history: Option<PathBuf>, config: &Config) -> Input {
+        #[cfg(feature = "readline")]
+        {
+            if interactive {
+                let mut builder = rustyline::Config::builder().completion_type(CompletionType::List);
+                if let Some(size) = config.history_size {
+                    if let Ok(sized) = builder.clone().max_history_size(size) {
+                        builder = sized;
+                    }
+                }
+                if !config.colour {
+                    builder = builder.color_mode(ColorMode::Disabled);
+                }
+                if let Ok(mut editor) = Editor::with_config(builder.build()) {
+                    editor.set_helper(Some(ReplHelper::default()));
+                    if let Some(ref path) = history {
+                        // there is no file before the first session
+                        let _ = editor.load_history(path);
+                    }
+                    return Input::Editor(Box::new(editor), history);
+                }
+            }
+        }
+        let _ = (history, config);
+        Input::Plain { prompt: interactive }
+    }
+
+    /// Has Tab complete the functions, variables and constants of
+    /// `calculator` and `environment`.
+    fn set_names(&mut self, calculator: &Calculator, environment: &Environment) {
+        #[cfg(feature = "readline")]
+        {
+            if let Input::Editor(ref mut editor, _) = *self {
+                let mut functions: Vec<String> = calculator.function_names().into_iter().map(String::from).collect();
+                functions.extend(environment.functions().keys().cloned());
+                let mut variables: Vec<String> = calculator.constant_names().into_iter().map(String::from).collect();
+                variables.extend(environment.variables().keys().chain(environment.values().keys()).cloned());
+                variables.push("ans".to_string());
+                if let Some(helper) = editor.helper_mut() {
+                    helper.set_names(functions, variables);
+                }
+            }
+        }
+        let _ = (calculator, environment);
+    }
+
+    /// Writes the history to its file, for the next session.
+    fn save_history(&mut self) {
+        #[cfg(feature = "readline")]
+        {
+            if let Input::Editor(ref mut editor, Some(ref path)) = *self {
+                if let Err(err) = editor.save_history(path) {
+                    eprintln!("Cannot save history to {}: {}", path.display(), err);
+                }
+            }
+        }
+    }
+
+    /// The next command or expression, or `None` once the input has ended,
+    /// e.g. with Ctrl-D. An expression that isn't complete yet, see
+    /// `Calculator::is_complete`, goes on over the lines after it, which are
+    /// joined with spaces. Ctrl-C starts it over.
+    fn read_entry(&mut self, calculator: &Calculator) -> Option<String> {
+        'entry: loop {
+            let mut entry = match self.read_line(PROMPT) {
+                Line::Read(line) => line,
+                Line::Interrupted => continue,
+                Line::Ended => return None,
+            };
+            if !entry.trim_start().starts_with(':') {
+                while !entry.trim().is_empty() && !calculator.is_complete(&entry) {
+                    match self.read_line(CONTINUATION_PROMPT) {
+                        Line::Read(line) => {
+                            entry.push(' ');
+                            entry.push_str(&line);
+                        }
+                        Line::Interrupted => continue 'entry,
+                        // the part there is gives the error
+                        Line::Ended => break,
+                    }
+                }
+            }
+            #[cfg(feature = "readline")]
+            {
+                if let Input::Editor(ref mut editor, _) = *self {
+                    if !entry.trim().is_empty() {
+                        let _ = editor.add_history_entry(entry.as_str());
+                    }
+                }
+            }
+            return Some(entry);
+        }
+    }
+
+    /// The next line, without its line break.
+    fn read_line(&mut self, prompt: &str) -> Line {
+        match *self {
+            #[cfg(feature = "readline")]
+            Input::Editor(ref mut editor, _) => match editor.readline(prompt) {
+                Ok(line) => Line::Read(line),
+                Err(ReadlineError::Interrupted) => Line::Interrupted,
+                Err(ReadlineError::Eof) => Line::Ended,
+                Err(err) => {
+                    eprintln!("Cannot read input: {}", err);
+                    Line::Ended
+                }
+            },
+            Input::Plain { prompt: show_prompt } => {
+                if show_prompt {
+                    print!("{}", prompt);
+                    io::stdout().flush().unwrap();
+                }
+                let mut line = String::new();
+                match io::stdin().read_line(&mut line) {
+                    Ok(0) => Line::Ended,
+                    Ok(_) => Line::Read(line.trim_end_matches(&['\r', '\n'][..]).to_string()),
+                    Err(err) => {
+                        eprintln!("Cannot read input: {}", err);
+                        Line::Ended
+                    }
+                }
+            }
+        }
+    }
+}
 
-        let result = rpn.calculate();
+/// What `Input::read_line` read.
+enum Line {
+    Read(String),
+    /// Ctrl-C was pressed, only in the line editor
+    #[cfg_attr(not(feature = "readline"), allow(dead_code))]
+    Interrupted,
+    /// Ctrl-D was pressed or the input has ended
+    Ended,
+}
+
+impl Session {
+    /// Prints `err` in the REPL like `describe_error`, or to stderr when the
+    /// input isn't a terminal. Then `status` becomes `error_status`, unless it
+    /// is already a worse one.
+    fn print_error(&mut self, line: &str, expression: &str, err: &CalcError, error_status: i32) {
+        if self.interactive {
+            println!("{}", describe_error(line, expression, err, self.options.colour && io::stdout().is_terminal()));
+        } else {
+            eprintln!("{}", describe_error(line, expression, err, self.options.colour && io::stderr().is_terminal()));
+            self.status = std::cmp::max(self.status, error_status);
+        }
+    }
+
+    /// Prints `message` for something other than an expression going wrong,
+    /// like `print_error`.
+    fn fail(&mut self, message: &str) {
+        if self.interactive {
+            println!("{}", message);
+        } else {
+            eprintln!("{}", message);
+            self.status = std::cmp::max(self.status, FAILURE);
+        }
+    }
+
+    /// Prints `result` with the number `$n` refers to it by from then on.
+    fn print_result(&mut self, result: &Value) {
+        let number = self.environment.push_result(result.clone());
+        if self.options.quiet {
+            println!("{}", self.options.show(result));
+        } else {
+            println!("[{}] = {}", number, self.options.show(result));
+        }
+    }
+
+    /// Runs a command, or calculates an expression and prints its result, or
+    /// defines a variable or function, or solves an equation.
+    fn run(&mut self, buffer: &str) -> Flow {
+        if buffer.trim().is_empty() {
+            return Flow::Continue;
+        }
+        if let Some(command) = buffer.trim().strip_prefix(':') {
+            return commands::run(self, command);
+        }
+
+        let (target, expression) = match split_assignment(buffer) {
+            Ok(split) => split,
+            // not something that can be assigned to, so an equation to solve
+            Err(CalcError::InvalidAssignment(_)) => {
+                match Equation::parse(buffer) {
+                    Ok(equation) => match equation.solve_with(&self.environment) {
+                        Ok(solution) => println!("{}", solution),
+                        Err(err) => self.print_error(buffer, buffer, &err, EVALUATION_ERROR),
+                    },
+                    Err(err) => self.print_error(buffer, buffer, &err, PARSE_ERROR),
+                }
+                return Flow::Continue;
+            }
+            Err(err) => {
+                self.print_error(buffer, buffer, &err, PARSE_ERROR);
+                return Flow::Continue;
+            }
+        };
+
+        let started = Instant::now();
+        let rpn = self.calculator.parse(expression);
+        let parsing = started.elapsed();
+        if rpn.is_err() {
+            self.print_error(buffer, expression, &rpn.err().unwrap(), PARSE_ERROR);
+            return Flow::Continue;
+        }
+        let rpn: RPNQueue = rpn.unwrap();
+        if self.options.show_rpn {
+            println!("RPN Notation: {}", rpn);
+        }
+
+        if let Some(Target::Function(name, parameters)) = target {
+            let parameters = parameters.into_iter().map(String::from).collect();
+            match self.environment.define_with(name, parameters, rpn, &self.calculator) {
+                Ok(()) if !self.options.quiet => println!("Defined: {}", name),
+                Ok(()) => {}
+                Err(err) => self.print_error(buffer, expression, &err, EVALUATION_ERROR),
+            }
+            return Flow::Continue;
+        }
+
+        let started = Instant::now();
+        let result = self.calculator.evaluate(&rpn, &self.environment);
+        let calculating = started.elapsed();
         if result.is_err() {
-            println!("Error: {}", result.err().unwrap());
-            continue
+            self.print_error(buffer, expression, &result.err().unwrap(), EVALUATION_ERROR);
+            return Flow::Continue;
+        }
+        let result = result.unwrap();
+        if let Some(Target::Variable(name)) = target {
+            self.environment.set_value(name, result.clone());
+        }
+        self.print_result(&result);
+        if self.options.timing {
+            println!("Parsed in {:?}, calculated in {:?}", parsing, calculating);
+        }
+        Flow::Continue
+    }
+}
+
+fn main() {
+    let mut arguments: Vec<String> = std::env::args().skip(1).collect();
+    if take_flag(&mut arguments, "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    let output = take_option(&mut arguments, "--output", "--output text|json", |value| match value {
+        "text" => Some(Output::Text),
+        "json" => Some(Output::Json),
+        _ => None,
+    });
+    let precision = take_option(&mut arguments, "--precision", "--precision <decimal places>", |value| {
+        value.parse().ok()
+    });
+    let format = take_option(&mut arguments, "--format", "--format latex|mathml|sexpr", |value| match value {
+        "latex" => Some(Expr::to_latex as fn(&Expr) -> String),
+        "mathml" => Some(Expr::to_mathml as fn(&Expr) -> String),
+        "sexpr" => Some(Expr::to_sexpr as fn(&Expr) -> String),
+        _ => None,
+    });
+    let history = take_option(&mut arguments, "--history", "--history <path>", |value| Some(PathBuf::from(value)));
+    let config = take_option(&mut arguments, "--config", "--config <path>", |value| Some(PathBuf::from(value)));
+    let flags = [take_flag(&mut arguments, "--show-rpn"), take_flag(&mut arguments, "--quiet")];
+    if let Some(option) = unknown_option(&arguments, format.is_none()) {
+        eprintln!("Unknown option {}\n{}", option, USAGE);
+        std::process::exit(USAGE_ERROR);
+    }
+    if let Some(format) = format {
+        convert(&arguments.join(" "), format);
+    }
+    // the command line wins over the configuration
+    let config = config.or_else(config::default_path).map_or_else(Config::default, |path| Config::read(&path));
+    let mut options = Options {
+        output: output.unwrap_or(Output::Text),
+        precision: precision.or(config.precision),
+        notation: Notation::Plain,
+        show_rpn: flags[0] || config.show_rpn,
+        colour: config.colour,
+        timing: false,
+        quiet: flags[1],
+    };
+    match arguments.first().map(String::as_str) {
+        Some("--dot") => {
+            print_dot(&arguments[1..].join(" "));
+            return;
+        }
+        Some("--ast") => {
+            print_ast(&arguments[1..].join(" "), &options);
+            return;
+        }
+        Some("--rpn-only") => {
+            print_rpn(&config.calculator(), &arguments[1..].join(" "), &options);
+            return;
+        }
+        Some("-e") | Some("--eval") if arguments.len() > 1 => {
+            evaluate_once(&config.calculator(), &arguments[1..].join(" "), &options)
+        }
+        Some("-e") | Some("--eval") => {
+            eprintln!("Usage: infix-calculator -e <expression>");
+            std::process::exit(USAGE_ERROR);
+        }
+        Some("--file") => match arguments.get(1) {
+            Some(path) => evaluate_file(&config.calculator(), path, &options),
+            None => {
+                eprintln!("Usage: infix-calculator --file <path>");
+                std::process::exit(USAGE_ERROR);
+            }
+        },
+        _ => {}
+    }
+
+    // when reading from a pipe or a file, only the results are printed
+    let interactive = io::stdin().is_terminal();
+    options.quiet |= !interactive;
+    // without a terminal, errors decide the exit status like with `--file`
+    let mut session =
+        Session { calculator: config.calculator(), environment: Environment::new(), options, interactive, status: 0 };
+    let mut input = Input::new(interactive, history.or_else(default_history), &config);
+    loop {
+        // for completing the names defined by the line before
+        input.set_names(&session.calculator, &session.environment);
+        let buffer = match input.read_entry(&session.calculator) {
+            Some(line) => line,
+            None => break,
+        };
+        if session.run(&buffer) == Flow::Quit {
+            break;
         }
-        println!("Result: {}", result.unwrap());
     }
+    input.save_history();
+    std::process::exit(session.status);
 }
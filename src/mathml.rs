@@ -0,0 +1,135 @@
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use ast::Level;
+use {BinaryOp, Expr, UnaryOp};
+
+/// Invisible operator between a function name and its brackets
+const APPLY_FUNCTION: &str = "<mo>&#x2061;</mo>";
+
+impl Expr {
+    /// Writes the expression as presentation MathML, for screen readers and
+    /// other tools that read maths. Brackets are placed like `to_latex` does.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::Expr;
+    ///
+    /// let expr = Expr::parse("x ^ 2 / 2").unwrap();
+    /// assert_eq!(expr.to_mathml(), concat!(
+    ///     r#"<math xmlns="http://www.w3.org/1998/Math/MathML">"#,
+    ///     "<mfrac><msup><mi>x</mi><mn>2</mn></msup><mn>2</mn></mfrac>",
+    ///     "</math>",
+    /// ));
+    /// ```
+    pub fn to_mathml(&self) -> String {
+        format!(r#"<math xmlns="http://www.w3.org/1998/Math/MathML">{}</math>"#, self.mathml())
+    }
+
+    fn mathml(&self) -> String {
+        match *self {
+            Expr::Number(number) if number < 0.0 => format!("<mrow><mo>&#x2212;</mo><mn>{}</mn></mrow>", -number),
+            Expr::Number(number) => format!("<mn>{}</mn>", number),
+            Expr::Variable(ref name) => format!("<mi>{}</mi>", identifier(name)),
+            Expr::Unary(UnaryOp::Neg, ref operand) => {
+                format!("<mrow><mo>&#x2212;</mo>{}</mrow>", operand.mathml_bracketed(Level::Negation))
+            }
+            Expr::Unary(UnaryOp::Factorial, ref operand) => {
+                format!("<mrow>{}<mo>!</mo></mrow>", operand.mathml_bracketed(Level::Atom))
+            }
+            Expr::Unary(UnaryOp::Not, ref operand) => {
+                format!("<mrow><mo>&#x00AC;</mo>{}</mrow>", operand.mathml_bracketed(Level::Negation))
+            }
+            Expr::Unary(UnaryOp::BitNot, ref operand) => {
+                format!("<mrow><mo>~</mo>{}</mrow>", operand.mathml_bracketed(Level::Negation))
+            }
+            Expr::Binary(BinaryOp::Div, ref left, ref right) => format!("<mfrac>{}{}</mfrac>", left.mathml(), right.mathml()),
+            Expr::Binary(BinaryOp::Pow, ref base, ref exponent) => power(base, exponent),
+            Expr::Binary(op, ref left, ref right) => {
+                let (level, symbol) = match op {
+                    BinaryOp::Add => (Level::Sum, "+"),
+                    BinaryOp::Sub => (Level::Sum, "&#x2212;"),
+                    BinaryOp::Mul => (Level::Product, "&#x22C5;"),
+                    BinaryOp::Eq => (Level::Comparison, "="),
+                    BinaryOp::Ne => (Level::Comparison, "&#x2260;"),
+                    BinaryOp::Lt => (Level::Comparison, "&lt;"),
+                    BinaryOp::Le => (Level::Comparison, "&#x2264;"),
+                    BinaryOp::Gt => (Level::Comparison, "&gt;"),
+                    BinaryOp::Ge => (Level::Comparison, "&#x2265;"),
+                    BinaryOp::And => (Level::And, "&#x2227;"),
+                    BinaryOp::Or => (Level::Or, "&#x2228;"),
+                    BinaryOp::BitAnd => (Level::BitAnd, "&amp;"),
+                    BinaryOp::BitOr => (Level::BitOr, "|"),
+                    BinaryOp::BitXor => (Level::BitXor, "&#x2295;"),
+                    BinaryOp::PlusMinus => (Level::Measurement, "&#x00B1;"),
+                    _ => (Level::Product, "mod"),
+                };
+                let right = match right.level() {
+                    Level::Negation => right.mathml_bracketed(Level::Power),
+                    _ => right.mathml_bracketed(level.next()),
+                };
+                format!("<mrow>{}<mo>{}</mo>{}</mrow>", left.mathml_bracketed(level), symbol, right)
+            }
+            Expr::Call(ref name, ref arguments) => call(name, arguments),
+        }
+    }
+
+    fn mathml_bracketed(&self, level: Level) -> String {
+        if self.level() < level {
+            fenced("(", &self.mathml(), ")")
+        } else {
+            self.mathml()
+        }
+    }
+}
+
+fn fenced(open: &str, content: &str, close: &str) -> String {
+    format!("<mrow><mo>{}</mo>{}<mo>{}</mo></mrow>", open, content, close)
+}
+
+fn power(base: &Expr, exponent: &Expr) -> String {
+    format!("<msup>{}{}</msup>", base.mathml_bracketed(Level::Atom), exponent.mathml())
+}
+
+fn call(name: &str, arguments: &[Expr]) -> String {
+    let list = arguments.iter().map(Expr::mathml).collect::<Vec<String>>().join("<mo>,</mo>");
+    match (name, arguments) {
+        ("sqrt", [argument]) => format!("<msqrt>{}</msqrt>", argument.mathml()),
+        ("abs", [argument]) => fenced("|", &argument.mathml(), "|"),
+        ("pow", [base, exponent]) => power(base, exponent),
+        ("interval", _) => fenced("[", &list, "]"),
+        _ => format!("<mrow><mi>{}</mi>{}{}</mrow>", identifier(name), APPLY_FUNCTION, fenced("(", &list, ")")),
+    }
+}
+
+fn identifier(name: &str) -> &str {
+    match name {
+        "pi" => "π",
+        "tau" => "τ",
+        name => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mathml(input: &str) -> String {
+        Expr::parse(input).unwrap().mathml()
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(mathml("1 - 2 * x"), "<mrow><mn>1</mn><mo>&#x2212;</mo><mrow><mn>2</mn><mo>&#x22C5;</mo><mi>x</mi></mrow></mrow>");
+        assert_eq!(mathml("(1 + 2)!"), "<mrow><mrow><mo>(</mo><mrow><mn>1</mn><mo>+</mo><mn>2</mn></mrow><mo>)</mo></mrow><mo>!</mo></mrow>");
+        assert_eq!(mathml("-pi ^ 2"), "<mrow><mo>&#x2212;</mo><msup><mi>π</mi><mn>2</mn></msup></mrow>");
+        assert_eq!(mathml("2 ^ (1 / 2)"), "<msup><mn>2</mn><mfrac><mn>1</mn><mn>2</mn></mfrac></msup>");
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(mathml("sqrt(abs(x))"), "<msqrt><mrow><mo>|</mo><mi>x</mi><mo>|</mo></mrow></msqrt>");
+        assert_eq!(mathml("max(1, y)"), "<mrow><mi>max</mi><mo>&#x2061;</mo><mrow><mo>(</mo><mn>1</mn><mo>,</mo><mi>y</mi><mo>)</mo></mrow></mrow>");
+        assert_eq!(mathml("[1, 2]"), "<mrow><mo>[</mo><mn>1</mn><mo>,</mo><mn>2</mn><mo>]</mo></mrow>");
+    }
+}
@@ -0,0 +1,41 @@
+//! Stands in for the standard library without the `std` feature, so the
+//! rest of the crate can keep its `use std::...` paths. Everything here
+//! comes from `core` and `alloc`, with `hashbrown` for the maps and
+//! `num-traits` (with `libm`) for the float functions.
+
+pub use core::{convert, error, f64, fmt, hash, iter, mem, slice, str};
+
+pub use alloc::{borrow, vec};
+
+pub mod collections {
+    pub use alloc::collections::*;
+    pub use hashbrown::HashMap;
+}
+
+pub mod sync {
+    pub use alloc::sync::Arc;
+    pub use core::sync::atomic;
+
+    /// `std::sync::OnceLock`, for the statics initialized on first use.
+    pub struct OnceLock<T>(::once_cell::race::OnceBox<T>);
+
+    impl<T> OnceLock<T> {
+        pub const fn new() -> Self {
+            OnceLock(::once_cell::race::OnceBox::new())
+        }
+
+        pub fn get_or_init<F: FnOnce() -> T>(&self, f: F) -> &T {
+            self.0.get_or_init(|| ::alloc::boxed::Box::new(f()))
+        }
+    }
+}
+
+pub mod prelude {
+    pub mod v1 {
+        pub use alloc::boxed::Box;
+        pub use alloc::string::{String, ToString};
+        pub use alloc::vec::Vec;
+        // the methods `f64` only has with the standard library, such as `sqrt`
+        pub use num_traits::Float;
+    }
+}
@@ -0,0 +1,145 @@
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use {compute_result, factorial, BinaryOp, CalcError};
+
+/// A number type expressions can be evaluated with, see `RPNQueue::calculate_as`.
+///
+/// Only the arithmetic has to be provided. Constants, variables and functions
+/// are worked out with `f64`s and converted, unless `call` has a version of
+/// the function for the type itself.
+///
+/// # Example
+/// ```
+/// use infix_calculator::{BinaryOp, CalcError, CalcNum, RPNQueue};
+///
+/// /// Amounts of money, kept as a whole number of cents.
+/// #[derive(Debug, PartialEq)]
+/// struct Cents(i64);
+///
+/// impl CalcNum for Cents {
+///     fn from_f64(n: f64) -> Self {
+///         Cents((n * 100.0).round() as i64)
+///     }
+///
+///     fn to_f64(&self) -> f64 {
+///         self.0 as f64 / 100.0
+///     }
+///
+///     fn binary(self, op: BinaryOp, other: Self) -> Result<Self, CalcError> {
+///         match op {
+///             BinaryOp::Add => Ok(Cents(self.0 + other.0)),
+///             BinaryOp::Sub => Ok(Cents(self.0 - other.0)),
+///             BinaryOp::Mul => Ok(Cents(self.0 * other.0 / 100)),
+///             BinaryOp::Div if other.0 == 0 => Err(CalcError::DivisionByZero),
+///             BinaryOp::Div => Ok(Cents(self.0 * 100 / other.0)),
+///             _ => Err(CalcError::UnknownOperator(op.symbol().to_string())),
+///         }
+///     }
+///
+///     fn negate(self) -> Self {
+///         Cents(-self.0)
+///     }
+/// }
+///
+/// let queue = RPNQueue::from_infix_string("0.1 + 0.2 * 3").unwrap();
+/// assert_eq!(queue.calculate_as::<Cents>().unwrap(), Cents(70));
+/// ```
+pub trait CalcNum: Sized {
+    /// Converts the float a constant, variable or function gave.
+    fn from_f64(n: f64) -> Self;
+
+    fn to_f64(&self) -> f64;
+
+    /// Reads a number literal from an `RPNQueue`, such as `2` or `0.5`. By
+    /// default it is read as an `f64` and converted.
+    fn parse(literal: &str) -> Option<Self> {
+        literal.parse::<f64>().ok().map(Self::from_f64)
+    }
+
+    fn binary(self, op: BinaryOp, other: Self) -> Result<Self, CalcError>;
+
+    fn negate(self) -> Self;
+
+    /// By default the factorial of the value as an `f64`.
+    fn factorial(self) -> Result<Self, CalcError> {
+        factorial(self.to_f64()).map(Self::from_f64)
+    }
+
+    /// The type's own version of a built-in function, such as an exact `abs`.
+    /// None, the default, has it called with `f64`s instead.
+    fn call(_name: &str, _arguments: &[Self]) -> Option<Result<Self, CalcError>> {
+        None
+    }
+}
+
+impl CalcNum for f64 {
+    fn from_f64(n: f64) -> Self {
+        n
+    }
+
+    fn to_f64(&self) -> f64 {
+        *self
+    }
+
+    fn parse(literal: &str) -> Option<Self> {
+        literal.parse().ok()
+    }
+
+    fn binary(self, op: BinaryOp, other: Self) -> Result<Self, CalcError> {
+        compute_result(self, other, op.symbol())
+    }
+
+    fn negate(self) -> Self {
+        -self
+    }
+
+    fn factorial(self) -> Result<Self, CalcError> {
+        factorial(self)
+    }
+}
+
+impl CalcNum for f32 {
+    fn from_f64(n: f64) -> Self {
+        n as f32
+    }
+
+    fn to_f64(&self) -> f64 {
+        *self as f64
+    }
+
+    fn parse(literal: &str) -> Option<Self> {
+        literal.parse().ok()
+    }
+
+    fn binary(self, op: BinaryOp, other: Self) -> Result<Self, CalcError> {
+        match op {
+            BinaryOp::Add => Ok(self + other),
+            BinaryOp::Sub => Ok(self - other),
+            BinaryOp::Mul => Ok(self * other),
+            BinaryOp::Div => Ok(self / other),
+            BinaryOp::Rem => Ok(self % other),
+            BinaryOp::Pow => Ok(self.powf(other)),
+            op => compute_result(self as f64, other as f64, op.symbol()).map(|n| n as f32),
+        }
+    }
+
+    fn negate(self) -> Self {
+        -self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use RPNQueue;
+
+    #[test]
+    fn single_precision() {
+        let queue = RPNQueue::from_infix_string("0.1 + 0.2").unwrap();
+        assert_eq!(queue.calculate_as::<f32>().unwrap(), 0.3f32);
+        assert_ne!(queue.calculate_as::<f64>().unwrap(), 0.3);
+
+        let queue = RPNQueue::from_infix_string("sqrt(16) * pi - 3!").unwrap();
+        assert_eq!(queue.calculate_as::<f32>().unwrap(), 4.0 * std::f32::consts::PI - 6.0);
+    }
+}
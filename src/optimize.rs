@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use {compute_result, factorial, is_logical, short_circuits, whole, BinaryOp, Calculator, Expr, Fold, Function, UnaryOp};
+
+/// Works out the parts of an expression that only involve numbers, with
+/// the functions of a `Calculator`.
+pub(crate) struct ConstantFolder<'a> {
+    pub(crate) functions: &'a HashMap<String, Function>,
+}
+
+/// Only finite results are kept, NaN and infinities can't be written as a
+/// number in an `RPNQueue`.
+fn finite(n: f64) -> Option<Expr> {
+    if n.is_finite() {
+        Some(Expr::Number(n))
+    } else {
+        None
+    }
+}
+
+fn number(expr: &Expr) -> Option<f64> {
+    match *expr {
+        Expr::Number(n) => Some(n),
+        _ => None,
+    }
+}
+
+impl<'a> ConstantFolder<'a> {
+    // These work out a single node whose operands have been folded already.
+
+    fn unary(&self, op: UnaryOp, operand: Expr) -> Expr {
+        let folded = number(&operand).and_then(|n| match op {
+            UnaryOp::Neg => finite(-n),
+            UnaryOp::Factorial => factorial(n).ok().and_then(finite),
+            UnaryOp::Not => finite((n == 0.0) as u8 as f64),
+            UnaryOp::BitNot => whole(n).ok().and_then(|n| finite(!n as f64)),
+        });
+        folded.unwrap_or_else(|| Expr::Unary(op, Box::new(operand)))
+    }
+
+    fn binary(&self, op: BinaryOp, left: Expr, right: Expr) -> Expr {
+        let folded = match (number(&left), number(&right)) {
+            (Some(a), Some(b)) => compute_result(a, b, op.symbol()).ok().and_then(finite),
+            // `0 && x` is 0 whatever `x` is, and `1 || x` is 1
+            (Some(a), None) if is_logical(op.symbol()) && short_circuits(op.symbol(), a != 0.0) => {
+                finite((a != 0.0) as u8 as f64)
+            }
+            _ => None,
+        };
+        folded.unwrap_or_else(|| Expr::Binary(op, Box::new(left), Box::new(right)))
+    }
+
+    fn call(&self, name: String, arguments: Vec<Expr>) -> Expr {
+        // user defined functions aren't known until the expression is calculated
+        let folded = match self.functions.get(&name) {
+            Some(function) if function.accepts(arguments.len()) => {
+                arguments.iter().map(number).collect::<Option<Vec<f64>>>().and_then(|numbers| finite(function.call(&numbers)))
+            }
+            _ => None,
+        };
+        folded.unwrap_or(Expr::Call(name, arguments))
+    }
+}
+
+impl<'a> Fold for ConstantFolder<'a> {
+    fn fold_unary(&mut self, op: UnaryOp, operand: Expr) -> Expr {
+        let operand = self.fold_expr(operand);
+        self.unary(op, operand)
+    }
+
+    fn fold_binary(&mut self, op: BinaryOp, left: Expr, right: Expr) -> Expr {
+        let (left, right) = (self.fold_expr(left), self.fold_expr(right));
+        self.binary(op, left, right)
+    }
+
+    fn fold_call(&mut self, name: String, arguments: Vec<Expr>) -> Expr {
+        let arguments = arguments.into_iter().map(|argument| self.fold_expr(argument)).collect();
+        self.call(name, arguments)
+    }
+}
+
+/// Folds constants and removes operations that do nothing, such as `x * 1`.
+struct Simplifier<'a> {
+    constants: ConstantFolder<'a>,
+}
+
+impl<'a> Simplifier<'a> {
+    /// Whether `expr` depends on a name, a variable, a constant such as `pi`
+    /// or a user defined function. Folded parts that don't are numbers, or
+    /// else an error, NaN or an infinity.
+    fn has_name(&self, expr: &Expr) -> bool {
+        match *expr {
+            Expr::Number(_) => false,
+            Expr::Variable(_) => true,
+            Expr::Unary(_, ref operand) => self.has_name(operand),
+            Expr::Binary(_, ref left, ref right) => self.has_name(left) || self.has_name(right),
+            Expr::Call(ref name, ref arguments) => {
+                !self.constants.functions.contains_key(name) || arguments.iter().any(|argument| self.has_name(argument))
+            }
+        }
+    }
+
+    /// Simplifies a single node whose operands are simplified already.
+    fn binary(&self, op: BinaryOp, left: Expr, right: Expr) -> Expr {
+        let is = |expr: &Expr, n: f64| number(expr) == Some(n);
+        match op {
+            BinaryOp::Add if is(&right, 0.0) => left,
+            BinaryOp::Add if is(&left, 0.0) => right,
+            BinaryOp::Add if left == right => self.binary(BinaryOp::Mul, Expr::Number(2.0), left),
+            BinaryOp::Sub if is(&right, 0.0) => left,
+            BinaryOp::Sub if is(&left, 0.0) => self.negate(right),
+            // only for names, so `1 / 0 - 1 / 0` stays NaN
+            BinaryOp::Sub if left == right && self.has_name(&left) => Expr::Number(0.0),
+            BinaryOp::Mul if is(&left, 0.0) && self.has_name(&right) => Expr::Number(0.0),
+            BinaryOp::Mul if is(&right, 0.0) && self.has_name(&left) => Expr::Number(0.0),
+            BinaryOp::Mul if is(&right, 1.0) => left,
+            BinaryOp::Mul if is(&left, 1.0) => right,
+            BinaryOp::Div if is(&right, 1.0) => left,
+            BinaryOp::Div if left == right && self.has_name(&left) => Expr::Number(1.0),
+            BinaryOp::Div if is(&left, 0.0) && self.has_name(&right) => Expr::Number(0.0),
+            BinaryOp::Pow if is(&right, 0.0) || is(&left, 1.0) => Expr::Number(1.0),
+            BinaryOp::Pow if is(&right, 1.0) => left,
+            // `(x + 1) + 2` is `x + 3`, and likewise for products
+            BinaryOp::Add | BinaryOp::Mul if number(&right).is_some() => match left {
+                Expr::Binary(inner, ref operand, ref constant) if inner == op && number(constant).is_some() => {
+                    let constant = self.constants.binary(op, (**constant).clone(), right);
+                    self.binary(op, (**operand).clone(), constant)
+                }
+                left => self.constants.binary(op, left, right),
+            },
+            // `2 * (3 * x)` is `6 * x`
+            BinaryOp::Mul if number(&left).is_some() => match right {
+                Expr::Binary(BinaryOp::Mul, ref constant, ref operand) if number(constant).is_some() => {
+                    let constant = self.constants.binary(op, left, (**constant).clone());
+                    self.binary(op, constant, (**operand).clone())
+                }
+                right => self.constants.binary(op, left, right),
+            },
+            _ => self.constants.binary(op, left, right),
+        }
+    }
+
+    fn negate(&self, operand: Expr) -> Expr {
+        match operand {
+            Expr::Unary(UnaryOp::Neg, operand) => *operand,
+            operand => self.constants.unary(UnaryOp::Neg, operand),
+        }
+    }
+}
+
+impl<'a> Fold for Simplifier<'a> {
+    fn fold_unary(&mut self, op: UnaryOp, operand: Expr) -> Expr {
+        let operand = self.fold_expr(operand);
+        match op {
+            UnaryOp::Neg => self.negate(operand),
+            UnaryOp::Factorial | UnaryOp::Not | UnaryOp::BitNot => self.constants.unary(op, operand),
+        }
+    }
+
+    fn fold_binary(&mut self, op: BinaryOp, left: Expr, right: Expr) -> Expr {
+        let (left, right) = (self.fold_expr(left), self.fold_expr(right));
+        self.binary(op, left, right)
+    }
+
+    fn fold_call(&mut self, name: String, arguments: Vec<Expr>) -> Expr {
+        let arguments = arguments.into_iter().map(|argument| self.fold_expr(argument)).collect();
+        self.constants.call(name, arguments)
+    }
+}
+
+impl Expr {
+    /// Works out every part of the expression that doesn't depend on a name,
+    /// so it doesn't have to be done again each time the expression is
+    /// calculated. Parts giving an error, NaN or an infinity are left as they
+    /// are, and so are constants such as `pi` since a variable can hide them.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::Expr;
+    ///
+    /// let expr = Expr::parse("x * (2 + 3) + sqrt(16) / x").unwrap();
+    /// assert_eq!(expr.fold_constants().to_string(), "x * 5 + 4 / x");
+    /// ```
+    pub fn fold_constants(self) -> Expr {
+        ConstantFolder { functions: &Calculator::standard().functions }.fold_expr(self)
+    }
+
+    /// A smaller expression equal to this one. Constants are folded like in
+    /// `fold_constants`, sums and products of several constants such as
+    /// `x + 1 + 2` are combined, and these identities are used:
+    ///
+    /// - `x + 0`, `0 + x`, `x - 0`, `x * 1`, `1 * x`, `x / 1` and `x ^ 1` are `x`
+    /// - `0 - x` is `-x` and `-(-x)` is `x`
+    /// - `x + x` is `2 * x`
+    /// - `x - x`, `x * 0`, `0 * x` and `0 / x` are `0`
+    /// - `x / x`, `x ^ 0` and `1 ^ x` are `1`
+    ///
+    /// Unlike floats, the ones giving `0` or `1` hold for every `x` that
+    /// depends on a name, so `x * 0` is `0` even though it would be NaN for
+    /// an infinite `x`, and `x / x` is `1` even for `x = 0`. Parts without a
+    /// name that couldn't be folded, such as `1 / 0`, are an error, NaN or an
+    /// infinity, so `0 * (1 / 0)` is left as it is.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::Expr;
+    ///
+    /// let expr = Expr::parse("(x * 1 + 0) ^ (y - y + 1) + 2 * (3 * z)").unwrap();
+    /// assert_eq!(expr.simplify().to_string(), "x + 6 * z");
+    /// ```
+    pub fn simplify(self) -> Expr {
+        let constants = ConstantFolder { functions: &Calculator::standard().functions };
+        Simplifier { constants }.fold_expr(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folded(input: &str) -> String {
+        Expr::parse(input).unwrap().fold_constants().to_string()
+    }
+
+    #[test]
+    fn constants_are_worked_out() {
+        assert_eq!(folded("2 * 3 ^ 2 - max(1, 4, 2)!"), "-6");
+        assert_eq!(folded("-(1 + 1) * x"), "-2 * x");
+        assert_eq!(folded("f(1 + 1) + pi * 2"), "f(2) + pi * 2");
+        assert_eq!(folded("x + 1 + 2"), "x + 1 + 2");
+    }
+
+    fn simplified(input: &str) -> String {
+        Expr::parse(input).unwrap().simplify().to_string()
+    }
+
+    #[test]
+    fn identities() {
+        assert_eq!(simplified("0 + x - 0"), "x");
+        assert_eq!(simplified("0 - x"), "-x");
+        assert_eq!(simplified("-(-(x))"), "x");
+        assert_eq!(simplified("f(x) - f(x) + y"), "y");
+        assert_eq!(simplified("x * 0 + 1 * y / 1"), "y");
+        assert_eq!(simplified("x ^ 0 + 1 ^ x + x ^ 1"), "2 + x");
+        assert_eq!(simplified("x + x"), "2 * x");
+        assert_eq!(simplified("x - y"), "x - y");
+        assert_eq!(simplified("x / x + 0 / y"), "1");
+        assert_eq!(simplified("(x + 1) / (x + 1) * sin(y) ^ 1"), "sin(y)");
+    }
+
+    #[test]
+    fn identities_differ_from_floats() {
+        // on purpose: NaN or an infinity for some x
+        assert_eq!(simplified("x * 0"), "0");
+        assert_eq!(simplified("x / x"), "1");
+        assert_eq!(simplified("0 / x"), "0");
+        // but not for what is known not to be a number
+        assert_eq!(simplified("0 * (1 / 0)"), "0 * (1 / 0)");
+        assert_eq!(simplified("sqrt(-1) - sqrt(-1)"), "sqrt(-1) - sqrt(-1)");
+        assert_eq!(simplified("0 / 0"), "0 / 0");
+        assert_eq!(simplified("(1 / 0) / (1 / 0)"), "1 / 0 / (1 / 0)");
+    }
+
+    #[test]
+    fn constants_are_combined() {
+        assert_eq!(simplified("x + 1 + 2"), "x + 3");
+        assert_eq!(simplified("x * 2 * 3 * 4"), "x * 24");
+        assert_eq!(simplified("2 * (3 * (x + 0))"), "6 * x");
+        assert_eq!(simplified("x - 1 - 2"), "x - 1 - 2");
+        assert_eq!(simplified("sqrt(4 + 5) * x"), "3 * x");
+    }
+
+    #[test]
+    fn problems_are_left_for_later() {
+        assert_eq!(folded("1 / 0 + x"), "1 / 0 + x");
+        assert_eq!(folded("sqrt(-1) * (0.5)!"), "sqrt(-1) * 0.5!");
+        assert_eq!(folded("min(x, 1 + 1)"), "min(x, 2)");
+    }
+}
@@ -0,0 +1,93 @@
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use {arity, CalcError, Calculator, RPNQueue};
+
+impl RPNQueue {
+    /// Constructs a new `Result<RPNQueue>` from an expression in prefix
+    /// (Polish) notation, with the operator before its operands. Tokens are
+    /// separated by whitespace and written like in `from_rpn_string`.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::RPNQueue;
+    ///
+    /// let queue = RPNQueue::from_prefix_string("+ 1 * 2 max(2) x 3").unwrap();
+    /// assert_eq!(queue.to_string(), "1 2 x 3 max(2) * +");
+    /// ```
+    pub fn from_prefix_string(input: &str) -> Result<Self, CalcError> {
+        let operators = &Calculator::standard().operators;
+        // read from the end, so operands are done before their operator
+        let mut operands: Vec<Vec<String>> = Vec::new();
+        for token in input.split_whitespace().rev() {
+            let arity = arity(token, operators)?;
+            if operands.len() < arity {
+                return Err(CalcError::NotEnoughOperands);
+            }
+            let mut operand = Vec::new();
+            for _ in 0..arity {
+                operand.extend(operands.pop().unwrap());
+            }
+            operand.push(token.to_string());
+            operands.push(operand);
+        }
+        match operands.len() {
+            0 => Err(CalcError::NotEnoughOperands),
+            1 => Ok(RPNQueue::from_vec(operands.pop().unwrap())),
+            _ => Err(CalcError::TooManyOperands),
+        }
+    }
+
+    /// Writes the queue in prefix notation, see `from_prefix_string`.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::RPNQueue;
+    ///
+    /// let queue = RPNQueue::from_infix_string("(1 + 2) * -3").unwrap();
+    /// assert_eq!(queue.to_prefix().unwrap(), "* + 1 2 neg 3");
+    /// ```
+    pub fn to_prefix(&self) -> Result<String, CalcError> {
+        let operators = &Calculator::standard().operators;
+        let mut operands: Vec<Vec<&str>> = Vec::new();
+        for token in self {
+            let arity = arity(token, operators)?;
+            if operands.len() < arity {
+                return Err(CalcError::NotEnoughOperands);
+            }
+            let mut operand = vec![token];
+            for argument in operands.split_off(operands.len() - arity) {
+                operand.extend(argument);
+            }
+            operands.push(operand);
+        }
+        match operands.len() {
+            0 => Err(CalcError::NotEnoughOperands),
+            1 => Ok(operands[0].join(" ")),
+            _ => Err(CalcError::TooManyOperands),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for input in &["1 - 2 - 3", "2 ^ 3 ^ 2", "-x! % f(1, 2, 3)", "[1, 2] ± 0.5 * pi"] {
+            let queue = RPNQueue::from_infix_string(input).unwrap();
+            let prefix = queue.to_prefix().unwrap();
+            assert_eq!(RPNQueue::from_prefix_string(&prefix).unwrap(), queue, "{} => {}", input, prefix);
+        }
+        assert_eq!(RPNQueue::from_infix_string("1 - 2 - 3").unwrap().to_prefix().unwrap(), "- - 1 2 3");
+    }
+
+    #[test]
+    fn invalid_prefix() {
+        assert_eq!(RPNQueue::from_prefix_string(""), Err(CalcError::NotEnoughOperands));
+        assert_eq!(RPNQueue::from_prefix_string("+ 1"), Err(CalcError::NotEnoughOperands));
+        assert_eq!(RPNQueue::from_prefix_string("+ 1 2 3"), Err(CalcError::TooManyOperands));
+        assert_eq!(RPNQueue::from_prefix_string("$ 1 2"), Err(CalcError::InvalidRpnToken("$".to_string())));
+    }
+}
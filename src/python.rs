@@ -0,0 +1,137 @@
+//! A Python module, `infix_calculator`, parsing and calculating the same
+//! way as the Rust API. Every `CalcError` is raised as a subclass of
+//! `infix_calculator.CalcError`: `ParseError` for malformed input,
+//! `UnknownNameError` for variables and functions without a definition and
+//! `MathError` for operations without a result, such as dividing an integer
+//! by zero.
+
+use std::collections::HashMap;
+
+use pyo3::prelude::*;
+
+use {CalcError, RPNQueue};
+
+mod exceptions {
+    use pyo3::create_exception;
+    use pyo3::exceptions::PyException;
+
+    create_exception!(infix_calculator, CalcError, PyException, "Anything that went wrong parsing or calculating.");
+    create_exception!(infix_calculator, ParseError, CalcError, "The input is not a well-formed expression.");
+    create_exception!(infix_calculator, UnknownNameError, CalcError, "A variable or function without a definition.");
+    create_exception!(infix_calculator, MathError, CalcError, "An operation without a result.");
+}
+
+impl From<CalcError> for PyErr {
+    fn from(err: CalcError) -> PyErr {
+        let message = err.to_string();
+        match err {
+            CalcError::InvalidToken { .. } |
+            CalcError::NumberOutOfRange { .. } |
+            CalcError::InvalidRpnToken(_) |
+            CalcError::NotInTree(_) |
+            CalcError::NotEnoughOperands |
+            CalcError::TooManyOperands |
+            CalcError::MissingOperator(_) |
+            CalcError::MissingOperand(_) |
+            CalcError::MissingArgument(_) |
+            CalcError::MissingBrackets { .. } |
+            CalcError::UnbalancedParen { .. } |
+            CalcError::UnknownOperator(_) |
+            CalcError::WrongArgumentCount { .. } |
+            CalcError::InputTooLong(_) |
+            CalcError::TooManyTokens(_) |
+            CalcError::NestingTooDeep(_) |
+            CalcError::OutputTooLong(_) => exceptions::ParseError::new_err(message),
+            CalcError::UnknownVariable(_) |
+            CalcError::MissingVariables(_) |
+            CalcError::UnknownFunction(_) => exceptions::UnknownNameError::new_err(message),
+            CalcError::DivisionByZero |
+            CalcError::Overflow |
+            CalcError::NotFinite { .. } |
+            CalcError::InvalidInterval { .. } |
+            CalcError::InvalidFactorial(_) |
+            CalcError::NotAnInteger(_) => exceptions::MathError::new_err(message),
+            _ => exceptions::CalcError::new_err(message),
+        }
+    }
+}
+
+/// A parsed expression, kept in Reverse Polish Notation.
+#[pyclass(name = "Expression", frozen)]
+struct Expression {
+    queue: RPNQueue,
+}
+
+#[pymethods]
+impl Expression {
+    /// The tokens in Reverse Polish Notation.
+    #[getter]
+    fn tokens(&self) -> Vec<String> {
+        self.queue.tokens().to_vec()
+    }
+
+    /// Calculates the expression with the values in `variables`, a dict of
+    /// names to numbers.
+    #[pyo3(signature = (variables = None))]
+    fn evaluate(&self, variables: Option<HashMap<String, f64>>) -> PyResult<f64> {
+        Ok(self.queue.calculate_with_vars(&variables.unwrap_or_default())?)
+    }
+
+    fn __str__(&self) -> String {
+        self.queue.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Expression('{}')", self.queue)
+    }
+}
+
+/// Parses an infix expression such as `2 * (x + 1)`.
+#[pyfunction]
+fn parse(input: &str) -> PyResult<Expression> {
+    Ok(Expression { queue: RPNQueue::from_infix_string(input)? })
+}
+
+/// Parses and calculates `input` with the values in `variables`.
+#[pyfunction]
+#[pyo3(signature = (input, variables = None))]
+fn evaluate(input: &str, variables: Option<HashMap<String, f64>>) -> PyResult<f64> {
+    parse(input)?.evaluate(variables)
+}
+
+#[pymodule]
+fn infix_calculator(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = module.py();
+    module.add_function(wrap_pyfunction!(self::parse, module)?)?;
+    module.add_function(wrap_pyfunction!(self::evaluate, module)?)?;
+    module.add_class::<Expression>()?;
+    module.add("CalcError", py.get_type::<exceptions::CalcError>())?;
+    module.add("ParseError", py.get_type::<exceptions::ParseError>())?;
+    module.add("UnknownNameError", py.get_type::<exceptions::UnknownNameError>())?;
+    module.add("MathError", py.get_type::<exceptions::MathError>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_and_exceptions() {
+        Python::initialize();
+        Python::attach(|py| {
+            let variables = [("x".to_string(), 4.0)].iter().cloned().collect();
+            assert_eq!(evaluate("2 * x + 1", Some(variables)).unwrap(), 9.0);
+            assert_eq!(parse("2 ^ y").unwrap().tokens(), vec!["2", "y", "^"]);
+
+            let err = evaluate("1 $ 2", None).unwrap_err();
+            assert!(err.is_instance_of::<exceptions::ParseError>(py));
+            assert_eq!(err.value(py).to_string(), "Invalid token: $");
+            assert!(evaluate("x", None).unwrap_err().is_instance_of::<exceptions::UnknownNameError>(py));
+            let err = evaluate("(-1)!", None).unwrap_err();
+            assert!(err.is_instance_of::<exceptions::MathError>(py));
+            assert!(err.is_instance_of::<exceptions::CalcError>(py));
+            assert!(!err.is_instance_of::<exceptions::ParseError>(py));
+        });
+    }
+}
@@ -0,0 +1,102 @@
+use std::io::BufRead;
+
+use {CalcError, RPNQueue};
+
+/// The expressions of a reader, one per line, see `RPNQueue::from_reader`.
+#[derive(Debug)]
+pub struct Expressions<R> {
+    reader: R,
+    line: String,
+    number: usize,
+    /// Set after a read error, nothing more is read then
+    failed: bool,
+}
+
+impl RPNQueue {
+    /// Parses the expressions of `reader`, one per line, as they are read.
+    /// Only one line is held in memory at a time, so it works for files of
+    /// any size. Blank lines are skipped, `Expressions::line` tells which
+    /// line an expression was on. A failed read gives a `CalcError::Io`
+    /// and ends the iterator.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::RPNQueue;
+    ///
+    /// let input = "1 + 2\n\n3 * (4 - 1)\n".as_bytes();
+    /// let results: Vec<f64> = RPNQueue::from_reader(input)
+    ///     .map(|queue| queue.unwrap().calculate().unwrap())
+    ///     .collect();
+    /// assert_eq!(results, vec![3.0, 9.0]);
+    /// ```
+    pub fn from_reader<R: BufRead>(reader: R) -> Expressions<R> {
+        Expressions { reader, line: String::new(), number: 0, failed: false }
+    }
+}
+
+impl<R> Expressions<R> {
+    /// The line, counting from 1, of the expression given last.
+    pub fn line(&self) -> usize {
+        self.number
+    }
+}
+
+impl<R: BufRead> Iterator for Expressions<R> {
+    type Item = Result<RPNQueue, CalcError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.failed {
+            // the same buffer is used for every line
+            self.line.clear();
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => {
+                    self.number += 1;
+                    if !self.line.trim().is_empty() {
+                        return Some(RPNQueue::from_infix_string(&self.line));
+                    }
+                }
+                Err(err) => {
+                    self.failed = true;
+                    return Some(Err(CalcError::Io(err.to_string())));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, BufReader, Read};
+
+    use {CalcError, RPNQueue, Span};
+
+    #[test]
+    fn one_expression_per_line() {
+        let mut expressions = RPNQueue::from_reader("2 ^ 3\n  \n1 $ 2\r\nsqrt(x)".as_bytes());
+        assert_eq!(expressions.next().unwrap().unwrap().to_string(), "2 3 ^");
+        assert_eq!(expressions.line(), 1);
+        let err = CalcError::InvalidToken { token: "$".to_string(), span: Span::new(2, 3) };
+        assert_eq!(expressions.next().unwrap().unwrap_err(), err);
+        assert_eq!(expressions.line(), 3);
+        assert_eq!(expressions.next().unwrap().unwrap().to_string(), "x sqrt(1)");
+        assert_eq!(expressions.line(), 4);
+        assert!(expressions.next().is_none());
+    }
+
+    struct Failing;
+
+    impl Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk on fire"))
+        }
+    }
+
+    #[test]
+    fn read_errors() {
+        let mut expressions = RPNQueue::from_reader(BufReader::new(Failing));
+        assert_eq!(expressions.next().unwrap().unwrap_err(), CalcError::Io("disk on fire".to_string()));
+        assert!(expressions.next().is_none());
+    }
+}
@@ -0,0 +1,166 @@
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use {BinaryOp, CalcError, Expr, Span, UnaryOp};
+
+impl Expr {
+    /// Writes the expression as an S-expression, with every operator and
+    /// call in prefix form. A unary minus is `(- x)` and a factorial `(! x)`.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::Expr;
+    ///
+    /// let expr = Expr::parse("1 + 2 * -max(x, 3)").unwrap();
+    /// assert_eq!(expr.to_sexpr(), "(+ 1 (* 2 (- (max x 3))))");
+    /// ```
+    pub fn to_sexpr(&self) -> String {
+        match *self {
+            Expr::Number(number) => number.to_string(),
+            Expr::Variable(ref name) => name.clone(),
+            Expr::Unary(op, ref operand) => {
+                let symbol = match op {
+                    UnaryOp::Neg => "-",
+                    UnaryOp::Factorial => "!",
+                    UnaryOp::Not => "not",
+                    UnaryOp::BitNot => "~",
+                };
+                format!("({} {})", symbol, operand.to_sexpr())
+            }
+            Expr::Binary(op, ref left, ref right) => format!("({} {} {})", op.symbol(), left.to_sexpr(), right.to_sexpr()),
+            Expr::Call(ref name, ref arguments) => {
+                let mut sexpr = format!("({}", name);
+                for argument in arguments {
+                    sexpr.push(' ');
+                    sexpr.push_str(&argument.to_sexpr());
+                }
+                sexpr.push(')');
+                sexpr
+            }
+        }
+    }
+
+    /// Reads an S-expression written like `to_sexpr` writes them. Any other
+    /// name at the start of a list is a call.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::Expr;
+    ///
+    /// let expr = Expr::from_sexpr("(^ (- x) (sqrt 2))").unwrap();
+    /// assert_eq!(expr, Expr::parse("(-x) ^ sqrt(2)").unwrap());
+    /// ```
+    pub fn from_sexpr(input: &str) -> Result<Self, CalcError> {
+        let tokens = tokenize(input);
+        let mut position = 0;
+        let expr = parse(&tokens, &mut position)?;
+        match tokens.get(position) {
+            Some(&(ref token, span)) => Err(CalcError::InvalidToken { token: token.clone(), span }),
+            None => Ok(expr),
+        }
+    }
+}
+
+/// Splits `input` into brackets and atoms, with where each one is.
+fn tokenize(input: &str) -> Vec<(String, Span)> {
+    let mut tokens: Vec<(String, Span)> = Vec::new();
+    let mut atom: Option<(String, usize)> = None;
+    let chars = input.chars().enumerate().chain(Some((input.chars().count(), ' ')));
+    for (i, c) in chars {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            if let Some((text, start)) = atom.take() {
+                tokens.push((text, Span::new(start, i)));
+            }
+            if !c.is_whitespace() {
+                tokens.push((c.to_string(), Span::new(i, i + 1)));
+            }
+        } else {
+            atom.get_or_insert_with(|| (String::new(), i)).0.push(c);
+        }
+    }
+    tokens
+}
+
+fn parse(tokens: &[(String, Span)], position: &mut usize) -> Result<Expr, CalcError> {
+    let (token, span) = match tokens.get(*position) {
+        Some(&(ref token, span)) => (token.as_str(), span),
+        None => return Err(CalcError::NotEnoughOperands),
+    };
+    *position += 1;
+    match token {
+        "(" => {}
+        ")" => return Err(CalcError::UnbalancedParen { position: span.start }),
+        atom => {
+            return Ok(match atom.parse::<f64>() {
+                Ok(number) => Expr::Number(number),
+                Err(_) => Expr::Variable(atom.to_string()),
+            });
+        }
+    }
+
+    let head = match tokens.get(*position) {
+        Some(&(ref head, head_span)) if head == "(" || head == ")" => {
+            return Err(CalcError::InvalidToken { token: head.clone(), span: head_span });
+        }
+        Some((head, _)) => head.clone(),
+        None => return Err(CalcError::UnbalancedParen { position: span.start }),
+    };
+    *position += 1;
+    let mut arguments = Vec::new();
+    loop {
+        match tokens.get(*position) {
+            Some((token, _)) if token == ")" => break,
+            Some(_) => arguments.push(parse(tokens, position)?),
+            None => return Err(CalcError::UnbalancedParen { position: span.start }),
+        }
+    }
+    *position += 1;
+
+    let count = arguments.len();
+    let mut arguments = arguments.into_iter();
+    let mut next = || Box::new(arguments.next().unwrap());
+    let expr = match (head.as_str(), count) {
+        ("-", 1) => Expr::Unary(UnaryOp::Neg, next()),
+        ("!", 1) => Expr::Unary(UnaryOp::Factorial, next()),
+        ("not", 1) => Expr::Unary(UnaryOp::Not, next()),
+        ("~", 1) => Expr::Unary(UnaryOp::BitNot, next()),
+        (symbol, 2) if BinaryOp::from_symbol(symbol).is_some() => {
+            Expr::Binary(BinaryOp::from_symbol(symbol).unwrap(), next(), next())
+        }
+        (symbol, count) if matches!(symbol, "!" | "not" | "~") || BinaryOp::from_symbol(symbol).is_some() => {
+            return Err(CalcError::WrongArgumentCount { function: symbol.to_string(), count });
+        }
+        (name, _) => Expr::Call(name.to_string(), arguments.collect()),
+    };
+    Ok(expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for input in &["1.5 + 3 - (4 / 5)", "2 ^ 3 ^ 2", "-x! % 3", "max(1, min(2, 3), f(x)) * pi", "[1, 2] * 3", "!(x && y) || x <= 2"] {
+            let expr = Expr::parse(input).unwrap();
+            assert_eq!(Expr::from_sexpr(&expr.to_sexpr()).unwrap(), expr, "{}", input);
+        }
+        assert_eq!(Expr::parse("2 ^ 3 ^ 2").unwrap().to_sexpr(), "(^ 2 (^ 3 2))");
+        assert_eq!(Expr::from_sexpr("  ( *\n x\t(f) ) ").unwrap(), Expr::Binary(
+            BinaryOp::Mul,
+            Box::new(Expr::Variable("x".to_string())),
+            Box::new(Expr::Call("f".to_string(), Vec::new())),
+        ));
+    }
+
+    #[test]
+    fn invalid_sexprs() {
+        assert_eq!(Expr::from_sexpr(""), Err(CalcError::NotEnoughOperands));
+        assert_eq!(Expr::from_sexpr("(+ 1 2"), Err(CalcError::UnbalancedParen { position: 0 }));
+        assert_eq!(Expr::from_sexpr("(+ 1 2))"), Err(CalcError::InvalidToken { token: ")".to_string(), span: Span::new(7, 8) }));
+        assert_eq!(Expr::from_sexpr("x y"), Err(CalcError::InvalidToken { token: "y".to_string(), span: Span::new(2, 3) }));
+        assert_eq!(Expr::from_sexpr("(* 1 2 3)"), Err(CalcError::WrongArgumentCount { function: "*".to_string(), count: 3 }));
+        assert_eq!(Expr::from_sexpr("(! 1 2)"), Err(CalcError::WrongArgumentCount { function: "!".to_string(), count: 2 }));
+        assert_eq!(Expr::from_sexpr("(() 1)"), Err(CalcError::InvalidToken { token: "(".to_string(), span: Span::new(1, 2) }));
+    }
+}
@@ -0,0 +1,86 @@
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use Expr;
+
+impl Expr {
+    /// Draws the tree as text, one node per line with its operands indented
+    /// under it, in order.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::Expr;
+    ///
+    /// let tree = Expr::parse("1 + 2 * 3").unwrap().to_tree();
+    /// assert_eq!(tree, "+\n├── 1\n└── *\n    ├── 2\n    └── 3\n");
+    /// ```
+    pub fn to_tree(&self) -> String {
+        let mut tree = String::new();
+        self.write_tree(&mut tree, "", "");
+        tree
+    }
+
+    /// Writes the node after `first`, the prefix of its own line, and the
+    /// nodes under it after `rest`, the prefix of theirs.
+    fn write_tree(&self, tree: &mut String, first: &str, rest: &str) {
+        tree.push_str(first);
+        let operands: Vec<&Expr> = match *self {
+            Expr::Number(number) => {
+                tree.push_str(&number.to_string());
+                Vec::new()
+            }
+            Expr::Variable(ref name) => {
+                tree.push_str(name);
+                Vec::new()
+            }
+            Expr::Unary(op, ref operand) => {
+                tree.push_str(op.written());
+                vec![&**operand]
+            }
+            Expr::Binary(op, ref left, ref right) => {
+                tree.push_str(op.symbol());
+                vec![&**left, &**right]
+            }
+            Expr::Call(ref name, ref arguments) => {
+                tree.push_str(name);
+                tree.push_str("()");
+                arguments.iter().collect()
+            }
+        };
+        tree.push('\n');
+        for (i, operand) in operands.iter().enumerate() {
+            if i + 1 == operands.len() {
+                operand.write_tree(tree, &format!("{}└── ", rest), &format!("{}    ", rest));
+            } else {
+                operand.write_tree(tree, &format!("{}├── ", rest), &format!("{}│   ", rest));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_tree() {
+        assert_eq!(Expr::parse("-max(x, 2 + 3)!").unwrap().to_tree(), concat!(
+            "-\n",
+            "└── !\n",
+            "    └── max()\n",
+            "        ├── x\n",
+            "        └── +\n",
+            "            ├── 2\n",
+            "            └── 3\n",
+        ));
+        // a logical not and a factorial are both written `!`
+        assert_eq!(Expr::parse("!n! & ~m").unwrap().to_tree(), concat!(
+            "&\n",
+            "├── ¬\n",
+            "│   └── !\n",
+            "│       └── n\n",
+            "└── ~\n",
+            "    └── m\n",
+        ));
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use CalcError;
+
+/// A measured value with a standard uncertainty, written `10.0 ± 0.2` or
+/// `10.0 +- 0.2` in expressions.
+///
+/// Uncertainties are propagated to first order, assuming the operands are
+/// independent of each other, e.g. the errors of a sum add in quadrature.
+/// Nothing keeps track of where a value came from, so that holds even when
+/// both operands are the same measurement: `x - x` and `ans - $1` right
+/// after `$1` have an uncertainty of `√2` times that of `x` rather than
+/// none, and `x * x` has a smaller one than `x ^ 2`. Write such expressions
+/// so each measurement appears once.
+///
+/// # Example
+/// ```
+/// use infix_calculator::Calculator;
+///
+/// let result = Calculator::new().calculate("(3 ± 0.3) * (4 ± 0.4)").unwrap();
+/// assert_eq!(result.to_string(), "12 ± 1.697056274847714");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Uncertain {
+    value: f64,
+    error: f64,
+}
+
+impl Uncertain {
+    /// The sign of `error` doesn't matter, `1 ± -0.1` is `1 ± 0.1`.
+    pub fn new(value: f64, error: f64) -> Self {
+        Uncertain { value, error: error.abs() }
+    }
+
+    /// A value known exactly.
+    pub fn exact(value: f64) -> Self {
+        Uncertain { value, error: 0.0 }
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    pub fn error(&self) -> f64 {
+        self.error
+    }
+
+    /// `self op other`, with the first order error of independent operands.
+    pub(crate) fn binary(self, op: &str, other: Uncertain) -> Result<Uncertain, CalcError> {
+        let (a, b) = (self.value, other.value);
+        let (da, db) = (self.error, other.error);
+        let result = match op {
+            "+" => Uncertain::new(a + b, da.hypot(db)),
+            "-" => Uncertain::new(a - b, da.hypot(db)),
+            "*" => Uncertain::new(a * b, (b * da).hypot(a * db)),
+            "/" => Uncertain::new(a / b, (da / b).hypot(a * db / (b * b))),
+            "^" => {
+                let value = a.powf(b);
+                // the `b` term is left out when it is exact, so negative bases still work
+                let db_term = if db == 0.0 { 0.0 } else { value * a.ln() * db };
+                Uncertain::new(value, (b * a.powf(b - 1.0) * da).hypot(db_term))
+            }
+            // another error on top of the one already there
+            "±" => Uncertain::new(a, da.hypot(b)),
+            _ => return Err(CalcError::UnknownOperator(op.to_string())),
+        };
+        Ok(result)
+    }
+
+    pub(crate) fn negate(self) -> Uncertain {
+        Uncertain::new(-self.value, self.error)
+    }
+
+    /// Applies `f`, scaling the error by its derivative `df`, which is
+    /// only close while `f` is nearly straight within the error.
+    pub(crate) fn map(self, f: fn(f64) -> f64, df: fn(f64) -> f64) -> Uncertain {
+        Uncertain::new(f(self.value), df(self.value) * self.error)
+    }
+}
+
+impl fmt::Display for Uncertain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match f.precision() {
+            Some(precision) => write!(f, "{:.*} ± {:.*}", precision, self.value, precision, self.error),
+            None => write!(f, "{} ± {}", self.value, self.error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Calculator, Environment, Value};
+
+    #[test]
+    fn errors_propagate() {
+        let a = Uncertain::new(10.0, 0.3);
+        let b = Uncertain::new(5.0, 0.4);
+        assert_eq!(a.binary("+", b), Ok(Uncertain::new(15.0, 0.5)));
+        assert_eq!(a.binary("-", b), Ok(Uncertain::new(5.0, 0.5)));
+        assert_eq!(a.binary("*", Uncertain::exact(2.0)), Ok(Uncertain::new(20.0, 0.6)));
+        assert_eq!(Uncertain::new(6.0, 1.5).binary("/", Uncertain::exact(3.0)), Ok(Uncertain::new(2.0, 0.5)));
+        assert_eq!(Uncertain::new(3.0, 0.1).binary("^", Uncertain::exact(2.0)), Ok(Uncertain::new(9.0, 0.6000000000000001)));
+        assert_eq!(Uncertain::new(-2.0, 0.1).binary("^", Uncertain::exact(3.0)).unwrap().value(), -8.0);
+        assert_eq!(Uncertain::exact(1.0).binary("±", Uncertain::exact(-0.5)), Ok(Uncertain::new(1.0, 0.5)));
+        assert_eq!(a.negate(), Uncertain::new(-10.0, 0.3));
+        assert!(a.binary("%", b).is_err());
+    }
+
+    #[test]
+    fn operands_are_independent() {
+        let x = Uncertain::new(10.0, 0.3);
+        assert_eq!(x.binary("-", x), Ok(Uncertain::new(0.0, 0.3f64.hypot(0.3))));
+        assert_eq!(x.binary("*", x).unwrap().error(), 3f64.hypot(3.0));
+        assert_eq!(x.binary("^", Uncertain::exact(2.0)).unwrap().error(), 6.0);
+
+        let calculator = Calculator::new();
+        let mut environment = Environment::new();
+        environment.push_result(Value::Uncertain(x));
+        assert_eq!(calculator.calculate_with("ans - $1", &environment), Ok(Value::Uncertain(Uncertain::new(0.0, 0.3f64.hypot(0.3)))));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Uncertain::new(10.0, -0.2).to_string(), "10 ± 0.2");
+    }
+}
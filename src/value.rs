@@ -0,0 +1,694 @@
+use std::fmt;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
+#[cfg(feature = "complex")]
+use num_complex::Complex64;
+#[cfg(any(feature = "bigint", feature = "decimal"))]
+use num_traits::{ToPrimitive, Zero};
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
+
+use {compute_result, factorial, is_logical, whole, BinaryOp, CalcError, Interval, Overflow, Uncertain};
+
+/// A number produced by a `Calculator`.
+///
+/// Integers stay integers as long as every operand is one: `+ - * %` and `^`
+/// with a non-negative exponent give exact results and `/` divides like Rust
+/// does, truncating towards zero. As soon as a float is involved, the exponent
+/// is negative or the result doesn't fit in an `i64` the calculation carries
+/// on with floats. Functions, constants and variables always give floats.
+///
+/// With the `bigint` feature integers that outgrow an `i64` become a `BigInt`
+/// instead, and go back to being an `Integer` once they fit again.
+///
+/// Decimals work the same way, except that `/` and negative exponents stay
+/// exact. An integer mixed with a decimal gives a decimal.
+///
+/// With the `complex` feature anything mixed with a complex number is complex,
+/// even once the imaginary part cancels out, so `sqrt(-4 + 0i)` is `2i`.
+///
+/// Comparisons give the integer 1 or 0 for exact operands, and the float 1 or
+/// 0 otherwise. Complex numbers only compare with `==` and `!=`. So do `&&`,
+/// `||` and `!`, for which anything but zero is true.
+///
+/// Likewise anything mixed with an interval is an interval, and anything
+/// mixed with an uncertain value such as `10 ± 0.2` is uncertain.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Value {
+    Integer(i64),
+    Float(f64),
+    Interval(Interval),
+    Uncertain(Uncertain),
+    #[cfg(feature = "bigint")]
+    BigInt(BigInt),
+    #[cfg(feature = "decimal")]
+    Decimal(Decimal),
+    #[cfg(feature = "complex")]
+    Complex(Complex64),
+}
+
+impl Value {
+    /// The value as a float, NaN for intervals, uncertain values and complex numbers.
+    pub fn to_f64(&self) -> f64 {
+        match *self {
+            Value::Integer(n) => n as f64,
+            Value::Float(n) => n,
+            Value::Interval(_) | Value::Uncertain(_) => f64::NAN,
+            #[cfg(feature = "bigint")]
+            Value::BigInt(ref n) => n.to_f64().unwrap_or(f64::NAN),
+            #[cfg(feature = "decimal")]
+            Value::Decimal(n) => n.to_f64().unwrap_or(f64::NAN),
+            #[cfg(feature = "complex")]
+            Value::Complex(_) => f64::NAN,
+        }
+    }
+
+    fn to_interval(&self) -> Interval {
+        match *self {
+            Value::Interval(interval) => interval,
+            ref n => Interval::point(n.to_f64()),
+        }
+    }
+
+    fn to_uncertain(&self) -> Uncertain {
+        match *self {
+            Value::Uncertain(n) => n,
+            ref n => Uncertain::exact(n.to_f64()),
+        }
+    }
+
+    #[cfg(feature = "complex")]
+    fn to_complex(&self) -> Complex64 {
+        match *self {
+            Value::Complex(z) => z,
+            ref real => Complex64::new(real.to_f64(), 0.0),
+        }
+    }
+
+    /// Whether the value counts as true for `&&`, `||` and `!`, which
+    /// anything but zero does. None for intervals and uncertain values, which
+    /// could be either.
+    pub(crate) fn truth(&self) -> Option<bool> {
+        match *self {
+            Value::Interval(_) | Value::Uncertain(_) => None,
+            #[cfg(feature = "complex")]
+            Value::Complex(z) => Some(z.re != 0.0 || z.im != 0.0),
+            ref n => Some(n.to_f64() != 0.0),
+        }
+    }
+
+    /// 1 or 0 for whether something `holds`, a float if the value is one.
+    pub(crate) fn boolean(&self, holds: bool) -> Value {
+        match *self {
+            Value::Float(_) => Value::Float(holds as u8 as f64),
+            _ => Value::Integer(holds as i64),
+        }
+    }
+
+    pub(crate) fn not(self) -> Result<Value, CalcError> {
+        let truth = self.truth().ok_or_else(|| CalcError::UnknownOperator("!".to_string()))?;
+        Ok(self.boolean(!truth))
+    }
+
+    /// `~n`, exact for integers and whole decimals.
+    pub(crate) fn bit_not(self) -> Result<Value, CalcError> {
+        match self {
+            Value::Integer(n) => Ok(Value::Integer(!n)),
+            #[cfg(feature = "bigint")]
+            Value::BigInt(n) => Ok(big(!n)),
+            #[cfg(feature = "decimal")]
+            Value::Decimal(n) if n.fract().is_zero() => match n.to_i64() {
+                Some(n) => Ok(Value::Decimal(Decimal::from(!n))),
+                None => Err(CalcError::NotAnInteger(n.to_f64().unwrap_or(f64::NAN))),
+            },
+            #[cfg(feature = "complex")]
+            Value::Complex(_) => Err(CalcError::UnknownOperator("~".to_string())),
+            Value::Interval(_) | Value::Uncertain(_) => Err(CalcError::UnknownOperator("~".to_string())),
+            n => whole(n.to_f64()).map(|n| Value::Float(!n as f64)),
+        }
+    }
+
+    pub(crate) fn binary(self, op: &str, other: Value) -> Result<Value, CalcError> {
+        if is_logical(op) {
+            let (first, second) = match (self.truth(), other.truth()) {
+                (Some(first), Some(second)) => (first, second),
+                _ => return Err(CalcError::UnknownOperator(op.to_string())),
+            };
+            let holds = if op == "&&" { first && second } else { first || second };
+            let float = if let Value::Float(_) = other { other } else { self };
+            return Ok(float.boolean(holds));
+        }
+        if op == "±" {
+            return self.to_uncertain().binary(op, other.to_uncertain()).map(Value::Uncertain);
+        }
+        match (self, other) {
+            (Value::Integer(first), Value::Integer(second)) => integer(first, second, op),
+            #[cfg(feature = "bigint")]
+            (Value::BigInt(first), Value::BigInt(second)) => big_integer(first, second, op),
+            #[cfg(feature = "bigint")]
+            (Value::BigInt(first), Value::Integer(second)) => big_integer(first, BigInt::from(second), op),
+            #[cfg(feature = "bigint")]
+            (Value::Integer(first), Value::BigInt(second)) => big_integer(BigInt::from(first), second, op),
+            #[cfg(feature = "decimal")]
+            (Value::Decimal(first), Value::Decimal(second)) => decimal(first, second, op),
+            #[cfg(feature = "decimal")]
+            (Value::Decimal(first), Value::Integer(second)) => decimal(first, Decimal::from(second), op),
+            #[cfg(feature = "decimal")]
+            (Value::Integer(first), Value::Decimal(second)) => decimal(Decimal::from(first), second, op),
+            #[cfg(feature = "complex")]
+            (first @ Value::Complex(_), second) | (first, second @ Value::Complex(_)) => {
+                complex_binary(first.to_complex(), second.to_complex(), op)
+            }
+            (first @ Value::Interval(_), second) | (first, second @ Value::Interval(_)) => {
+                first.to_interval().binary(op, second.to_interval()).map(Value::Interval)
+            }
+            (first @ Value::Uncertain(_), second) | (first, second @ Value::Uncertain(_)) => {
+                first.to_uncertain().binary(op, second.to_uncertain()).map(Value::Uncertain)
+            }
+            (first, second) => compute_result(first.to_f64(), second.to_f64(), op).map(Value::Float),
+        }
+    }
+
+    pub(crate) fn negate(self) -> Value {
+        match self {
+            Value::Integer(n) => match n.checked_neg() {
+                Some(n) => Value::Integer(n),
+                #[cfg(feature = "bigint")]
+                None => big(-BigInt::from(n)),
+                #[cfg(not(feature = "bigint"))]
+                None => Value::Float(-(n as f64)),
+            },
+            Value::Float(n) => Value::Float(-n),
+            Value::Interval(interval) => Value::Interval(interval.negate()),
+            Value::Uncertain(n) => Value::Uncertain(n.negate()),
+            #[cfg(feature = "bigint")]
+            Value::BigInt(n) => big(-n),
+            #[cfg(feature = "decimal")]
+            Value::Decimal(n) => Value::Decimal(-n),
+            #[cfg(feature = "complex")]
+            Value::Complex(z) => Value::Complex(-z),
+        }
+    }
+
+    pub(crate) fn factorial(self) -> Result<Value, CalcError> {
+        let result = match self {
+            Value::Integer(n) if n >= 0 => match (2..=n).try_fold(1i64, |result, i| result.checked_mul(i)) {
+                Some(result) => Some(Value::Integer(result)),
+                #[cfg(feature = "bigint")]
+                None => Some(Value::BigInt((2..=n).fold(BigInt::from(1), |result, i| result * i))),
+                #[cfg(not(feature = "bigint"))]
+                None => None,
+            },
+            #[cfg(feature = "decimal")]
+            Value::Decimal(n) if n >= Decimal::zero() && n.fract().is_zero() => n.to_i64()
+                .and_then(|n| (2..=n).try_fold(Decimal::ONE, |result, i| result.checked_mul(Decimal::from(i))))
+                .map(Value::Decimal),
+            _ => None,
+        };
+        match result {
+            Some(result) => Ok(result),
+            None => factorial(self.to_f64()).map(Value::Float),
+        }
+    }
+}
+
+/// 1 or 0 for whether the comparison `first op second` holds, None if `op`
+/// isn't a comparison.
+fn comparison<T: PartialOrd>(first: T, second: T, op: &str) -> Option<Value> {
+    BinaryOp::from_symbol(op)
+        .and_then(|op| op.compare(first, second))
+        .map(|holds| Value::Integer(holds as i64))
+}
+
+/// `first op second`, None if it overflows or isn't an integer.
+fn checked(first: i64, second: i64, op: &str) -> Option<i64> {
+    match op {
+        "+" => first.checked_add(second),
+        "-" => first.checked_sub(second),
+        "*" => first.checked_mul(second),
+        "/" => first.checked_div(second),
+        "%" => first.checked_rem(second),
+        "^" if second >= 0 => first.checked_pow(second.min(u32::MAX as i64) as u32),
+        "&" => Some(first & second),
+        "|" => Some(first | second),
+        "xor" => Some(first ^ second),
+        _ => None,
+    }
+}
+
+fn integer(first: i64, second: i64, op: &str) -> Result<Value, CalcError> {
+    if let Some(result) = comparison(first, second, op) {
+        return Ok(result);
+    }
+    if (op == "/" || op == "%") && second == 0 {
+        return Err(CalcError::DivisionByZero);
+    }
+    match checked(first, second, op) {
+        Some(result) => Ok(Value::Integer(result)),
+        #[cfg(feature = "bigint")]
+        None if op != "^" || second >= 0 => big_integer(BigInt::from(first), BigInt::from(second), op),
+        None => compute_result(first as f64, second as f64, op).map(Value::Float),
+    }
+}
+
+/// Like `Value::binary` for two integers, with `overflow` deciding what
+/// happens to results that don't fit in an `i64`.
+pub(crate) fn fixed_integer(first: i64, second: i64, op: &str, overflow: Overflow) -> Result<Value, CalcError> {
+    // negative exponents give floats rather than overflowing
+    if overflow == Overflow::Promote || op == "^" && second < 0 || !"+-*/%^".contains(op) {
+        return Value::Integer(first).binary(op, Value::Integer(second));
+    }
+    if (op == "/" || op == "%") && second == 0 {
+        return Err(CalcError::DivisionByZero);
+    }
+    if let Some(result) = checked(first, second, op) {
+        return Ok(Value::Integer(result));
+    }
+    let exponent = second.min(u32::MAX as i64) as u32;
+    let result = match (overflow, op) {
+        (Overflow::Wrap, "+") => first.wrapping_add(second),
+        (Overflow::Wrap, "-") => first.wrapping_sub(second),
+        (Overflow::Wrap, "*") => first.wrapping_mul(second),
+        (Overflow::Wrap, "/") => first.wrapping_div(second),
+        (Overflow::Wrap, "%") => first.wrapping_rem(second),
+        (Overflow::Wrap, _) => first.wrapping_pow(exponent),
+        (Overflow::Saturate, "+") => first.saturating_add(second),
+        (Overflow::Saturate, "-") => first.saturating_sub(second),
+        (Overflow::Saturate, "*") => first.saturating_mul(second),
+        (Overflow::Saturate, "/") => first.saturating_div(second),
+        // only `i64::MIN % -1` overflows, and it is exactly 0
+        (Overflow::Saturate, "%") => 0,
+        (Overflow::Saturate, _) => first.saturating_pow(exponent),
+        _ => return Err(CalcError::Overflow),
+    };
+    Ok(Value::Integer(result))
+}
+
+/// `-n` and `n!` for an integer, overflowing according to `overflow`.
+pub(crate) fn fixed_negate(n: i64, overflow: Overflow) -> Result<Value, CalcError> {
+    match overflow {
+        Overflow::Promote => Ok(Value::Integer(n).negate()),
+        Overflow::Error => n.checked_neg().map(Value::Integer).ok_or(CalcError::Overflow),
+        Overflow::Wrap => Ok(Value::Integer(n.wrapping_neg())),
+        Overflow::Saturate => Ok(Value::Integer(n.saturating_neg())),
+    }
+}
+
+pub(crate) fn fixed_factorial(n: i64, overflow: Overflow) -> Result<Value, CalcError> {
+    if overflow == Overflow::Promote || n < 0 {
+        return Value::Integer(n).factorial();
+    }
+    let result = match overflow {
+        Overflow::Wrap => (2..=n).fold(1i64, |result, i| result.wrapping_mul(i)),
+        Overflow::Saturate => (2..=n).fold(1i64, |result, i| result.saturating_mul(i)),
+        _ => (2..=n).try_fold(1i64, |result, i| result.checked_mul(i)).ok_or(CalcError::Overflow)?,
+    };
+    Ok(Value::Integer(result))
+}
+
+/// Keeps a big integer as an `Integer` if it fits.
+#[cfg(feature = "bigint")]
+fn big(n: BigInt) -> Value {
+    n.to_i64().map_or(Value::BigInt(n), Value::Integer)
+}
+
+#[cfg(feature = "bigint")]
+fn big_integer(first: BigInt, second: BigInt, op: &str) -> Result<Value, CalcError> {
+    if let Some(result) = comparison(&first, &second, op) {
+        return Ok(result);
+    }
+    if (op == "/" || op == "%") && second.is_zero() {
+        return Err(CalcError::DivisionByZero);
+    }
+    match op {
+        "+" => Ok(big(first + second)),
+        "-" => Ok(big(first - second)),
+        "*" => Ok(big(first * second)),
+        "/" => Ok(big(first / second)),
+        "%" => Ok(big(first % second)),
+        "&" => Ok(big(first & second)),
+        "|" => Ok(big(first | second)),
+        "xor" => Ok(big(first ^ second)),
+        "^" => match second.to_u32() {
+            Some(exponent) => Ok(big(first.pow(exponent))),
+            None => compute_result(Value::BigInt(first).to_f64(), Value::BigInt(second).to_f64(), op).map(Value::Float),
+        },
+        _ => Err(CalcError::UnknownOperator(op.to_string())),
+    }
+}
+
+#[cfg(feature = "complex")]
+fn complex_binary(first: Complex64, second: Complex64, op: &str) -> Result<Value, CalcError> {
+    match op {
+        "+" => Ok(Value::Complex(first + second)),
+        "-" => Ok(Value::Complex(first - second)),
+        "*" => Ok(Value::Complex(first * second)),
+        "/" => Ok(Value::Complex(first / second)),
+        "%" => Ok(Value::Complex(first % second)),
+        // whole exponents are multiplied out, `i ^ 2` is exactly -1
+        "^" if second.im == 0.0 && second.re.fract() == 0.0 && second.re.abs() <= i32::MAX as f64 => {
+            Ok(Value::Complex(first.powi(second.re as i32)))
+        }
+        "^" => Ok(Value::Complex(first.powc(second))),
+        // complex numbers aren't ordered
+        "==" => Ok(Value::Integer((first == second) as i64)),
+        "!=" => Ok(Value::Integer((first != second) as i64)),
+        _ => Err(CalcError::UnknownOperator(op.to_string())),
+    }
+}
+
+/// Builds the interval for `[lo, hi]` or calls the interval version of a
+/// built-in function. None if it isn't `interval` and none of the `arguments`
+/// is an interval, or the function doesn't have an interval version.
+pub(crate) fn interval_function(name: &str, arguments: &[Value]) -> Option<Result<Value, CalcError>> {
+    if name == "interval" && arguments.len() == 2 {
+        let lo = arguments[0].to_interval().lo();
+        let hi = arguments[1].to_interval().hi();
+        return Some(Interval::new(lo, hi).map(Value::Interval));
+    }
+    let interval = match *arguments {
+        [Value::Interval(interval)] => interval,
+        _ => return None,
+    };
+    let result = match name {
+        "sqrt" if interval.lo() >= 0.0 => interval.map_increasing(f64::sqrt),
+        "ln" if interval.lo() > 0.0 => interval.map_increasing(f64::ln),
+        "log" if interval.lo() > 0.0 => interval.map_increasing(f64::log10),
+        "exp" => interval.map_increasing(f64::exp),
+        "abs" => interval.abs(),
+        _ => return None,
+    };
+    Some(Ok(Value::Interval(result)))
+}
+
+/// Calls the uncertain version of a built-in function, None if `name` doesn't
+/// have one or its argument isn't uncertain.
+pub(crate) fn uncertain_function(name: &str, arguments: &[Value]) -> Option<Value> {
+    let n = match *arguments {
+        [Value::Uncertain(n)] => n,
+        _ => return None,
+    };
+    let result = match name {
+        "sqrt" => n.map(f64::sqrt, |x| 0.5 / x.sqrt()),
+        "exp" => n.map(f64::exp, f64::exp),
+        "ln" => n.map(f64::ln, |x| 1.0 / x),
+        "log" => n.map(f64::log10, |x| 1.0 / (x * ::std::f64::consts::LN_10)),
+        "sin" => n.map(f64::sin, f64::cos),
+        "cos" => n.map(f64::cos, |x| -x.sin()),
+        "tan" => n.map(f64::tan, |x| 1.0 / (x.cos() * x.cos())),
+        "abs" => n.map(f64::abs, f64::signum),
+        _ => return None,
+    };
+    Some(Value::Uncertain(result))
+}
+
+/// Calls the complex version of a built-in function, None if `name` doesn't
+/// have one or none of the `arguments` is complex.
+#[cfg(feature = "complex")]
+pub(crate) fn complex_function(name: &str, arguments: &[Value]) -> Option<Value> {
+    if !arguments.iter().any(|argument| matches!(*argument, Value::Complex(_))) {
+        return None;
+    }
+    let z: Vec<Complex64> = arguments.iter().map(Value::to_complex).collect();
+    let result = match (name, z.len()) {
+        ("sqrt", 1) => z[0].sqrt(),
+        ("sin", 1) => z[0].sin(),
+        ("cos", 1) => z[0].cos(),
+        ("tan", 1) => z[0].tan(),
+        ("ln", 1) => z[0].ln(),
+        ("log", 1) => z[0].log10(),
+        ("exp", 1) => z[0].exp(),
+        ("conj", 1) => z[0].conj(),
+        ("pow", 2) => z[0].powc(z[1]),
+        ("abs", 1) => return Some(Value::Float(z[0].norm())),
+        ("arg", 1) => return Some(Value::Float(z[0].arg())),
+        ("re", 1) => return Some(Value::Float(z[0].re)),
+        ("im", 1) => return Some(Value::Float(z[0].im)),
+        _ => return None,
+    };
+    Some(Value::Complex(result))
+}
+
+#[cfg(feature = "decimal")]
+fn decimal(first: Decimal, second: Decimal, op: &str) -> Result<Value, CalcError> {
+    if let Some(result) = comparison(first, second, op) {
+        return Ok(result);
+    }
+    if (op == "/" || op == "%") && second.is_zero() {
+        return Err(CalcError::DivisionByZero);
+    }
+    let result = match op {
+        "+" => first.checked_add(second),
+        "-" => first.checked_sub(second),
+        "*" => first.checked_mul(second),
+        "/" => first.checked_div(second),
+        "%" => first.checked_rem(second),
+        "^" if second.fract().is_zero() => second.to_i64().and_then(|exponent| decimal_pow(first, exponent)),
+        "&" | "|" | "xor" if first.fract().is_zero() && second.fract().is_zero() => match (first.to_i64(), second.to_i64()) {
+            (Some(first), Some(second)) => checked(first, second, op).map(Decimal::from),
+            _ => None,
+        },
+        _ => None,
+    };
+    match result {
+        Some(result) => Ok(Value::Decimal(result)),
+        None => Value::Decimal(first).binary(op, Value::Float(second.to_f64().unwrap_or(f64::NAN))),
+    }
+}
+
+/// Exponentiation by squaring, None if the result doesn't fit.
+#[cfg(feature = "decimal")]
+fn decimal_pow(base: Decimal, exponent: i64) -> Option<Decimal> {
+    if exponent < 0 {
+        return Decimal::ONE.checked_div(decimal_pow(base, exponent.checked_neg()?)?);
+    }
+    let mut result = Decimal::ONE;
+    let mut base = base;
+    let mut exponent = exponent;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result.checked_mul(base)?;
+        }
+        exponent >>= 1;
+        if exponent > 0 {
+            base = base.checked_mul(base)?;
+        }
+    }
+    Some(result)
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Self {
+        Value::Integer(n)
+    }
+}
+
+impl From<Uncertain> for Value {
+    fn from(n: Uncertain) -> Self {
+        Value::Uncertain(n)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Float(n)
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl From<BigInt> for Value {
+    fn from(n: BigInt) -> Self {
+        big(n)
+    }
+}
+
+#[cfg(feature = "complex")]
+impl From<Complex64> for Value {
+    fn from(z: Complex64) -> Self {
+        Value::Complex(z)
+    }
+}
+
+#[cfg(feature = "decimal")]
+impl From<Decimal> for Value {
+    fn from(n: Decimal) -> Self {
+        Value::Decimal(n)
+    }
+}
+
+/// A precision, e.g. `format!("{:.2}", value)`, is the number of decimal
+/// places of everything that isn't an integer.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self, f.precision()) {
+            (&Value::Integer(n), _) => write!(f, "{}", n),
+            (&Value::Float(n), Some(precision)) => write!(f, "{:.*}", precision, n),
+            (&Value::Float(n), None) => write!(f, "{}", n),
+            (&Value::Interval(interval), _) => interval.fmt(f),
+            (&Value::Uncertain(n), _) => n.fmt(f),
+            #[cfg(feature = "bigint")]
+            (Value::BigInt(n), _) => write!(f, "{}", n),
+            #[cfg(feature = "decimal")]
+            (&Value::Decimal(n), Some(precision)) => write!(f, "{:.*}", precision, n),
+            // `1.50 * 2` is shown as `3` rather than `3.00`
+            #[cfg(feature = "decimal")]
+            (&Value::Decimal(n), None) => write!(f, "{}", n.normalize()),
+            #[cfg(feature = "complex")]
+            (&Value::Complex(z), precision) => {
+                let number = |x: f64| match precision {
+                    Some(precision) => format!("{:.*}", precision, x),
+                    None => x.to_string(),
+                };
+                // `5 + 5i`, `-2i`, `1 - i` or just `5`
+                if z.im == 0.0 {
+                    return write!(f, "{}", number(z.re));
+                }
+                let imaginary = if z.im.abs() == 1.0 { "i".to_string() } else { format!("{}i", number(z.im.abs())) };
+                let sign = if z.im < 0.0 { "-" } else { "+" };
+                if z.re == 0.0 {
+                    write!(f, "{}{}", if z.im < 0.0 { "-" } else { "" }, imaginary)
+                } else {
+                    write!(f, "{} {} {}", number(z.re), sign, imaginary)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integers_stay_exact() {
+        assert_eq!(Value::Integer(7).binary("/", Value::Integer(2)), Ok(Value::Integer(3)));
+        assert_eq!(Value::Integer(-7).binary("%", Value::Integer(2)), Ok(Value::Integer(-1)));
+        assert_eq!(Value::Integer(3).binary("^", Value::Integer(39)), Ok(Value::Integer(4052555153018976267)));
+        assert_eq!(Value::Integer(20).factorial(), Ok(Value::Integer(2432902008176640000)));
+        assert_eq!(Value::Integer(1).binary("/", Value::Integer(0)), Err(CalcError::DivisionByZero));
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(Value::Integer(3).binary("<=", Value::Integer(3)), Ok(Value::Integer(1)));
+        assert_eq!(Value::Integer(i64::MAX).binary(">", Value::Integer(i64::MAX - 1)), Ok(Value::Integer(1)));
+        assert_eq!(Value::Integer(1).binary("==", Value::Float(1.5)), Ok(Value::Float(0.0)));
+        assert!(Value::Interval(Interval::point(1.0)).binary("<", Value::Integer(2)).is_err());
+    }
+
+    #[test]
+    fn bitwise_operators_take_integers() {
+        assert_eq!(Value::Integer(0b1100).binary("&", Value::Integer(0b1010)), Ok(Value::Integer(0b1000)));
+        assert_eq!(Value::Integer(0b1100).binary("xor", Value::Integer(0b1010)), Ok(Value::Integer(0b0110)));
+        assert_eq!(Value::Integer(-8).binary("|", Value::Float(3.0)), Ok(Value::Float(-5.0)));
+        assert_eq!(Value::Integer(1).binary("&", Value::Float(0.5)), Err(CalcError::NotAnInteger(0.5)));
+        assert_eq!(Value::Integer(i64::MIN).bit_not(), Ok(Value::Integer(i64::MAX)));
+        assert_eq!(Value::Float(f64::NAN).bit_not().unwrap_err().to_string(), "Bitwise operators are only defined for integers: NaN");
+        assert!(Value::Interval(Interval::point(1.0)).bit_not().is_err());
+    }
+
+    #[test]
+    fn promotes_to_float() {
+        assert_eq!(Value::Integer(1).binary("/", Value::Float(2.0)), Ok(Value::Float(0.5)));
+        assert_eq!(Value::Integer(2).binary("^", Value::Integer(-1)), Ok(Value::Float(0.5)));
+    }
+
+    #[test]
+    fn overflow_policies() {
+        let max = i64::MAX;
+        assert_eq!(fixed_integer(max, 1, "+", Overflow::Error), Err(CalcError::Overflow));
+        assert_eq!(fixed_integer(max, 1, "+", Overflow::Wrap), Ok(Value::Integer(i64::MIN)));
+        assert_eq!(fixed_integer(max, 2, "*", Overflow::Saturate), Ok(Value::Integer(max)));
+        assert_eq!(fixed_integer(-2, 63, "^", Overflow::Error), Ok(Value::Integer(i64::MIN)));
+        assert_eq!(fixed_integer(-2, 65, "^", Overflow::Saturate), Ok(Value::Integer(i64::MIN)));
+        assert_eq!(fixed_integer(i64::MIN, -1, "/", Overflow::Wrap), Ok(Value::Integer(i64::MIN)));
+        assert_eq!(fixed_integer(i64::MIN, -1, "%", Overflow::Saturate), Ok(Value::Integer(0)));
+        assert_eq!(fixed_integer(2, -1, "^", Overflow::Error), Ok(Value::Float(0.5)));
+        assert_eq!(fixed_integer(1, 0, "/", Overflow::Wrap), Err(CalcError::DivisionByZero));
+        assert_eq!(fixed_negate(i64::MIN, Overflow::Saturate), Ok(Value::Integer(max)));
+        assert_eq!(fixed_negate(i64::MIN, Overflow::Error), Err(CalcError::Overflow));
+        assert_eq!(fixed_factorial(21, Overflow::Error), Err(CalcError::Overflow));
+        assert_eq!(fixed_factorial(21, Overflow::Wrap), Ok(Value::Integer(-4249290049419214848)));
+        assert_eq!(fixed_factorial(20, Overflow::Saturate), Value::Integer(20).factorial());
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn overflow_promotes_to_float() {
+        assert_eq!(Value::Integer(i64::MAX).binary("+", Value::Integer(1)), Ok(Value::Float(i64::MAX as f64 + 1.0)));
+        assert_eq!(Value::Integer(i64::MIN).negate(), Value::Float(-(i64::MIN as f64)));
+        assert_eq!(Value::Integer(21).factorial(), Ok(Value::Float(51090942171709440000.0)));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn overflow_promotes_to_bigint() {
+        let max = Value::Integer(i64::MAX);
+        let bigger = max.clone().binary("+", Value::Integer(1)).unwrap();
+        assert_eq!(bigger.to_string(), "9223372036854775808");
+        assert_eq!(bigger.binary("-", Value::Integer(1)), Ok(max));
+        assert_eq!(Value::Integer(i64::MIN).negate().to_string(), "9223372036854775808");
+        assert_eq!(Value::Integer(21).factorial().unwrap().to_string(), "51090942171709440000");
+        let power = Value::Integer(2).binary("^", Value::Integer(200)).unwrap();
+        assert_eq!(power.to_string(), "1606938044258990275541962092341162602522202993782792835301376");
+        assert_eq!(power.binary("/", Value::Integer(2).binary("^", Value::Integer(199)).unwrap()), Ok(Value::Integer(2)));
+    }
+
+    #[test]
+    fn uncertainty_is_sticky() {
+        let measured = Value::Integer(10).binary("±", Value::Float(0.3)).unwrap();
+        assert_eq!(measured, Value::Uncertain(Uncertain::new(10.0, 0.3)));
+        assert_eq!(measured.clone().binary("*", Value::Integer(2)), Ok(Value::Uncertain(Uncertain::new(20.0, 0.6))));
+        assert_eq!(measured.clone().negate().to_string(), "-10 ± 0.3");
+        assert_eq!(uncertain_function("sqrt", &[Value::Uncertain(Uncertain::new(4.0, 0.4))]),
+                   Some(Value::Uncertain(Uncertain::new(2.0, 0.1))));
+        assert_eq!(uncertain_function("sqrt", &[Value::Float(4.0)]), None);
+        assert!(measured.to_f64().is_nan());
+    }
+
+    #[cfg(feature = "complex")]
+    #[test]
+    fn complex_numbers() {
+        let a = Value::Complex(Complex64::new(1.0, 2.0));
+        let b = Value::Complex(Complex64::new(3.0, -1.0));
+        assert_eq!(a.clone().binary("*", b).unwrap().to_string(), "5 + 5i");
+        assert_eq!(a.clone().binary("-", Value::Integer(1)).unwrap().to_string(), "2i");
+        assert_eq!(Value::Complex(Complex64::new(1.0, -1.0)).to_string(), "1 - i");
+        assert_eq!(a.clone().binary("*", Value::from(Complex64::new(1.0, -2.0))).unwrap().to_string(), "5");
+        assert_eq!(complex_function("conj", std::slice::from_ref(&a)), Some(Value::Complex(Complex64::new(1.0, -2.0))));
+        assert_eq!(complex_function("abs", &[Value::Complex(Complex64::new(3.0, 4.0))]), Some(Value::Float(5.0)));
+        assert_eq!(complex_function("abs", &[Value::Float(-3.0)]), None);
+        assert!(a.to_f64().is_nan());
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn decimals_stay_exact() {
+        let tenth = Value::Decimal(Decimal::new(1, 1));
+        let sum = tenth.clone().binary("+", Value::Decimal(Decimal::new(2, 1))).unwrap();
+        assert_eq!(sum, Value::Decimal(Decimal::new(3, 1)));
+        assert_eq!(Value::Decimal(Decimal::new(150, 2)).binary("*", Value::Integer(2)).unwrap().to_string(), "3");
+        assert_eq!(Value::Integer(1).binary("/", Value::Decimal(Decimal::from(4))), Ok(Value::Decimal(Decimal::new(25, 2))));
+        assert_eq!(tenth.clone().binary("^", Value::Integer(-2)), Ok(Value::Decimal(Decimal::from(100))));
+        assert_eq!(tenth.binary("^", Value::Float(0.5)), Ok(Value::Float(0.1f64.sqrt())));
+        assert_eq!(Value::Decimal(Decimal::from(5)).factorial(), Ok(Value::Decimal(Decimal::from(120))));
+        assert_eq!(Value::Decimal(Decimal::from(6)).binary("&", Value::Integer(3)), Ok(Value::Decimal(Decimal::from(2))));
+        assert_eq!(Value::Decimal(Decimal::from(6)).bit_not(), Ok(Value::Decimal(Decimal::from(-7))));
+        assert_eq!(Value::Decimal(Decimal::new(1, 1)).binary("|", Value::Integer(1)), Err(CalcError::NotAnInteger(0.1)));
+    }
+
+    #[test]
+    fn precision_is_decimal_places() {
+        assert_eq!(format!("{:.2}", Value::Float(2.0 / 3.0)), "0.67");
+        assert_eq!(format!("{:.3}", Value::Integer(7)), "7");
+        let interval = Value::Interval(Interval::new(1.0 / 3.0, 0.5).unwrap());
+        assert_eq!(format!("{:.1}", interval), "[0.3, 0.5]");
+        assert_eq!(format!("{:.1}", Value::Uncertain(Uncertain::new(10.04, 0.25))), "10.0 ± 0.2");
+        assert_eq!(Value::Float(0.1).to_string(), "0.1");
+    }
+}
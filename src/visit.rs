@@ -0,0 +1,204 @@
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+use {BinaryOp, Expr, UnaryOp};
+
+/// Goes through an `Expr` without changing it, for analyses such as
+/// collecting names. Every method does nothing but go on to the operands by
+/// default, so only the ones for the nodes of interest need implementing.
+///
+/// # Example
+/// ```
+/// use infix_calculator::{Expr, Visitor};
+///
+/// /// Counts the calls, as a rough cost of evaluating an expression.
+/// struct Calls(usize);
+///
+/// impl Visitor for Calls {
+///     fn visit_call(&mut self, _name: &str, arguments: &[Expr]) {
+///         self.0 += 1;
+///         for argument in arguments {
+///             self.visit_expr(argument);
+///         }
+///     }
+/// }
+///
+/// let mut calls = Calls(0);
+/// calls.visit_expr(&Expr::parse("sin(x) ^ 2 + cos(sqrt(x)) ^ 2").unwrap());
+/// assert_eq!(calls.0, 3);
+/// ```
+pub trait Visitor {
+    /// Called for every node, `walk`s into it by default.
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk(self, expr)
+    }
+
+    fn visit_number(&mut self, _number: f64) {}
+
+    fn visit_variable(&mut self, _name: &str) {}
+
+    fn visit_unary(&mut self, _op: UnaryOp, operand: &Expr) {
+        self.visit_expr(operand)
+    }
+
+    fn visit_binary(&mut self, _op: BinaryOp, left: &Expr, right: &Expr) {
+        self.visit_expr(left);
+        self.visit_expr(right);
+    }
+
+    fn visit_call(&mut self, _name: &str, arguments: &[Expr]) {
+        for argument in arguments {
+            self.visit_expr(argument);
+        }
+    }
+}
+
+/// Calls the method of `visitor` for the kind of node `expr` is.
+pub fn walk<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match *expr {
+        Expr::Number(number) => visitor.visit_number(number),
+        Expr::Variable(ref name) => visitor.visit_variable(name),
+        Expr::Unary(op, ref operand) => visitor.visit_unary(op, operand),
+        Expr::Binary(op, ref left, ref right) => visitor.visit_binary(op, left, right),
+        Expr::Call(ref name, ref arguments) => visitor.visit_call(name, arguments),
+    }
+}
+
+/// Rebuilds an `Expr` bottom up, for transforms. By default every method
+/// puts the node back together from its transformed operands.
+///
+/// # Example
+/// ```
+/// use infix_calculator::{Expr, Fold};
+///
+/// /// Renames the variable `x` to `t`.
+/// struct Rename;
+///
+/// impl Fold for Rename {
+///     fn fold_variable(&mut self, name: String) -> Expr {
+///         Expr::Variable(if name == "x" { "t".to_string() } else { name })
+///     }
+/// }
+///
+/// let expr = Rename.fold_expr(Expr::parse("x ^ 2 + f(x, y)").unwrap());
+/// assert_eq!(expr.to_string(), "t ^ 2 + f(t, y)");
+/// ```
+pub trait Fold {
+    /// Called for every node, hands it to `fold` by default.
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        fold(self, expr)
+    }
+
+    fn fold_number(&mut self, number: f64) -> Expr {
+        Expr::Number(number)
+    }
+
+    fn fold_variable(&mut self, name: String) -> Expr {
+        Expr::Variable(name)
+    }
+
+    fn fold_unary(&mut self, op: UnaryOp, operand: Expr) -> Expr {
+        Expr::Unary(op, Box::new(self.fold_expr(operand)))
+    }
+
+    fn fold_binary(&mut self, op: BinaryOp, left: Expr, right: Expr) -> Expr {
+        let left = self.fold_expr(left);
+        Expr::Binary(op, Box::new(left), Box::new(self.fold_expr(right)))
+    }
+
+    fn fold_call(&mut self, name: String, arguments: Vec<Expr>) -> Expr {
+        Expr::Call(name, arguments.into_iter().map(|argument| self.fold_expr(argument)).collect())
+    }
+}
+
+/// Calls the method of `folder` for the kind of node `expr` is.
+pub fn fold<F: Fold + ?Sized>(folder: &mut F, expr: Expr) -> Expr {
+    match expr {
+        Expr::Number(number) => folder.fold_number(number),
+        Expr::Variable(name) => folder.fold_variable(name),
+        Expr::Unary(op, operand) => folder.fold_unary(op, *operand),
+        Expr::Binary(op, left, right) => folder.fold_binary(op, *left, *right),
+        Expr::Call(name, arguments) => folder.fold_call(name, arguments),
+    }
+}
+
+/// Collects names in the order they first appear, for `Expr::variables`.
+struct Variables(Vec<String>);
+
+impl Visitor for Variables {
+    fn visit_variable(&mut self, name: &str) {
+        if !self.0.iter().any(|known| known == name) {
+            self.0.push(name.to_string());
+        }
+    }
+}
+
+impl Expr {
+    /// The variables and constants in the expression, each once, in the
+    /// order they first appear.
+    ///
+    /// # Example
+    /// ```
+    /// use infix_calculator::Expr;
+    ///
+    /// assert_eq!(Expr::parse("b * x + a * x ^ 2").unwrap().variables(), vec!["b", "x", "a"]);
+    /// ```
+    pub fn variables(&self) -> Vec<String> {
+        let mut variables = Variables(Vec::new());
+        variables.visit_expr(self);
+        variables.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts the nodes of each kind.
+    #[derive(Default)]
+    struct Count {
+        leaves: usize,
+        operators: usize,
+    }
+
+    impl Visitor for Count {
+        fn visit_expr(&mut self, expr: &Expr) {
+            match *expr {
+                Expr::Number(_) | Expr::Variable(_) => self.leaves += 1,
+                _ => self.operators += 1,
+            }
+            walk(self, expr);
+        }
+    }
+
+    #[test]
+    fn visitor_sees_every_node() {
+        let mut count = Count::default();
+        count.visit_expr(&Expr::parse("-max(1, x!, 2 * y)").unwrap());
+        assert_eq!((count.leaves, count.operators), (4, 4));
+        assert_eq!(Expr::parse("max(pi, 1) * r(pi)").unwrap().variables(), vec!["pi"]);
+    }
+
+    /// Swaps the operands of every addition.
+    struct Swap;
+
+    impl Fold for Swap {
+        fn fold_binary(&mut self, op: BinaryOp, left: Expr, right: Expr) -> Expr {
+            let (left, right) = (self.fold_expr(left), self.fold_expr(right));
+            match op {
+                BinaryOp::Add => Expr::Binary(op, Box::new(right), Box::new(left)),
+                _ => Expr::Binary(op, Box::new(left), Box::new(right)),
+            }
+        }
+    }
+
+    #[test]
+    fn fold_rebuilds_the_tree() {
+        let expr = Expr::parse("(1 + 2) * -f(a + b)").unwrap();
+        assert_eq!(Swap.fold_expr(expr.clone()), Expr::parse("(2 + 1) * -f(b + a)").unwrap());
+
+        struct Identity;
+        impl Fold for Identity {}
+        assert_eq!(Identity.fold_expr(expr.clone()), expr);
+    }
+}
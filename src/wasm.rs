@@ -0,0 +1,35 @@
+//! Bindings for JavaScript with `wasm-bindgen`, e.g. for a calculator in a
+//! web page. Errors are thrown as a JavaScript `Error` with the message of
+//! the `CalcError`.
+
+use wasm_bindgen::prelude::*;
+
+use {CalcError, RPNQueue};
+
+fn js_error(err: CalcError) -> JsValue {
+    JsError::new(&err.to_string()).into()
+}
+
+/// Calculates `input`, see `RPNQueue::from_infix_string` for the syntax.
+#[wasm_bindgen]
+pub fn evaluate(input: &str) -> Result<f64, JsValue> {
+    RPNQueue::from_infix_string(input).and_then(|queue| queue.calculate()).map_err(js_error)
+}
+
+/// `input` in Reverse Polish Notation, written like `RPNQueue`'s `Display`.
+#[wasm_bindgen]
+pub fn to_rpn(input: &str) -> Result<String, JsValue> {
+    RPNQueue::from_infix_string(input).map(|queue| queue.to_string()).map_err(js_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // errors need JavaScript, so only results can be checked outside a browser
+    #[test]
+    fn results() {
+        assert_eq!(evaluate("2 ^ 3 + 1").ok(), Some(9.0));
+        assert_eq!(to_rpn("(1 + 2) * x").ok(), Some("1 2 + x *".to_string()));
+    }
+}